@@ -237,6 +237,7 @@ fn simple_type_dump_list(simple_type: &str) -> &str {
         "f64" => "dump_float",
         "Value" => "dump_value",
         "Field" => "dump_field",
+        "usize" => "dump_usize",
         _ => panic!("Unknown type"),
     }
 }
@@ -264,23 +265,26 @@ fn type_to_value(
                 let native_type = Ident::new(type_name, ty.span());
                 let mutator = simple_type_to_mutator(type_name, &allowed_values_name);
                 let value_type = simple_type_to_value(type_name);
+                // Restricted values are listed in the signature, so that they can be shown in
+                // the help and used for tab completion.
+                let value_description = match &allowed_values {
+                    None => simple_type_to_value_description(type_name)
+                        .to_string()
+                        .to_lowercase(),
+                    Some(literals) => format!(
+                        "({})",
+                        literals
+                            .iter()
+                            .map(|l| l.to_string())
+                            .collect::<Vec<_>>()
+                            .join("|")
+                    ),
+                };
                 Ok(TypeData {
                     signature: if default.is_none() {
-                        format!(
-                            "{}={}",
-                            name.to_string(),
-                            simple_type_to_value_description(type_name)
-                                .to_string()
-                                .to_lowercase()
-                        )
+                        format!("{}={}", name.to_string(), value_description)
                     } else {
-                        format!(
-                            "[{}={}]",
-                            name.to_string(),
-                            simple_type_to_value_description(type_name)
-                                .to_string()
-                                .to_lowercase()
-                        )
+                        format!("[{}={}]", name.to_string(), value_description)
                     },
                     initialize: match allowed_values {
                         None => quote! { let mut #name = None; },
@@ -7,6 +7,7 @@ use crate::lang::value::Value;
 use crate::lang::value::ValueDefinition;
 use ordered_map::OrderedMap;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone)]
 pub enum ArgumentType {
@@ -76,6 +77,21 @@ impl ArgumentDefinition {
     }
 }
 
+impl Display for ArgumentDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.argument_type {
+            ArgumentType::Some(name) => {
+                f.write_str(name)?;
+                f.write_str("=")?;
+            }
+            ArgumentType::None => {}
+            ArgumentType::ArgumentList => f.write_str("@")?,
+            ArgumentType::ArgumentDict => f.write_str("@@")?,
+        }
+        self.value.fmt(f)
+    }
+}
+
 pub type Argument = BaseArgument<Option<String>, Value>;
 
 impl Argument {
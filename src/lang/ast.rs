@@ -4,6 +4,7 @@ use crate::lang::command_invocation::CommandInvocation;
 use crate::lang::errors::{error, to_crush_error, CrushResult};
 use crate::lang::job::Job;
 use crate::lang::scope::Scope;
+use crate::lang::source::Source;
 use crate::lang::value::{Value, ValueDefinition, ValueType};
 use crate::util::glob::Glob;
 use regex::Regex;
@@ -18,10 +19,18 @@ impl JobListNode {
     pub fn generate(&self, env: &Scope) -> CrushResult<Vec<Job>> {
         self.jobs.iter().map(|j| j.generate(env)).collect()
     }
+
+    /// Mark the last job in the list as a background job.
+    pub fn background(&mut self) {
+        if let Some(j) = self.jobs.last_mut() {
+            j.background = true;
+        }
+    }
 }
 
 pub struct JobNode {
     pub commands: Vec<CommandNode>,
+    pub background: bool,
 }
 
 impl JobNode {
@@ -31,19 +40,26 @@ impl JobNode {
                 .iter()
                 .map(|c| c.generate(env))
                 .collect::<CrushResult<Vec<CommandInvocation>>>()?,
+            self.background,
         ))
     }
 }
 
 pub struct CommandNode {
     pub expressions: Vec<Node>,
+    pub source: Option<Source>,
 }
 
 impl CommandNode {
     pub fn generate(&self, env: &Scope) -> CrushResult<CommandInvocation> {
+        self.generate_invocation(env)
+            .map_err(|e| e.with_source(&self.source))
+    }
+
+    fn generate_invocation(&self, env: &Scope) -> CrushResult<CommandInvocation> {
         if let Some(c) = self.expressions[0].generate_standalone(env)? {
             if self.expressions.len() == 1 {
-                Ok(c)
+                Ok(c.with_source(&self.source))
             } else {
                 error("Stray arguments")
             }
@@ -53,7 +69,7 @@ impl CommandNode {
                 .iter()
                 .map(|e| e.generate_argument(env))
                 .collect::<CrushResult<Vec<ArgumentDefinition>>>()?;
-            Ok(CommandInvocation::new(cmd.unnamed_value()?, arguments))
+            Ok(CommandInvocation::new(cmd.unnamed_value()?, arguments).with_source(&self.source))
         }
     }
 }
@@ -87,7 +103,7 @@ fn propose_name(name: &str, v: ValueDefinition) -> ValueDefinition {
         }
         ValueDefinition::JobDefinition(d) => ValueDefinition::JobDefinition(d),
         o => {
-            let j = Job::new(vec![CommandInvocation::new(o, vec![])]);
+            let j = Job::new(vec![CommandInvocation::new(o, vec![])], false);
             ValueDefinition::JobDefinition(j)
         }
     }
@@ -115,11 +131,11 @@ impl Node {
             | Node::Term(_, _, _)
             | Node::Factor(_, _, _) => ValueDefinition::JobDefinition(Job::new(vec![self
                 .generate_standalone(env)?
-                .unwrap()])),
+                .unwrap()], false)),
             Node::Unary(op, r) => match op.deref() {
                 "neg" | "not" | "typeof" => ValueDefinition::JobDefinition(Job::new(vec![self
                     .generate_standalone(env)?
-                    .unwrap()])),
+                    .unwrap()], false)),
                 "@" => {
                     return Ok(ArgumentDefinition::list(
                         r.generate_argument(env)?.unnamed_value()?,
@@ -386,9 +402,11 @@ fn simple_substitution(cmd: Vec<Node>) -> Box<Node> {
             JobNode {
                 commands: vec![
                     CommandNode {
-                        expressions: cmd
+                        expressions: cmd,
+                        source: None,
                     }
-                ]
+                ],
+                background: false,
             }
         )
     )
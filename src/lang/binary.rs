@@ -49,9 +49,10 @@ impl std::io::Read for ChannelReader {
                     self.buff = None;
                     Ok(res)
                 } else {
-                    dst.write_all(src)?;
-                    self.buff = Some(Box::from(&src[dst.len()..]));
-                    Ok(dst.len())
+                    let len = dst.len();
+                    dst.copy_from_slice(&src[..len]);
+                    self.buff = Some(Box::from(&src[len..]));
+                    Ok(len)
                 }
             }
         }
@@ -127,6 +128,18 @@ impl dyn BinaryReader {
         }
     }
 
+    pub fn concat(
+        mut readers: Vec<Box<dyn BinaryReader + Send + Sync>>,
+    ) -> Box<dyn BinaryReader + Send + Sync> {
+        if readers.len() == 1 {
+            readers.remove(0)
+        } else {
+            Box::from(MultiReader {
+                inner: VecDeque::from(readers),
+            })
+        }
+    }
+
     pub fn vec(vec: &Vec<u8>) -> Box<dyn BinaryReader + Send + Sync> {
         Box::from(VecReader {
             vec: vec.clone(),
@@ -213,3 +226,25 @@ impl Debug for VecReader {
         f.write_str("<vec reader>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_reads_into_small_buffers() {
+        let (mut writer, mut reader) = binary_channel();
+        writer.write_all(b"abcdefghij").unwrap();
+        drop(writer);
+        let mut buf = [0u8; 4];
+        let mut res = Vec::new();
+        loop {
+            let len = reader.read(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            res.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(res, b"abcdefghij");
+    }
+}
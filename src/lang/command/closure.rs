@@ -6,6 +6,7 @@ use crate::lang::errors::{argument_error, error, mandate, CrushResult};
 use crate::lang::execution_context::{CompileContext, CommandContext, JobContext};
 use crate::lang::help::Help;
 use crate::lang::job::Job;
+use crate::lang::jobs;
 use crate::lang::list::List;
 use crate::lang::scope::Scope;
 use crate::lang::serialization::model;
@@ -49,17 +50,28 @@ impl CrushCommand for Closure {
             } else {
                 empty_channel()
             };
-            let output = if last {
+            let output = if !last {
+                black_hole()
+            } else if job_definition.is_background() {
+                context.output.without_terminal()
+            } else {
                 context.output.clone()
+            };
+            let printer = if job_definition.is_background() {
+                context.printer.background()
             } else {
-                black_hole()
+                context.printer.clone()
             };
-            let _job = job_definition.invoke(JobContext::new(
+            let job = job_definition.invoke(JobContext::new(
                 input,
                 output,
                 env.clone(),
-                context.printer.clone(),
+                printer.clone(),
+                context.background || job_definition.is_background(),
             ))?;
+            if job_definition.is_background() {
+                jobs::spawn(job_definition.to_string(), job, &printer)?;
+            }
             //            job.join(&context.printer);
             if env.is_stopped() {
                 return Ok(());
@@ -208,6 +220,7 @@ impl<'a> ClosureSerializer<'a> {
         for c in job.commands() {
             s.commands.push(self.command(c)?);
         }
+        s.background = job.is_background();
         Ok(s)
     }
 
@@ -371,6 +384,7 @@ impl<'a> ClosureDeserializer<'a> {
                 .iter()
                 .map(|c| self.command(c))
                 .collect::<CrushResult<Vec<_>>>()?,
+            s.background,
         ))
     }
 
@@ -438,6 +452,7 @@ impl<'a> ClosureDeserializer<'a> {
                             .iter()
                             .map(|c| self.command(c))
                             .collect::<CrushResult<Vec<_>>>()?,
+                        j.background,
                     ))
                 }
                 model::value_definition::ValueDefinition::Label(s) => {
@@ -43,7 +43,7 @@ impl OutputType {
     }
 }
 
-pub trait CrushCommand: Help {
+pub trait CrushCommand: Help + Display {
     fn invoke(&self, context: CommandContext) -> CrushResult<()>;
     fn can_block(&self, arguments: &[ArgumentDefinition], context: &mut CompileContext) -> bool;
     fn name(&self) -> &str;
@@ -273,6 +273,12 @@ impl std::cmp::PartialEq for SimpleCommand {
 
 impl std::cmp::Eq for SimpleCommand {}
 
+impl Display for SimpleCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.full_name.join(":"))
+    }
+}
+
 impl std::fmt::Debug for SimpleCommand {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Command")
@@ -349,6 +355,12 @@ impl Help for ConditionCommand {
     }
 }
 
+impl Display for ConditionCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.full_name.join(":"))
+    }
+}
+
 impl std::cmp::PartialEq for ConditionCommand {
     fn eq(&self, _other: &ConditionCommand) -> bool {
         false
@@ -448,6 +460,12 @@ impl CrushCommand for BoundCommand {
     }
 }
 
+impl Display for BoundCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.command.fmt(f)
+    }
+}
+
 impl Help for BoundCommand {
     fn signature(&self) -> String {
         self.command.signature()
@@ -1,6 +1,7 @@
-use crate::lang::errors::{error, CrushError, CrushResult};
+use crate::lang::errors::{error, CrushErrorType, CrushResult};
 use crate::lang::execution_context::{CompileContext, JobContext};
 use crate::lang::scope::Scope;
+use crate::lang::source::Source;
 use crate::lang::{argument::ArgumentDefinition, argument::ArgumentVecCompiler, value::Value};
 use crate::lang::{
     command::Command, execution_context::CommandContext, job::JobJoinHandle,
@@ -15,9 +16,30 @@ use std::fmt::{Display, Formatter};
 pub struct CommandInvocation {
     command: ValueDefinition,
     arguments: Vec<ArgumentDefinition>,
+    source: Option<Source>,
 }
 
-fn resolve_external_command(name: &str, env: &Scope) -> CrushResult<Option<PathBuf>> {
+/// The name and source location of the invoked command, used to tell the user where
+/// an error happened.
+#[derive(Clone)]
+struct Origin {
+    command: Option<String>,
+    source: Option<Source>,
+}
+
+impl Origin {
+    fn annotate<T>(&self, result: CrushResult<T>) -> CrushResult<T> {
+        result.map_err(|e| {
+            let e = e.with_source(&self.source);
+            match &self.command {
+                Some(command) => e.with_command(command),
+                None => e,
+            }
+        })
+    }
+}
+
+pub fn resolve_external_command(name: &str, env: &Scope) -> CrushResult<Option<PathBuf>> {
     if let Some(Value::List(path)) = env.get("cmd_path")? {
         let path_vec = path.dump();
         for val in path_vec {
@@ -46,7 +68,34 @@ fn arg_can_block(local_arguments: &Vec<ArgumentDefinition>, context: &mut Compil
 
 impl CommandInvocation {
     pub fn new(command: ValueDefinition, arguments: Vec<ArgumentDefinition>) -> CommandInvocation {
-        CommandInvocation { command, arguments }
+        CommandInvocation {
+            command,
+            arguments,
+            source: None,
+        }
+    }
+
+    /// Set the location in the source code of this command invocation, unless it is
+    /// already known.
+    pub fn with_source(mut self, source: &Option<Source>) -> CommandInvocation {
+        if self.source.is_none() {
+            self.source = source.clone();
+        }
+        self
+    }
+
+    fn origin(&self) -> Origin {
+        let command = match &self.command {
+            ValueDefinition::Value(Value::Command(c)) => Some(c.to_string()),
+            ValueDefinition::Label(_)
+            | ValueDefinition::GetAttr(_, _)
+            | ValueDefinition::Path(_, _) => Some(self.command.to_string()),
+            _ => None,
+        };
+        Origin {
+            command,
+            source: self.source.clone(),
+        }
     }
 
     pub fn as_string(&self) -> Option<String> {
@@ -121,33 +170,54 @@ impl CommandInvocation {
     }
 
     pub fn invoke(&self, context: JobContext) -> CrushResult<JobJoinHandle> {
+        let origin = self.origin();
+        origin.annotate(self.invoke_from(context, origin.clone()))
+    }
+
+    fn invoke_from(&self, context: JobContext, origin: Origin) -> CrushResult<JobJoinHandle> {
         match self
             .command
             .compile_internal(&mut context.compile_context(), false)
         {
-            Ok((this, value)) => invoke_value(this, value, self.arguments.clone(), context),
+            Ok((this, value)) => {
+                invoke_value(this, value, self.arguments.clone(), context, origin)
+            }
             Err(err) => {
-                if err == CrushError::BlockError {
+                if err.is(CrushErrorType::BlockError) {
                     let cmd = self.command.clone();
                     let arguments = self.arguments.clone();
                     Ok(handle(build(self.command.to_string().as_str()).spawn(
-                        move || match cmd.clone().compile_unbound(&mut context.compile_context()) {
-                            Ok((this, value)) => context.printer.handle_error(invoke_value(
-                                this,
-                                value,
-                                arguments,
-                                context.clone(),
-                            )),
-
-                            _ => context.printer.handle_error(try_external_command(
-                                cmd,
-                                arguments,
-                                context.clone(),
-                            )),
+                        move || {
+                            let res =
+                                match cmd.clone().compile_unbound(&mut context.compile_context()) {
+                                    Ok((this, value)) => invoke_value(
+                                        this,
+                                        value,
+                                        arguments,
+                                        context.clone(),
+                                        origin.clone(),
+                                    ),
+                                    _ => try_external_command(
+                                        cmd,
+                                        arguments,
+                                        context.clone(),
+                                        origin.clone(),
+                                    ),
+                                };
+                            // Wait for the command, so that the job doesn't end before it does
+                            match origin.annotate(res) {
+                                Ok(job) => job.join(&context.printer),
+                                res => context.printer.handle_error(res),
+                            }
                         },
                     )))
                 } else {
-                    try_external_command(self.command.clone(), self.arguments.clone(), context)
+                    try_external_command(
+                        self.command.clone(),
+                        self.arguments.clone(),
+                        context,
+                        origin,
+                    )
                 }
             }
         }
@@ -159,9 +229,10 @@ fn invoke_value(
     value: Value,
     local_arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: Origin,
 ) -> CrushResult<JobJoinHandle> {
     match value {
-        Value::Command(command) => invoke_command(command, this, local_arguments, context),
+        Value::Command(command) => invoke_command(command, this, local_arguments, context, origin),
         Value::File(f) => {
             if local_arguments.len() == 0 {
                 let meta = f.metadata();
@@ -175,6 +246,7 @@ fn invoke_value(
                             Value::File(f),
                         ))],
                         context,
+                        origin,
                     )
                 } else {
                     invoke_command(
@@ -184,6 +256,7 @@ fn invoke_value(
                             Value::File(f),
                         ))],
                         context,
+                        origin,
                     )
                 }
             } else {
@@ -204,17 +277,19 @@ fn invoke_value(
                     Value::Type(t),
                 ))],
                 context,
+                origin,
             ),
             Some(call) => invoke_command(
                 call.as_ref().copy(),
                 Some(Value::Type(t)),
                 local_arguments,
                 context,
+                origin,
             ),
         },
         Value::Struct(s) => match s.get("__call__") {
             Some(Value::Command(call)) => {
-                invoke_command(call, Some(Value::Struct(s)), local_arguments, context)
+                invoke_command(call, Some(Value::Struct(s)), local_arguments, context, origin)
             }
             Some(v) => error(
                 format!(
@@ -232,6 +307,7 @@ fn invoke_value(
                             Value::Struct(s)
                         ))],
                         context,
+                        origin,
                     )
                 } else {
                     error(
@@ -251,6 +327,7 @@ fn invoke_value(
                     None,
                     vec![ArgumentDefinition::unnamed(ValueDefinition::Value(value))],
                     context,
+                    origin,
                 )
             } else {
                 error(&format!("Not a command {}", value))
@@ -264,22 +341,25 @@ fn invoke_command(
     this: Option<Value>,
     local_arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: Origin,
 ) -> CrushResult<JobJoinHandle> {
     if !action.can_block(&local_arguments, &mut context.compile_context())
         && !arg_can_block(&local_arguments, &mut context.compile_context())
     {
         let new_context =
             CommandInvocation::execution_context(local_arguments, this, context.clone())?;
-        context.printer.handle_error(action.invoke(new_context));
+        context
+            .printer
+            .handle_error(origin.annotate(action.invoke(new_context)));
         Ok(JobJoinHandle::Many(vec![]))
     } else {
         Ok(handle(build(action.name()).spawn(move || {
             let res = CommandInvocation::execution_context(local_arguments, this, context.clone());
             if let Ok(ctx) = res {
                 let p = ctx.printer.clone();
-                p.handle_error(action.invoke(ctx));
+                p.handle_error(origin.annotate(action.invoke(ctx)));
             } else {
-                context.printer.handle_error(res);
+                context.printer.handle_error(origin.annotate(res));
             }
         })))
     }
@@ -289,6 +369,7 @@ fn try_external_command(
     def: ValueDefinition,
     mut arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: Origin,
 ) -> CrushResult<JobJoinHandle> {
     let (cmd, sub) = match def {
         ValueDefinition::Label(str) => (str, None),
@@ -321,14 +402,27 @@ fn try_external_command(
                         .global_static_cmd(vec!["global", "control", "cmd"])?,
                 )),
                 arguments,
+                source: origin.source.clone(),
             };
-            call.invoke(context)
+            // Report errors using the name of the external command, not the cmd command
+            call.invoke_from(
+                context,
+                Origin {
+                    command: Some(cmd),
+                    source: origin.source,
+                },
+            )
         }
     }
 }
 
 impl Display for CommandInvocation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.command.fmt(f)
+        self.command.fmt(f)?;
+        for a in &self.arguments {
+            f.write_str(" ")?;
+            a.fmt(f)?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,287 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use crate::util::file::{cwd, home};
+use ordered_map::OrderedMap;
+use rustyline::completion::Pair;
+
+/// Characters that end a word, in addition to whitespace.
+const DELIMITERS: &str = "(){}[]|;&@";
+
+/**
+    Complete the word that ends at the specified position of the line. Returns the position
+    where the completed part of the word starts, and the candidates to replace it with.
+
+    Depending on the word, variable names, members of values, files or the names and allowed
+    values of named arguments of the current command are completed.
+*/
+pub fn complete(line: &str, pos: usize, scope: &Scope) -> CrushResult<(usize, Vec<Pair>)> {
+    let line = &line[..pos];
+    let start = word_start(line);
+    let word = &line[start..];
+    let previous = line[command_start(&line[..start])..start]
+        .split_whitespace()
+        .collect::<Vec<_>>();
+
+    if word.starts_with('"') {
+        return Ok((pos, vec![]));
+    }
+
+    if let Some(idx) = word.find('=') {
+        return match previous.first() {
+            Some(command) => Ok((
+                start + idx + 1,
+                argument_values(command, &word[..idx], &word[idx + 1..], scope)?,
+            )),
+            None => Ok((pos, vec![])),
+        };
+    }
+
+    if word.contains('/') || word.starts_with('~') {
+        let idx = word.rfind('/').map(|i| i + 1).unwrap_or(0);
+        return Ok((start + idx, files(&word[..idx], &word[idx..])?));
+    }
+
+    if let Some(idx) = word.rfind(':') {
+        return Ok((start + idx + 1, members(&word[..idx], &word[idx + 1..], scope)?));
+    }
+
+    let mut res = labels(word, scope)?;
+    if let Some(command) = previous.first() {
+        res.append(&mut argument_names(command, word, scope)?);
+    }
+    res.append(&mut files("", word)?);
+    res.sort_by(|a, b| a.display.cmp(&b.display));
+    res.dedup_by(|a, b| a.display == b.display);
+    Ok((start, res))
+}
+
+/// Members like __call__ are only completed if the user asks for them.
+fn is_visible(name: &str, prefix: &str) -> bool {
+    name.starts_with(prefix) && (!name.starts_with("__") || prefix.starts_with('_'))
+}
+
+fn pair(candidate: String) -> Pair {
+    Pair {
+        display: candidate.clone(),
+        replacement: candidate,
+    }
+}
+
+fn word_start(line: &str) -> usize {
+    line.char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace() || DELIMITERS.contains(*c))
+        .map(|(idx, c)| idx + c.len_utf8())
+        .unwrap_or(0)
+}
+
+/// Find the start of the innermost command that the end of the line is a part of.
+fn command_start(line: &str) -> usize {
+    let mut starts = vec![0];
+    let mut in_quote = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        if in_quote {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quote = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quote = true,
+            '(' | '{' => starts.push(idx + 1),
+            ')' | '}' if starts.len() > 1 => {
+                starts.pop();
+            }
+            '|' | ';' | '&' | '\n' => {
+                if let Some(start) = starts.last_mut() {
+                    *start = idx + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    starts[starts.len() - 1]
+}
+
+/// Look up a value, e.g. `io:json:from`, without running any commands.
+fn resolve(path: &str, scope: &Scope) -> CrushResult<Option<Value>> {
+    let mut parts = path.split(':');
+    let mut value = match parts.next() {
+        Some(name) if !name.is_empty() => scope.get(name)?,
+        _ => None,
+    };
+    for part in parts {
+        value = match value {
+            Some(v) => v.field(part)?,
+            None => return Ok(None),
+        };
+    }
+    Ok(value)
+}
+
+fn labels(prefix: &str, scope: &Scope) -> CrushResult<Vec<Pair>> {
+    let mut names: OrderedMap<String, ValueType> = OrderedMap::new();
+    scope.dump(&mut names)?;
+    Ok(names
+        .keys()
+        .filter(|name| is_visible(name, prefix))
+        .map(|name| pair(name.to_string()))
+        .collect())
+}
+
+fn members(path: &str, prefix: &str, scope: &Scope) -> CrushResult<Vec<Pair>> {
+    let mut names = match resolve(path, scope)? {
+        Some(Value::Scope(s)) => {
+            let mut map: OrderedMap<String, ValueType> = OrderedMap::new();
+            s.dump(&mut map)?;
+            let mut names = map.keys().map(|k| k.to_string()).collect::<Vec<_>>();
+            names.append(&mut Value::Scope(s).fields());
+            names
+        }
+        Some(value) => value.fields(),
+        None => vec![],
+    };
+    names.sort();
+    names.dedup();
+    Ok(names
+        .drain(..)
+        .filter(|name| is_visible(name, prefix))
+        .map(pair)
+        .collect())
+}
+
+/// Complete the names of entries in the specified directory, relative to the current
+/// working directory.
+fn files(directory: &str, prefix: &str) -> CrushResult<Vec<Pair>> {
+    let path = if directory.is_empty() {
+        cwd()?
+    } else if let Some(rest) = directory.strip_prefix('~') {
+        home()?.join(rest.trim_start_matches('/'))
+    } else {
+        cwd()?.join(directory)
+    };
+    let entries = match path.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+    let mut res = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(prefix)
+            || (name.starts_with('.') && !prefix.starts_with('.'))
+            || name.contains(char::is_whitespace)
+        {
+            continue;
+        }
+        if entry.path().is_dir() {
+            res.push(pair(format!("{}/", name)));
+        } else {
+            res.push(pair(name));
+        }
+    }
+    res.sort_by(|a, b| a.display.cmp(&b.display));
+    Ok(res)
+}
+
+/// A parameter of a command, as described by its signature.
+struct Parameter {
+    name: String,
+    allowed_values: Vec<String>,
+}
+
+/**
+    Extract the parameters from a signature like
+    `http uri=string [method=("get"|"post")] [header=string...]`. Only literal values, not
+    types, are returned as the allowed values of a parameter.
+*/
+fn parameters(signature: &str) -> Vec<Parameter> {
+    signature
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|token| {
+            let token = token
+                .trim_start_matches(&['[', '@'][..])
+                .trim_end_matches(']')
+                .trim_end_matches("...");
+            let idx = token.find(&['=', ':'][..])?;
+            let (name, description) = (&token[..idx], &token[idx + 1..]);
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return None;
+            }
+            let allowed_values = if description == "bool" {
+                vec!["true".to_string(), "false".to_string()]
+            } else {
+                description
+                    .trim_start_matches('(')
+                    .trim_end_matches(')')
+                    .split('|')
+                    .filter(|v| v.starts_with(|c: char| c == '"' || c == '\'' || c.is_numeric()))
+                    .map(|v| v.to_string())
+                    .collect()
+            };
+            Some(Parameter {
+                name: name.to_string(),
+                allowed_values,
+            })
+        })
+        .collect()
+}
+
+fn command_parameters(command: &str, scope: &Scope) -> CrushResult<Vec<Parameter>> {
+    Ok(match resolve(command, scope)? {
+        Some(Value::Command(c)) => parameters(&c.help().signature()),
+        _ => vec![],
+    })
+}
+
+fn argument_names(command: &str, prefix: &str, scope: &Scope) -> CrushResult<Vec<Pair>> {
+    Ok(command_parameters(command, scope)?
+        .drain(..)
+        .filter(|p| p.name.starts_with(prefix))
+        .map(|p| pair(format!("{}=", p.name)))
+        .collect())
+}
+
+fn argument_values(
+    command: &str,
+    name: &str,
+    prefix: &str,
+    scope: &Scope,
+) -> CrushResult<Vec<Pair>> {
+    Ok(command_parameters(command, scope)?
+        .drain(..)
+        .filter(|p| p.name == name)
+        .flat_map(|p| p.allowed_values)
+        .filter(|v| v.starts_with(prefix))
+        .map(pair)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_start_of_command() {
+        assert_eq!(command_start("ls | where "), 4);
+        assert_eq!(command_start("echo (foo "), 6);
+        assert_eq!(command_start("echo (foo) "), 0);
+        assert_eq!(command_start("echo \"a|b\" "), 0);
+    }
+
+    #[test]
+    fn parses_signatures() {
+        let p = parameters("http uri=string [method=(\"get\"|\"post\")] [header=string...]");
+        assert_eq!(
+            p.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+            vec!["uri", "method", "header"]
+        );
+        assert_eq!(p[1].allowed_values, vec!["\"get\"", "\"post\""]);
+        assert!(p[0].allowed_values.is_empty());
+    }
+}
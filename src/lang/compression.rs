@@ -0,0 +1,329 @@
+use crate::lang::binary::BinaryReader;
+use crate::lang::errors::{to_crush_error, CrushResult};
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The compression formats that crush can read and write transparently.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+/// The longest magic number of any supported format.
+const MAGIC_LEN: usize = 6;
+
+/// Extensions of text formats. Files with one of these are never sniffed for a magic number,
+/// so that e.g. a text file that happens to start with "BZh1" is read as it is.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "log", "csv", "tsv", "json", "jsonl", "ndjson", "yaml", "yml", "toml", "xml", "html",
+    "md", "env", "proto", "crush", "ini", "conf",
+];
+
+fn is_text(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| TEXT_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+impl Compression {
+    pub fn from_extension(path: &Path) -> Option<Compression> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    pub fn from_magic(data: &[u8]) -> Option<Compression> {
+        // Gzip is followed by the compression method, which is always deflate, and bzip2 by
+        // the block size, which is a digit between 1 and 9.
+        if data.starts_with(&[0x1f, 0x8b, 0x08]) {
+            Some(Compression::Gzip)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if data.starts_with(b"BZh") && matches!(data.get(3), Some(b'1'..=b'9')) {
+            Some(Compression::Bzip2)
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    /// Find out how the specified file is compressed, first by looking at its extension, and
+    /// failing that, at its first few bytes. Only regular files are sniffed, since reading
+    /// from e.g. a pipe would consume the data, and files with the extension of a text format
+    /// are assumed to be uncompressed.
+    pub fn detect(path: &Path) -> CrushResult<Option<Compression>> {
+        if let Some(compression) = Compression::from_extension(path) {
+            return Ok(Some(compression));
+        }
+        if is_text(path) || !to_crush_error(path.metadata())?.is_file() {
+            return Ok(None);
+        }
+        let file = to_crush_error(File::open(path))?;
+        let mut magic = Vec::with_capacity(MAGIC_LEN);
+        to_crush_error(file.take(MAGIC_LEN as u64).read_to_end(&mut magic))?;
+        Ok(Compression::from_magic(&magic))
+    }
+
+    pub fn decoder<'a>(
+        self,
+        reader: impl Read + Send + 'a,
+    ) -> CrushResult<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::from(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => {
+                Box::from(to_crush_error(::zstd::stream::read::Decoder::new(reader))?)
+            }
+            Compression::Bzip2 => Box::from(bzip2::read::MultiBzDecoder::new(reader)),
+            Compression::Xz => Box::from(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+
+    /// Wrap the specified writer in an encoder. The compressed stream is only complete once
+    /// `Encoder::finish` has been called.
+    pub fn encoder<W: Write>(self, writer: W) -> CrushResult<Encoder<W>> {
+        Ok(match self {
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => Encoder::Zstd(to_crush_error(
+                ::zstd::stream::write::Encoder::new(writer, 0),
+            )?),
+            Compression::Bzip2 => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+                writer,
+                bzip2::Compression::default(),
+            )),
+            Compression::Xz => Encoder::Xz(xz2::write::XzEncoder::new(writer, 6)),
+        })
+    }
+}
+
+/// A writer that compresses its data using one of the formats in `Compression`.
+///
+/// The encoders would also finish the stream when dropped, but they would discard any error
+/// while doing so, so a failure to write the end of the stream would go unnoticed.
+pub enum Encoder<W: Write> {
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(::zstd::stream::write::Encoder<W>),
+    Bzip2(bzip2::write::BzEncoder<W>),
+    Xz(xz2::write::XzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Write the end of the compressed stream and return the underlying writer.
+    pub fn finish(self) -> CrushResult<W> {
+        to_crush_error(match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+            Encoder::Bzip2(encoder) => encoder.finish(),
+            Encoder::Xz(encoder) => encoder.finish(),
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Bzip2(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Bzip2(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A reader that decompresses a file on the fly.
+struct DecompressingReader {
+    path: PathBuf,
+    compression: Compression,
+    decoder: Mutex<Box<dyn Read + Send>>,
+}
+
+impl DecompressingReader {
+    fn new(path: PathBuf, compression: Compression) -> CrushResult<DecompressingReader> {
+        let file = to_crush_error(File::open(&path))?;
+        let decoder = Mutex::new(compression.decoder(file)?);
+        Ok(DecompressingReader {
+            path,
+            compression,
+            decoder,
+        })
+    }
+}
+
+impl Debug for DecompressingReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str("<decompressing reader>")
+    }
+}
+
+impl Read for DecompressingReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.decoder.get_mut() {
+            Ok(decoder) => decoder.read(buf),
+            Err(poisoned) => poisoned.into_inner().read(buf),
+        }
+    }
+}
+
+impl BinaryReader for DecompressingReader {
+    /// The decoder state can't be copied, so the clone starts over from the beginning of the
+    /// file. If the file can't be reopened, reading from the clone fails with the reason.
+    fn clone(&self) -> Box<dyn BinaryReader + Send + Sync> {
+        match DecompressingReader::new(self.path.clone(), self.compression) {
+            Ok(reader) => Box::from(reader),
+            Err(e) => Box::from(FailedReader {
+                message: format!("Failed to reopen {}: {}", self.path.display(), e.message()),
+            }),
+        }
+    }
+}
+
+/// A reader that fails on every read, used in place of a reader that could not be created.
+#[derive(Debug)]
+struct FailedReader {
+    message: String,
+}
+
+impl Read for FailedReader {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::new(ErrorKind::Other, self.message.clone()))
+    }
+}
+
+impl BinaryReader for FailedReader {
+    fn clone(&self) -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(FailedReader {
+            message: self.message.clone(),
+        })
+    }
+}
+
+/// Open the specified file, decompressing it if it is compressed.
+pub fn open(path: PathBuf) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
+    match Compression::detect(&path)? {
+        Some(compression) => Ok(Box::from(DecompressingReader::new(path, compression)?)),
+        None => BinaryReader::paths(vec![path]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_from_extension() {
+        assert_eq!(
+            Compression::from_extension(Path::new("access.log.gz")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("a.tar.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_extension(Path::new("a.txt")), None);
+        assert_eq!(Compression::from_extension(Path::new("gz")), None);
+    }
+
+    #[test]
+    fn detect_from_magic() {
+        assert_eq!(Compression::from_magic(b"BZh9"), Some(Compression::Bzip2));
+        assert_eq!(Compression::from_magic(b"BZhello"), None);
+        assert_eq!(Compression::from_magic(b"BZh"), None);
+        assert_eq!(
+            Compression::from_magic(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::from_magic(&[0x1f, 0x8b, 0x00]), None);
+    }
+
+    #[test]
+    fn detect_skips_text_and_special_files() {
+        assert!(is_text(Path::new("notes.txt")));
+        assert!(is_text(Path::new("data.CSV")));
+        assert!(!is_text(Path::new("data.bin")));
+        assert!(!is_text(Path::new("data")));
+        assert_eq!(Compression::detect(Path::new("/dev/null")).unwrap(), None);
+    }
+
+    #[test]
+    fn failed_clone_fails_to_read() {
+        let reader = DecompressingReader {
+            path: PathBuf::from("/nonexistent/file.gz"),
+            compression: Compression::Gzip,
+            decoder: Mutex::new(Box::new(std::io::empty())),
+        };
+        let mut clone = BinaryReader::clone(&reader);
+        assert!(clone.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = b"hello hello hello hello\n".repeat(100);
+        for compression in &[
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Bzip2,
+            Compression::Xz,
+        ] {
+            let mut encoder = compression.encoder(Vec::new()).unwrap();
+            encoder.write_all(&data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(Compression::from_magic(&compressed), Some(*compression));
+            let mut decompressed = Vec::new();
+            compression
+                .decoder(&compressed[..])
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    /// A writer that fails every write, like a file on a full disk.
+    struct FullWriter;
+
+    impl Write for FullWriter {
+        fn write(&mut self, _buf: &[u8]) -> Result<usize, Error> {
+            Err(Error::new(ErrorKind::Other, "No space left on device"))
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn finish_reports_errors() {
+        for compression in &[
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Bzip2,
+            Compression::Xz,
+        ] {
+            assert!(compression.encoder(FullWriter).unwrap().finish().is_err());
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::lang::completion::complete;
+use crate::lang::highlight::{highlight, ExternalCommands};
+use crate::lang::scope::Scope;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::borrow::Cow;
+
+/// The rustyline helper used by the interactive shell.
+pub struct EditorHelper {
+    scope: Scope,
+    external_commands: ExternalCommands,
+}
+
+impl Completer for EditorHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(complete(line, pos, &self.scope).unwrap_or((pos, vec![])))
+    }
+}
+
+impl Hinter for EditorHelper {}
+
+impl Highlighter for EditorHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line, &self.scope, &self.external_commands))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Helper for EditorHelper {}
+
+/// Create a line editor that completes using the variables in the specified scope.
+pub fn editor(scope: &Scope) -> Editor<EditorHelper> {
+    let mut editor = Editor::with_config(
+        Config::builder()
+            .completion_type(CompletionType::List)
+            .build(),
+    );
+    editor.set_helper(Some(EditorHelper {
+        scope: scope.clone(),
+        external_commands: ExternalCommands::default(),
+    }));
+    editor
+}
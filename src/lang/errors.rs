@@ -1,59 +1,130 @@
+use crate::lang::source::Source;
 use std::error::Error;
 use std::fmt::{Display};
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum CrushError {
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CrushErrorType {
     InvalidArgument(String),
     InvalidData(String),
     GenericError(String),
+    ExternalCommandError(String),
+    /// An error raised by the user, of a kind chosen by the user.
+    RaisedError(String, String),
     BlockError,
     SendError,
     EOFError,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrushError {
+    error_type: CrushErrorType,
+    command: Option<String>,
+    source: Option<Source>,
+}
+
 impl CrushError {
+    pub fn new(error_type: CrushErrorType) -> CrushError {
+        CrushError {
+            error_type,
+            command: None,
+            source: None,
+        }
+    }
+
+    pub fn is(&self, error_type: CrushErrorType) -> bool {
+        self.error_type == error_type
+    }
+
+    /// A short name describing the type of the error.
+    pub fn kind(&self) -> &str {
+        match &self.error_type {
+            CrushErrorType::InvalidArgument(_) => "invalid_argument",
+            CrushErrorType::InvalidData(_) => "invalid_data",
+            CrushErrorType::GenericError(_) => "generic",
+            CrushErrorType::ExternalCommandError(_) => "external_command",
+            CrushErrorType::RaisedError(kind, _) => kind.as_str(),
+            CrushErrorType::BlockError => "block",
+            CrushErrorType::SendError => "send",
+            CrushErrorType::EOFError => "eof",
+        }
+    }
+
     pub fn message(&self) -> String {
-        match self {
-            CrushError::InvalidArgument(s)
-            | CrushError::InvalidData(s)
-            | CrushError::GenericError(s) => s.clone(),
-            CrushError::BlockError => "Block error".to_string(),
-            CrushError::SendError => "Send error".to_string(),
-            CrushError::EOFError => "EOF error".to_string(),
+        match &self.error_type {
+            CrushErrorType::InvalidArgument(s)
+            | CrushErrorType::InvalidData(s)
+            | CrushErrorType::GenericError(s)
+            | CrushErrorType::ExternalCommandError(s)
+            | CrushErrorType::RaisedError(_, s) => s.clone(),
+            CrushErrorType::BlockError => "Block error".to_string(),
+            CrushErrorType::SendError => "Send error".to_string(),
+            CrushErrorType::EOFError => "EOF error".to_string(),
         }
     }
+
+    /// The name of the command that failed, if known.
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    /// The location in the source code of the command that failed, if known.
+    pub fn source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+
+    /// Set the name of the command that failed, unless it is already known.
+    pub fn with_command(mut self, command: &str) -> CrushError {
+        if self.command.is_none() {
+            self.command = Some(command.to_string());
+        }
+        self
+    }
+
+    /// Set the source location of the error, unless it is already known.
+    pub fn with_source(mut self, source: &Option<Source>) -> CrushError {
+        if self.source.is_none() {
+            self.source = source.clone();
+        }
+        self
+    }
 }
 
 impl<T: Display> From<T> for CrushError {
     fn from(message: T) -> Self {
-        CrushError::GenericError(message.to_string())
+        CrushError::new(CrushErrorType::GenericError(message.to_string()))
     }
 }
 
 pub type CrushResult<T> = Result<T, CrushError>;
 
 pub fn block_error<T>() -> Result<T, CrushError> {
-    Err(CrushError::BlockError)
+    Err(CrushError::new(CrushErrorType::BlockError))
 }
 
 pub fn eof_error<T>() -> Result<T, CrushError> {
-    Err(CrushError::EOFError)
+    Err(CrushError::new(CrushErrorType::EOFError))
 }
 
 pub fn send_error<T>() -> Result<T, CrushError> {
-    Err(CrushError::SendError)
+    Err(CrushError::new(CrushErrorType::SendError))
 }
 
 pub fn argument_error<T>(message: impl Into<String>) -> Result<T, CrushError> {
-    Err(CrushError::InvalidArgument(message.into()))
+    Err(CrushError::new(CrushErrorType::InvalidArgument(message.into())))
 }
 
 pub fn data_error<T>(message: impl Into<String>) -> Result<T, CrushError> {
-    Err(CrushError::InvalidData(message.into()))
+    Err(CrushError::new(CrushErrorType::InvalidData(message.into())))
 }
 
 pub fn error<T>(message: impl Into<String>) -> Result<T, CrushError> {
-    Err(CrushError::GenericError(message.into()))
+    Err(CrushError::new(CrushErrorType::GenericError(message.into())))
+}
+
+pub fn external_command_error<T>(message: impl Into<String>) -> Result<T, CrushError> {
+    Err(CrushError::new(CrushErrorType::ExternalCommandError(
+        message.into(),
+    )))
 }
 
 pub fn to_crush_error<T, E: Error>(result: Result<T, E>) -> Result<T, CrushError> {
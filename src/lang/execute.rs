@@ -1,5 +1,6 @@
 use crate::lang::errors::{argument_error, to_crush_error, CrushError, CrushResult};
 use crate::lang::execution_context::{CommandContext, JobContext};
+use crate::lang::jobs;
 use crate::lang::parser::parse;
 use crate::lang::printer::Printer;
 use crate::lang::scope::Scope;
@@ -17,7 +18,13 @@ pub fn file(
     output: &ValueSender,
 ) -> CrushResult<()> {
     let cmd = to_crush_error(fs::read_to_string(filename))?;
-    string(global_env, &cmd.as_str(), printer, output);
+    code(
+        global_env,
+        &cmd.as_str(),
+        filename.to_str().unwrap_or("<invalid filename>"),
+        printer,
+        output,
+    );
     Ok(())
 }
 
@@ -45,6 +52,7 @@ pub fn pup(env: Scope, buf: &Vec<u8>, printer: &Printer) -> CrushResult<()> {
                 scope: env,
                 this: None,
                 printer: printer.clone(),
+                background: false,
             })?;
 
             match t.join() {
@@ -57,17 +65,36 @@ pub fn pup(env: Scope, buf: &Vec<u8>, printer: &Printer) -> CrushResult<()> {
 }
 
 pub fn string(global_env: Scope, s: &str, printer: &Printer, output: &ValueSender) {
-    match parse(s, &global_env) {
+    code(global_env, s, "<input>", printer, output)
+}
+
+/// Execute the specified code. The name describes where the code came from, and is used
+/// when reporting errors.
+pub fn code(global_env: Scope, s: &str, name: &str, printer: &Printer, output: &ValueSender) {
+    match parse(s, name, &global_env) {
         Ok(jobs) => {
             for job_definition in jobs {
+                let (job_output, job_printer) = if job_definition.is_background() {
+                    (output.without_terminal(), printer.background())
+                } else {
+                    (output.clone(), printer.clone())
+                };
                 match job_definition.invoke(JobContext::new(
                     empty_channel(),
-                    output.clone(),
+                    job_output,
                     global_env.clone(),
-                    printer.clone(),
+                    job_printer,
+                    job_definition.is_background(),
                 )) {
                     Ok(handle) => {
-                        handle.join(&printer);
+                        if job_definition.is_background() {
+                            match jobs::spawn(job_definition.to_string(), handle, printer) {
+                                Ok(id) => printer.line(&format!("[{}]", id)),
+                                Err(e) => printer.crush_error(e),
+                            }
+                        } else {
+                            handle.join(&printer);
+                        }
                     }
                     Err(e) => printer.crush_error(e),
                 }
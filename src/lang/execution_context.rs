@@ -164,19 +164,27 @@ pub struct CompileContext {
     pub dependencies: Vec<JobJoinHandle>,
     pub env: Scope,
     pub printer: Printer,
+    pub background: bool,
 }
 
 impl CompileContext {
-    pub fn new(env: Scope, printer: Printer) -> CompileContext {
+    pub fn new(env: Scope, printer: Printer, background: bool) -> CompileContext {
         CompileContext {
             dependencies: Vec::new(),
             env,
             printer,
+            background,
         }
     }
 
     pub fn job_context(&self, input: ValueReceiver, output: ValueSender) -> JobContext {
-        JobContext::new(input, output, self.env.clone(), self.printer.clone())
+        JobContext::new(
+            input,
+            output,
+            self.env.clone(),
+            self.printer.clone(),
+            self.background,
+        )
     }
 
     pub fn with_scope(&self, env: &Scope) -> CompileContext {
@@ -184,6 +192,7 @@ impl CompileContext {
             dependencies: vec![],
             env: env.clone(),
             printer: self.printer.clone(),
+            background: self.background,
         }
     }
 }
@@ -194,6 +203,8 @@ pub struct JobContext {
     pub output: ValueSender,
     pub env: Scope,
     pub printer: Printer,
+    /// Whether this is, or is part of, a background job.
+    pub background: bool,
 }
 
 impl JobContext {
@@ -202,12 +213,14 @@ impl JobContext {
         output: ValueSender,
         env: Scope,
         printer: Printer,
+        background: bool,
     ) -> JobContext {
         JobContext {
             input,
             output,
             env,
             printer,
+            background,
         }
     }
 
@@ -217,11 +230,12 @@ impl JobContext {
             output,
             env: self.env.clone(),
             printer: self.printer.clone(),
+            background: self.background,
         }
     }
 
     pub fn compile_context(&self) -> CompileContext {
-        CompileContext::new(self.env.clone(), self.printer.clone())
+        CompileContext::new(self.env.clone(), self.printer.clone(), self.background)
     }
 
     pub fn command_context(
@@ -236,6 +250,7 @@ impl JobContext {
             output: self.output.clone(),
             printer: self.printer.clone(),
             scope: self.env.clone(),
+            background: self.background,
         }
     }
 }
@@ -248,6 +263,8 @@ pub struct CommandContext {
     pub scope: Scope,
     pub this: Option<Value>,
     pub printer: Printer,
+    /// Whether the command is part of a background job.
+    pub background: bool,
 }
 
 impl CommandContext {
@@ -255,7 +272,7 @@ impl CommandContext {
     Return a compile context with the environemnt from this execution context..
     */
     pub fn compile_context(&self) -> CompileContext {
-        CompileContext::new(self.scope.clone(), self.printer.clone())
+        CompileContext::new(self.scope.clone(), self.printer.clone(), self.background)
     }
 
     /**
@@ -269,6 +286,7 @@ impl CommandContext {
             printer: self.printer,
             arguments,
             this,
+            background: self.background,
         }
     }
 
@@ -280,6 +298,19 @@ impl CommandContext {
             printer: self.printer,
             arguments: self.arguments,
             this: self.this,
+            background: self.background,
+        }
+    }
+
+    pub fn with_printer(self, printer: Printer) -> CommandContext {
+        CommandContext {
+            input: self.input,
+            output: self.output,
+            scope: self.scope,
+            printer,
+            arguments: self.arguments,
+            this: self.this,
+            background: self.background,
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::lang::binary::{binary_channel, BinaryReader};
+use crate::lang::compression::{self, Compression, Encoder};
 use crate::lang::errors::{argument_error, error, to_crush_error, CrushResult};
 use crate::lang::printer::Printer;
 use crate::lang::stream::{ValueReceiver, ValueSender};
@@ -6,12 +7,13 @@ use crate::lang::value::{Value, ValueType};
 use crate::util::file::cwd;
 use crate::util::regex::RegexFileMatcher;
 use std::fs::File;
-use std::io::Write;
+use std::io::{Error, Write};
 use std::path::PathBuf;
 
 #[derive(Debug)]
 pub struct Files {
     had_entries: bool,
+    had_patterns: bool,
     files: Vec<PathBuf>,
 }
 
@@ -19,6 +21,7 @@ impl Files {
     pub fn new() -> Files {
         Files {
             had_entries: false,
+            had_patterns: false,
             files: Vec::new(),
         }
     }
@@ -27,6 +30,12 @@ impl Files {
         self.had_entries
     }
 
+    /// Whether any of the files came from a glob, a regex or a table stream, i.e. whether the
+    /// number of files depends on what they matched rather than on how many were given.
+    pub fn had_patterns(&self) -> bool {
+        self.had_patterns
+    }
+
     pub fn into_vec(self) -> Vec<PathBuf> {
         self.files
     }
@@ -39,7 +48,26 @@ impl Files {
         }
     }
 
+    /// Read the files, or the input if no files were given. Files compressed with any of the
+    /// formats in `Compression` are decompressed on the fly.
     pub fn reader(self, input: ValueReceiver) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
+        if !self.had_entries {
+            self.raw_reader(input)
+        } else {
+            Ok(BinaryReader::concat(
+                self.files
+                    .into_iter()
+                    .map(compression::open)
+                    .collect::<CrushResult<Vec<_>>>()?,
+            ))
+        }
+    }
+
+    /// Read the files, or the input if no files were given, as is.
+    pub fn raw_reader(
+        self,
+        input: ValueReceiver,
+    ) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
         if !self.had_entries {
             match input.recv()? {
                 Value::BinaryStream(b) => Ok(b),
@@ -51,7 +79,24 @@ impl Files {
         }
     }
 
-    pub fn writer(self, output: ValueSender) -> CrushResult<Box<dyn Write>> {
+    /// Write to the file, or to the output if no file was given. The file is compressed if its
+    /// extension is that of one of the formats in `Compression`. The writer has to be finished
+    /// once everything has been written.
+    pub fn writer(self, output: ValueSender) -> CrushResult<FileWriter> {
+        let compression = if self.had_entries && self.files.len() == 1 {
+            Compression::from_extension(&self.files[0])
+        } else {
+            None
+        };
+        let writer = self.raw_writer(output)?;
+        match compression {
+            Some(compression) => Ok(FileWriter::Compressed(compression.encoder(writer)?)),
+            None => Ok(FileWriter::Plain(writer)),
+        }
+    }
+
+    /// Write to the file, or to the output if no file was given, as is.
+    pub fn raw_writer(self, output: ValueSender) -> CrushResult<Box<dyn Write>> {
         if !self.had_entries {
             let (w, r) = binary_channel();
             output.send(Value::BinaryStream(r))?;
@@ -69,8 +114,14 @@ impl Files {
     pub fn expand(&mut self, value: Value, printer: &Printer) -> CrushResult<()> {
         match value {
             Value::File(p) => self.files.push(p),
-            Value::Glob(pattern) => pattern.glob_files(&PathBuf::from("."), &mut self.files)?,
-            Value::Regex(_, re) => re.match_files(&cwd()?, &mut self.files, printer),
+            Value::Glob(pattern) => {
+                pattern.glob_files(&PathBuf::from("."), &mut self.files)?;
+                self.had_patterns = true;
+            }
+            Value::Regex(_, re) => {
+                re.match_files(&cwd()?, &mut self.files, printer);
+                self.had_patterns = true;
+            }
             value => match value.stream() {
                 None => return argument_error("Expected a file name"),
                 Some(mut s) => {
@@ -81,6 +132,7 @@ impl Files {
                                 self.files.push(f);
                             }
                         }
+                        self.had_patterns = true;
                     } else {
                         return argument_error("Table stream must contain one column of type file");
                     }
@@ -91,3 +143,37 @@ impl Files {
         Ok(())
     }
 }
+
+/// A writer returned by `Files::writer`.
+pub enum FileWriter {
+    Plain(Box<dyn Write>),
+    Compressed(Encoder<Box<dyn Write>>),
+}
+
+impl FileWriter {
+    /// Write the end of the compressed stream, if any, and flush the file, reporting any
+    /// error instead of losing it when the writer is dropped.
+    pub fn finish(self) -> CrushResult<()> {
+        let mut writer = match self {
+            FileWriter::Plain(writer) => writer,
+            FileWriter::Compressed(encoder) => encoder.finish()?,
+        };
+        to_crush_error(writer.flush())
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self {
+            FileWriter::Plain(writer) => writer.write(buf),
+            FileWriter::Compressed(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            FileWriter::Plain(writer) => writer.flush(),
+            FileWriter::Compressed(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::lang::command_invocation::resolve_external_command;
+use crate::lang::scope::Scope;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use termion::{color, style};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TokenType {
+    Whitespace,
+    Comment,
+    Separator,
+    Operator,
+    Assignment,
+    Punctuation,
+    String,
+    QuotedLabel,
+    Regex,
+    Field,
+    Flag,
+    Integer,
+    Float,
+    Label,
+    Unknown,
+}
+
+/// The tokens of the grammar in lalrparser.lalrpop, which must be kept in sync with its match
+/// block, as (pattern, priority, skipped). Alternatives are ordered so that the first one that
+/// matches is the longest one, since the regex crate doesn't look for the longest match.
+const TOKENS: &[(&str, usize, bool)] = &[
+    (r"and|or", 1, false),
+    (r"typeof|neg|not", 1, false),
+    (r":=|:|=|@@|@|&|\(|\)|\[|\]|\{|\}|\|", 0, false),
+    (r"( |\t|\\\n)+", 0, true),
+    (r"#[^\n]*", 0, true),
+    (r">=|<=|==|!=|=~|!~|>|<", 0, false),
+    (r"\*|//", 0, false),
+    (r"\+|-", 0, false),
+    (r#""([^\\"]|\\.)*""#, 0, false),
+    (
+        r"[\._a-zA-Z%\?~][\._0-9a-zA-Z%\?]*(/[\._0-9a-zA-Z%\?]+)*/?|/[\._0-9a-zA-Z%\?]+(/[\._0-9a-zA-Z%\?]+)*/?|/",
+        0,
+        false,
+    ),
+    (r"--[_0-9a-zA-Z]+", 0, false),
+    (r"\^[\._a-zA-Z][\._a-zA-Z0-9]*", 0, false),
+    (r#"'([^\\']|\\.)*'"#, 0, false),
+    (r#"re"([^"]|\\.)*""#, 0, false),
+    (r"(;|\n)( |\t|;|\n|#[^\n]*)*", 0, false),
+    (r"[0-9][0-9_]*", 0, false),
+    (r"[0-9][0-9_]*\.[0-9_]+", 0, false),
+];
+
+lazy_static! {
+    static ref LEXER: Vec<(Regex, usize, bool)> = TOKENS
+        .iter()
+        .map(|(pattern, priority, skipped)| {
+            (
+                Regex::new(&format!("^(?:{})", pattern)).unwrap(),
+                *priority,
+                *skipped,
+            )
+        })
+        .collect();
+}
+
+/// The length of the token at the start of the text, and whether the parser skips it, or None
+/// if the text doesn't start with a valid token. Like the lexer generated by lalrpop, the
+/// longest match wins, and ties go to the tokens in the first part of the match block.
+fn next_token(text: &str) -> Option<(usize, bool)> {
+    let mut best: Option<(usize, usize, bool)> = None;
+    for (regex, priority, skipped) in LEXER.iter() {
+        if let Some(m) = regex.find(text) {
+            let better = match best {
+                Some((len, best_priority, _)) => {
+                    m.end() > len || (m.end() == len && *priority > best_priority)
+                }
+                None => m.end() > 0,
+            };
+            if better {
+                best = Some((m.end(), *priority, *skipped));
+            }
+        }
+    }
+    best.map(|(len, _, skipped)| (len, skipped))
+}
+
+/// The type of a token produced by the lexer, which is known from its text.
+fn token_type(text: &str) -> TokenType {
+    match text {
+        "and" | "or" | "typeof" | "neg" | "not" => TokenType::Operator,
+        ">" | "<" | ">=" | "<=" | "==" | "!=" | "=~" | "!~" | "*" | "//" | "+" | "-" => {
+            TokenType::Operator
+        }
+        ":=" | "=" => TokenType::Assignment,
+        "@@" | "@" | "&" | "(" | ")" | "[" | "]" | "{" | "}" | "|" | ":" => TokenType::Punctuation,
+        _ if text.starts_with(';') || text.starts_with('\n') => TokenType::Separator,
+        _ if text.starts_with("re\"") => TokenType::Regex,
+        _ if text.starts_with('"') => TokenType::String,
+        _ if text.starts_with('\'') => TokenType::QuotedLabel,
+        _ if text.starts_with("--") => TokenType::Flag,
+        _ if text.starts_with('^') => TokenType::Field,
+        _ if text.starts_with(|c: char| c.is_ascii_digit()) => {
+            if text.contains('.') {
+                TokenType::Float
+            } else {
+                TokenType::Integer
+            }
+        }
+        _ => TokenType::Label,
+    }
+}
+
+/// Add text that contains comments, i.e. text skipped by the lexer or a separator, which
+/// swallows the comments that follow it. Comments run to the end of the line, everything
+/// else is of the specified type.
+fn push_with_comments<'a>(
+    text: &'a str,
+    token_type: TokenType,
+    res: &mut Vec<(TokenType, &'a str)>,
+) {
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (kind, len) = if rest.starts_with('#') {
+            (TokenType::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else {
+            (token_type, rest.find('#').unwrap_or(rest.len()))
+        };
+        res.push((kind, &rest[..len]));
+        rest = &rest[len..];
+    }
+}
+
+/// Split the line into tokens the way the parser does. Text that can't be tokenized, like an
+/// unterminated string that the user is in the middle of typing, is not an error.
+fn tokenize(line: &str) -> Vec<(TokenType, &str)> {
+    let mut res = Vec::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        let len = match next_token(rest) {
+            Some((len, true)) => {
+                push_with_comments(&rest[..len], TokenType::Whitespace, &mut res);
+                len
+            }
+            Some((len, false)) => {
+                let text = &rest[..len];
+                match token_type(text) {
+                    TokenType::Separator => {
+                        push_with_comments(text, TokenType::Separator, &mut res)
+                    }
+                    token_type => res.push((token_type, text)),
+                }
+                len
+            }
+            None => {
+                let (token_type, len) = if rest.starts_with('"') {
+                    (TokenType::String, rest.len())
+                } else {
+                    (
+                        TokenType::Unknown,
+                        rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1),
+                    )
+                };
+                res.push((token_type, &rest[..len]));
+                len
+            }
+        };
+        rest = &rest[len..];
+    }
+    res
+}
+
+/// Remembers which names are external commands, since highlighting runs on every key press,
+/// and looking a name up means checking every directory in cmd_path. The names are looked up
+/// again when cmd_path changes, or after a few seconds, so that new commands are found.
+#[derive(Default)]
+pub struct ExternalCommands {
+    cache: Mutex<ExternalCommandCache>,
+}
+
+#[derive(Default)]
+struct ExternalCommandCache {
+    cmd_path: String,
+    updated: Option<Instant>,
+    known: HashMap<String, bool>,
+}
+
+const EXTERNAL_COMMAND_CACHE_TIME: Duration = Duration::from_secs(5);
+
+impl ExternalCommands {
+    fn contains(&self, name: &str, scope: &Scope) -> bool {
+        let cmd_path = match scope.get("cmd_path") {
+            Ok(Some(path)) => path.to_string(),
+            _ => String::new(),
+        };
+        let mut cache = self.cache.lock().unwrap();
+        if cache.cmd_path != cmd_path
+            || cache
+                .updated
+                .map(|t| t.elapsed() > EXTERNAL_COMMAND_CACHE_TIME)
+                .unwrap_or(true)
+        {
+            cache.cmd_path = cmd_path;
+            cache.updated = Some(Instant::now());
+            cache.known.clear();
+        }
+        *cache
+            .known
+            .entry(name.to_string())
+            .or_insert_with(|| matches!(resolve_external_command(name, scope), Ok(Some(_))))
+    }
+}
+
+fn is_known_command(name: &str, scope: &Scope, external: &ExternalCommands) -> bool {
+    name.contains('/') || matches!(scope.get(name), Ok(Some(_))) || external.contains(name, scope)
+}
+
+/**
+    Return the line with ANSI color codes added. Labels that are used as commands but that
+    are neither variables nor external commands are colored red.
+*/
+pub fn highlight(line: &str, scope: &Scope, external: &ExternalCommands) -> String {
+    let tokens = tokenize(line);
+    let significant = |from: usize| {
+        tokens[from..]
+            .iter()
+            .find(|(t, _)| *t != TokenType::Whitespace && *t != TokenType::Comment)
+            .copied()
+    };
+
+    let mut res = String::new();
+    let mut command_position = true;
+    let mut after_brace = false;
+    let mut in_signature = false;
+    for (idx, (token_type, text)) in tokens.iter().enumerate() {
+        let text = *text;
+        let colored = match token_type {
+            TokenType::Whitespace => {
+                res.push_str(text);
+                continue;
+            }
+            TokenType::Comment => format!("{}{}", color::Fg(color::LightBlack), text),
+            TokenType::Separator => {
+                command_position = true;
+                after_brace = false;
+                res.push_str(text);
+                continue;
+            }
+            TokenType::Punctuation => {
+                match text {
+                    "|" if after_brace => in_signature = true,
+                    "|" if in_signature => {
+                        in_signature = false;
+                        command_position = true;
+                    }
+                    "(" | "{" | "|" | "&" => command_position = true,
+                    _ => command_position = false,
+                }
+                after_brace = text == "{";
+                res.push_str(text);
+                continue;
+            }
+            TokenType::Operator | TokenType::Assignment => {
+                format!("{}{}", style::Bold, text)
+            }
+            TokenType::String | TokenType::QuotedLabel => {
+                format!("{}{}", color::Fg(color::Green), text)
+            }
+            TokenType::Regex => format!("{}{}", color::Fg(color::Magenta), text),
+            TokenType::Field => format!("{}{}", color::Fg(color::Yellow), text),
+            TokenType::Integer | TokenType::Float | TokenType::Flag => {
+                format!("{}{}", color::Fg(color::Cyan), text)
+            }
+            TokenType::Label => {
+                let is_assignment =
+                    matches!(significant(idx + 1), Some((TokenType::Assignment, _)));
+                if command_position
+                    && !in_signature
+                    && !is_assignment
+                    && !is_known_command(text, scope, external)
+                {
+                    format!("{}{}", color::Fg(color::Red), text)
+                } else {
+                    format!("{}{}", color::Fg(color::Blue), text)
+                }
+            }
+            TokenType::Unknown => text.to_string(),
+        };
+        res.push_str(&colored);
+        res.push_str(&format!("{}", style::Reset));
+        command_position = false;
+        after_brace = false;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_like_the_parser() {
+        let types = tokenize("ls | where {^size > 10} # big")
+            .iter()
+            .filter(|(t, _)| *t != TokenType::Whitespace)
+            .map(|(t, _)| *t)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Label,
+                TokenType::Punctuation,
+                TokenType::Label,
+                TokenType::Punctuation,
+                TokenType::Field,
+                TokenType::Operator,
+                TokenType::Integer,
+                TokenType::Punctuation,
+                TokenType::Comment,
+            ]
+        );
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        assert_eq!(
+            tokenize("x>=1.5//order and ./a/b")
+                .iter()
+                .filter(|(t, _)| *t != TokenType::Whitespace)
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![
+                (TokenType::Label, "x"),
+                (TokenType::Operator, ">="),
+                (TokenType::Float, "1.5"),
+                (TokenType::Operator, "//"),
+                (TokenType::Label, "order"),
+                (TokenType::Operator, "and"),
+                (TokenType::Label, "./a/b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_are_tokenized() {
+        assert_eq!(
+            tokenize("echo \"abc"),
+            vec![
+                (TokenType::Label, "echo"),
+                (TokenType::Whitespace, " "),
+                (TokenType::String, "\"abc"),
+            ]
+        );
+        assert_eq!(tokenize("re\"a.*\"")[0].0, TokenType::Regex);
+        assert_eq!(tokenize("not")[0].0, TokenType::Operator);
+        assert_eq!(tokenize("nothing")[0].0, TokenType::Label);
+    }
+
+    #[test]
+    fn comments_after_separators_are_tokenized() {
+        assert_eq!(
+            tokenize("a;# x\n$b"),
+            vec![
+                (TokenType::Label, "a"),
+                (TokenType::Separator, ";"),
+                (TokenType::Comment, "# x"),
+                (TokenType::Separator, "\n"),
+                (TokenType::Unknown, "$"),
+                (TokenType::Label, "b"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::lang::errors::{mandate, to_crush_error, CrushResult};
+use crate::lang::printer::Printer;
+use crate::util::file::{config_dir, home};
+use chrono::{DateTime, Duration, Local};
+use serde_json::json;
+use std::fs::{create_dir_all, read_to_string, remove_file, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+/// The largest number of entries that are loaded into the line editor.
+pub const EDITOR_ENTRIES: usize = 1000;
+
+/// An interactive invocation, as recorded in the history file. The metadata is missing for
+/// entries imported from the history file of older versions of Crush.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub start: Option<DateTime<Local>>,
+    pub duration: Option<Duration>,
+    pub cwd: Option<PathBuf>,
+    pub success: Option<bool>,
+}
+
+impl HistoryEntry {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "command": self.command,
+            "start": self.start.map(|start| start.to_rfc3339()),
+            "duration": self.duration.and_then(|duration| duration.num_microseconds()),
+            "cwd": self.cwd.as_ref().map(|cwd| cwd.to_string_lossy()),
+            "success": self.success,
+        })
+    }
+
+    fn from_json(line: &str) -> CrushResult<HistoryEntry> {
+        let value: serde_json::Value = to_crush_error(serde_json::from_str(line))?;
+        // A missing or null field means that the metadata is unknown
+        let field = |name: &str| value.get(name).filter(|v| !v.is_null());
+        Ok(HistoryEntry {
+            command: mandate(
+                field("command").and_then(|v| v.as_str()),
+                "Invalid command",
+            )?
+            .to_string(),
+            start: match field("start") {
+                Some(start) => Some(
+                    to_crush_error(DateTime::parse_from_rfc3339(mandate(
+                        start.as_str(),
+                        "Invalid start time",
+                    )?))?
+                    .with_timezone(&Local),
+                ),
+                None => None,
+            },
+            duration: match field("duration") {
+                Some(duration) => Some(Duration::microseconds(mandate(
+                    duration.as_i64(),
+                    "Invalid duration",
+                )?)),
+                None => None,
+            },
+            cwd: match field("cwd") {
+                Some(cwd) => Some(PathBuf::from(mandate(cwd.as_str(), "Invalid directory")?)),
+                None => None,
+            },
+            success: match field("success") {
+                Some(success) => Some(mandate(success.as_bool(), "Invalid status")?),
+                None => None,
+            },
+        })
+    }
+
+    /// An entry without metadata.
+    fn legacy(command: &str) -> HistoryEntry {
+        HistoryEntry {
+            command: command.to_string(),
+            start: None,
+            duration: None,
+            cwd: None,
+            success: None,
+        }
+    }
+}
+
+/// The history is stored as JSON lines, one invocation per line, so that appending an entry
+/// never requires rewriting the file.
+fn history_file() -> CrushResult<PathBuf> {
+    Ok(config_dir()?.join("history.jsonl"))
+}
+
+/// The flat history file used by older versions of Crush, with one command per line.
+fn legacy_history_file() -> CrushResult<PathBuf> {
+    Ok(home()?.join(".crush_history"))
+}
+
+/// Import the history of older versions of Crush, unless there already is a history file. The
+/// old file is left in place.
+pub fn migrate() -> CrushResult<()> {
+    let file = history_file()?;
+    if file.exists() {
+        return Ok(());
+    }
+    let content = match read_to_string(legacy_history_file()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return to_crush_error(Err(e)),
+    };
+    if let Some(dir) = file.parent() {
+        to_crush_error(create_dir_all(dir))?;
+    }
+    let mut out = to_crush_error(OpenOptions::new().create_new(true).write(true).open(&file))?;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        to_crush_error(writeln!(out, "{}", HistoryEntry::legacy(line).to_json()))?;
+    }
+    Ok(())
+}
+
+/// Read the full history, oldest invocation first. Lines that can't be parsed, e.g. because
+/// Crush crashed while writing them, are skipped with a warning.
+pub fn load(printer: &Printer) -> CrushResult<Vec<HistoryEntry>> {
+    let file = history_file()?;
+    let content = match read_to_string(&file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return to_crush_error(Err(e)),
+    };
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(idx, line)| match HistoryEntry::from_json(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                printer.error(&format!(
+                    "{}:{}: Skipping invalid history entry: {}",
+                    file.to_string_lossy(),
+                    idx + 1,
+                    e.message()
+                ));
+                None
+            }
+        })
+        .collect())
+}
+
+/// Add an invocation to the end of the history file.
+pub fn append(entry: &HistoryEntry) -> CrushResult<()> {
+    let file = history_file()?;
+    if let Some(dir) = file.parent() {
+        to_crush_error(create_dir_all(dir))?;
+    }
+    let mut out = to_crush_error(OpenOptions::new().create(true).append(true).open(&file))?;
+    to_crush_error(writeln!(out, "{}", entry.to_json()))
+}
+
+/// Remove all entries from the history.
+pub fn clear() -> CrushResult<()> {
+    match remove_file(history_file()?) {
+        Err(e) if e.kind() != ErrorKind::NotFound => to_crush_error(Err(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let entry = HistoryEntry {
+            command: "ls | where {^size > 1000}\necho \"done\"".to_string(),
+            start: Some(
+                DateTime::parse_from_rfc3339("2020-05-01T12:30:00+02:00")
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            duration: Some(Duration::milliseconds(1500)),
+            cwd: Some(PathBuf::from("/home/crush")),
+            success: Some(false),
+        };
+        let line = entry.to_json().to_string();
+        assert!(!line.contains('\n'));
+        assert_eq!(HistoryEntry::from_json(&line).unwrap(), entry);
+    }
+
+    #[test]
+    fn legacy_round_trip() {
+        let entry = HistoryEntry::legacy("ls");
+        let line = entry.to_json().to_string();
+        assert_eq!(HistoryEntry::from_json(&line).unwrap(), entry);
+        assert!(HistoryEntry::from_json(r#"{"command": "ls", "start": "#).is_err());
+    }
+}
@@ -30,11 +30,19 @@ impl JobJoinHandle {
 #[derive(Clone)]
 pub struct Job {
     commands: Vec<CommandInvocation>,
+    background: bool,
 }
 
 impl Job {
-    pub fn new(commands: Vec<CommandInvocation>) -> Job {
-        Job { commands }
+    pub fn new(commands: Vec<CommandInvocation>, background: bool) -> Job {
+        Job {
+            commands,
+            background,
+        }
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.background
     }
 
     pub fn can_block(&self, context: &mut CompileContext) -> bool {
@@ -86,6 +94,9 @@ impl Display for Job {
             }
             c.fmt(f)?;
         }
+        if self.background {
+            f.write_str(" &")?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,173 @@
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::job::JobJoinHandle;
+use crate::lang::printer::Printer;
+use crate::util::thread::build;
+use chrono::{DateTime, Local};
+use crossbeam::{bounded, Receiver};
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref JOBS: Mutex<JobTable> = Mutex::new(JobTable {
+        jobs: Vec::new(),
+        last_id: 0,
+    });
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Done,
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobState::Running => f.write_str("Running"),
+            JobState::Done => f.write_str("Done"),
+        }
+    }
+}
+
+/// A snapshot of a job in the job table.
+#[derive(Clone)]
+pub struct JobDescription {
+    pub id: usize,
+    pub invocation: String,
+    pub start_time: DateTime<Local>,
+    pub state: JobState,
+}
+
+struct JobEntry {
+    description: JobDescription,
+    /// Never receives anything, it is disconnected once the job has finished.
+    done: Receiver<()>,
+}
+
+struct JobTable {
+    jobs: Vec<JobEntry>,
+    /// The id of the most recently started job. Ids are never reused, so that an id always
+    /// refers to the same job.
+    last_id: usize,
+}
+
+impl JobTable {
+    fn next_id(&mut self) -> usize {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn find(&self, id: usize) -> Option<&JobEntry> {
+        self.jobs.iter().find(|j| j.description.id == id)
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.jobs.retain(|j| j.description.id != id);
+    }
+}
+
+/// Register a running job in the job table and detach it. A thread is created that
+/// waits for the job to finish, marks it as done and notifies the user.
+pub fn spawn(invocation: String, handle: JobJoinHandle, printer: &Printer) -> CrushResult<usize> {
+    let (sender, receiver) = bounded::<()>(0);
+    let mut table = JOBS.lock().unwrap();
+    let id = table.next_id();
+    table.jobs.push(JobEntry {
+        description: JobDescription {
+            id,
+            invocation: invocation.clone(),
+            start_time: Local::now(),
+            state: JobState::Running,
+        },
+        done: receiver,
+    });
+    drop(table);
+
+    let my_printer = printer.clone();
+    let waiter = build("job-waiter").spawn(move || {
+        handle.join(&my_printer);
+        let mut table = JOBS.lock().unwrap();
+        let found = table.jobs.iter_mut().find(|j| j.description.id == id);
+        if let Some(job) = found {
+            job.description.state = JobState::Done;
+            my_printer.line(&format!("[{}] Done  {}", id, invocation));
+        }
+        drop(table);
+        drop(sender);
+    });
+    if waiter.is_err() {
+        // Nothing would ever mark the job as done
+        JOBS.lock().unwrap().remove(id);
+    }
+    to_crush_error(waiter)?;
+    Ok(id)
+}
+
+/// Return a description of all jobs in the job table. Jobs that have finished stay in the
+/// table until they are waited for.
+pub fn list() -> Vec<JobDescription> {
+    JOBS.lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .map(|j| j.description.clone())
+        .collect()
+}
+
+/// The number of jobs that are still running.
+pub fn running() -> usize {
+    JOBS.lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .filter(|j| j.description.state == JobState::Running)
+        .count()
+}
+
+/// The id of the most recently started job that is still in the job table.
+pub fn last() -> Option<usize> {
+    JOBS.lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .map(|j| j.description.id)
+        .max()
+}
+
+/// Block until the job with the specified id has finished, then remove it from the job table.
+/// Returns None if there is no job with that id.
+fn wait_for(id: usize) -> Option<JobDescription> {
+    let (description, done) = {
+        let table = JOBS.lock().unwrap();
+        let job = table.find(id)?;
+        (job.description.clone(), job.done.clone())
+    };
+    let _ = done.recv();
+    JOBS.lock().unwrap().remove(id);
+    Some(description)
+}
+
+/// Block until the job with the specified id has finished, then remove it from the job table.
+pub fn wait(id: usize) -> CrushResult<JobDescription> {
+    match wait_for(id) {
+        Some(description) => Ok(description),
+        None => argument_error(format!("No job with id {}", id)),
+    }
+}
+
+/// Block until all jobs in the job table have finished. Jobs that someone else waits for in
+/// the meantime disappear from the table, and are skipped.
+pub fn wait_all() -> CrushResult<()> {
+    let ids = JOBS
+        .lock()
+        .unwrap()
+        .jobs
+        .iter()
+        .map(|j| j.description.id)
+        .collect::<Vec<_>>();
+    for id in ids {
+        wait_for(id);
+    }
+    Ok(())
+}
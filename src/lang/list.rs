@@ -1,10 +1,11 @@
-use crate::lang::errors::{argument_error, error, mandate, CrushResult};
+use crate::lang::errors::{argument_error, error, mandate, to_crush_error, CrushResult};
 use crate::lang::stream::CrushStream;
 use crate::lang::{table::ColumnType, table::Row, value::Field, value::Value, value::ValueType};
 use crate::util::identity_arc::Identity;
 use chrono::Duration;
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::hash::Hasher;
 use std::sync::{Arc, Mutex};
 use std::fmt::{Display, Formatter};
@@ -185,6 +186,15 @@ impl List {
     dump_to!(dump_type, ValueType, Type, |v: &ValueType| v.clone());
     dump_to!(dump_float, f64, Float, |v: &f64| *v);
     dump_to!(dump_field, Field, Field, |e: &Field| e.clone());
+
+    pub fn dump_usize(&self, destination: &mut Vec<usize>) -> CrushResult<()> {
+        let mut integers = Vec::new();
+        self.dump_integer(&mut integers)?;
+        for i in integers {
+            destination.push(to_crush_error(usize::try_from(i))?);
+        }
+        Ok(())
+    }
 }
 
 impl Display for List {
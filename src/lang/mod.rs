@@ -3,13 +3,19 @@ pub mod ast;
 pub mod binary;
 pub mod command;
 pub mod command_invocation;
+pub mod completion;
+pub mod compression;
 pub mod dict;
+pub mod editor;
 pub mod errors;
 pub mod execute;
 pub mod execution_context;
 pub mod files;
 pub mod help;
+pub mod highlight;
+pub mod history;
 pub mod job;
+pub mod jobs;
 pub mod list;
 pub mod ordered_string_map;
 pub mod parser;
@@ -18,6 +24,7 @@ pub mod pretty_printer;
 pub mod printer;
 pub mod scope;
 pub mod serialization;
+pub mod source;
 pub mod stream;
 pub mod r#struct;
 pub mod table;
@@ -1,6 +1,8 @@
-use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::errors::{error, CrushResult};
 use crate::lang::job::Job;
 use crate::lang::scope::Scope;
+use crate::lang::source::{Location, Source, SourceFile};
+use lalrpop_util::ParseError;
 
 lalrpop_mod!(pub lalrparser, "/lang/lalrparser.rs");
 
@@ -14,6 +16,45 @@ pub fn parse_name(s: &str) -> Option<Vec<String>> {
     Some(res.iter().map(|e| e.to_string()).collect())
 }
 
-pub fn parse(s: &str, env: &Scope) -> CrushResult<Vec<Job>> {
-    to_crush_error(lalrparser::JobListParser::new().parse(s))?.generate(env)
+/**
+    Check if the specified code ends in the middle of something, like a closure, a
+    subshell or a quoted string, so that the user can keep typing on the next line.
+*/
+pub fn is_incomplete(s: &str) -> bool {
+    let file = SourceFile::new("<input>", s);
+    match lalrparser::JobListParser::new().parse(&file, s) {
+        Err(ParseError::UnrecognizedEOF { .. }) => true,
+        Err(ParseError::InvalidToken { location }) => {
+            let rest = &s[location..];
+            // An unterminated string, or a backslash at the end of the line
+            rest.starts_with('"') || rest.starts_with('\'') || rest == "\\"
+        }
+        _ => false,
+    }
+}
+
+/// Parse the specified code. The name describes where the code came from, e.g. a file name,
+/// and is used when reporting errors.
+pub fn parse(s: &str, name: &str, env: &Scope) -> CrushResult<Vec<Job>> {
+    let file = SourceFile::new(name, s);
+    match lalrparser::JobListParser::new().parse(&file, s) {
+        Ok(jobs) => jobs.generate(env),
+        Err(e) => {
+            let location = match &e {
+                ParseError::InvalidToken { location }
+                | ParseError::UnrecognizedEOF { location, .. } => {
+                    Location::new(*location, *location)
+                }
+                ParseError::UnrecognizedToken {
+                    token: (start, _, end),
+                    ..
+                }
+                | ParseError::ExtraToken {
+                    token: (start, _, end),
+                } => Location::new(*start, *end),
+                ParseError::User { .. } => Location::new(0, 0),
+            };
+            error(e.to_string()).map_err(|err| err.with_source(&Some(Source::new(&file, location))))
+        }
+    }
 }
@@ -1,6 +1,8 @@
-use crate::lang::errors::{to_crush_error, CrushError, CrushResult};
-use crossbeam::bounded;
+use crate::lang::errors::{to_crush_error, CrushError, CrushErrorType, CrushResult};
 use crossbeam::Sender;
+use crossbeam::{bounded, unbounded, Receiver};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
 
 enum PrinterMessage {
@@ -17,20 +19,28 @@ use termion::terminal_size;
 #[derive(Clone)]
 pub struct Printer {
     sender: Sender<PrinterMessage>,
+    /// If set, errors are sent here instead of being printed.
+    errors: Option<Sender<CrushError>>,
+    /// If set, incremented for every crush error that is reported.
+    error_count: Option<Arc<AtomicUsize>>,
 }
 
 pub fn init() -> (Printer, JoinHandle<()>) {
     let (sender, receiver) = bounded(128);
 
     (
-        Printer { sender: sender },
+        Printer {
+            sender,
+            errors: None,
+            error_count: None,
+        },
         thread::Builder::new()
             .name("printer".to_string())
             .spawn(move || {
                 while let Ok(message) = receiver.recv() {
                     match message {
                         Error(err) => eprintln!("Error: {}", err),
-                        CrushError(err) => eprintln!("Error: {}", err.message()),
+                        CrushError(err) => print_crush_error(&err),
                         Line(line) => println!("{}", line),
                         //                        Lines(lines) => for line in lines {println!("{}", line)},
                     }
@@ -40,6 +50,22 @@ pub fn init() -> (Printer, JoinHandle<()>) {
     )
 }
 
+fn print_crush_error(err: &CrushError) {
+    match err.command() {
+        Some(command) => eprintln!("Error: {}: {}", command, err.message()),
+        None => eprintln!("Error: {}", err.message()),
+    }
+    if let Some(source) = err.source() {
+        let line = source.line().to_string();
+        let margin = " ".repeat(line.len());
+        let (code, underline) = source.excerpt();
+        eprintln!("{}--> {}:{}:{}", margin, source.name(), line, source.column());
+        eprintln!("{} |", margin);
+        eprintln!("{} | {}", line, code);
+        eprintln!("{} | {}", margin, underline);
+    }
+}
+
 impl Printer {
     pub fn line(&self, line: &str) {
         self.handle_error(to_crush_error(
@@ -53,16 +79,70 @@ impl Printer {
     */
     pub fn handle_error<T>(&self, result: CrushResult<T>) {
         if let Err(e) = result {
-            if e != CrushError::SendError {
+            if !e.is(CrushErrorType::SendError) {
                 self.crush_error(e)
             }
         }
     }
 
     pub fn crush_error(&self, err: CrushError) {
+        self.count_error();
+        let err = match &self.errors {
+            Some(errors) => match errors.send(err) {
+                Ok(()) => return,
+                Err(e) => e.into_inner(),
+            },
+            None => err,
+        };
         let _ = self.sender.send(PrinterMessage::CrushError(err));
     }
 
+    /// Create a printer that doesn't print errors, but sends them to the returned receiver.
+    /// The receiver is disconnected once the printer and all clones of it have been dropped,
+    /// which happens once all commands using it have finished.
+    pub fn capture_errors(&self) -> (Printer, Receiver<CrushError>) {
+        let (sender, receiver) = unbounded();
+        (
+            Printer {
+                sender: self.sender.clone(),
+                errors: Some(sender),
+                error_count: None,
+            },
+            receiver,
+        )
+    }
+
+    /// Create a printer that works like this one, but that also counts the crush errors reported
+    /// through it and all clones of it. Plain error messages, like the standard error of
+    /// external commands, are not counted.
+    pub fn count_errors(&self) -> (Printer, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        (
+            Printer {
+                sender: self.sender.clone(),
+                errors: self.errors.clone(),
+                error_count: Some(count.clone()),
+            },
+            count,
+        )
+    }
+
+    /// Create a printer that works like this one, for use by a background job. Errors are
+    /// printed instead of being captured or counted, since nothing waits for the job to finish.
+    pub fn background(&self) -> Printer {
+        Printer {
+            sender: self.sender.clone(),
+            errors: None,
+            error_count: None,
+        }
+    }
+
+    fn count_error(&self) {
+        if let Some(count) = &self.error_count {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
     pub fn error(&self, err: &str) {
         let _ = self.sender.send(PrinterMessage::Error(err.to_string()));
     }
@@ -580,16 +580,37 @@ impl Scope {
         }
     }
 
+    /// Look up a variable by following the chain of calling scopes instead of the chain of
+    /// parent scopes, i.e. using dynamic instead of lexical scoping. Only variables declared
+    /// directly in each scope are considered, used scopes are ignored.
+    pub fn get_calling(&self, name: &str) -> CrushResult<Option<Value>> {
+        let data = self.lock()?;
+        match data.mapping.get(name) {
+            Some(v) => Ok(Some(v.clone())),
+            None => match data.calling_scope.clone() {
+                Some(caller) => {
+                    drop(data);
+                    caller.get_calling(name)
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
     pub fn r#use(&self, other: &Scope) {
         self.data.lock().unwrap().uses.push(other.clone());
     }
 
     pub fn dump(&self, map: &mut OrderedMap<String, ValueType>) -> CrushResult<()> {
-        if let Some(p) = self.lock()?.parent_scope.clone() {
+        // Don't hold on to the lock while dumping other scopes, loading a lazy namespace
+        // will lock its parents.
+        let parent = self.lock()?.parent_scope.clone();
+        if let Some(p) = parent {
             p.dump(map)?;
         }
 
-        for u in self.data.lock().unwrap().uses.clone().iter().rev() {
+        let uses = self.data.lock().unwrap().uses.clone();
+        for u in uses.iter().rev() {
             u.dump(map)?;
         }
 
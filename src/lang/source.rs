@@ -0,0 +1,134 @@
+use std::cmp::{max, min};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// The full text of a piece of code, e.g. a script file or a line of interactive input,
+/// along with a name describing where it came from.
+pub struct SourceFile {
+    name: String,
+    text: String,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> Arc<SourceFile> {
+        Arc::new(SourceFile {
+            name: name.to_string(),
+            text: text.to_string(),
+        })
+    }
+}
+
+/// A byte range in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Location {
+    pub fn new(start: usize, end: usize) -> Location {
+        Location { start, end }
+    }
+}
+
+/// The location of a piece of code, e.g. a command invocation, in a source file.
+#[derive(Clone)]
+pub struct Source {
+    file: Arc<SourceFile>,
+    location: Location,
+}
+
+impl Source {
+    pub fn new(file: &Arc<SourceFile>, location: Location) -> Source {
+        Source {
+            file: file.clone(),
+            location,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.file.name
+    }
+
+    fn start(&self) -> usize {
+        min(self.location.start, self.file.text.len())
+    }
+
+    /// The line number of the start of the source, counting from one.
+    pub fn line(&self) -> usize {
+        self.file.text[..self.start()].matches('\n').count() + 1
+    }
+
+    /// The column of the start of the source, counting from one.
+    pub fn column(&self) -> usize {
+        let text = &self.file.text[..self.start()];
+        match text.rfind('\n') {
+            Some(idx) => text[idx + 1..].chars().count() + 1,
+            None => text.chars().count() + 1,
+        }
+    }
+
+    /// The line of code containing the start of the source, and a line with carets
+    /// underlining the source. Only the first line of sources spanning multiple lines
+    /// is underlined.
+    pub fn excerpt(&self) -> (String, String) {
+        let text = &self.file.text;
+        let start = self.start();
+        let line_start = text[..start].rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        let line_end = text[start..]
+            .find('\n')
+            .map(|idx| idx + start)
+            .unwrap_or_else(|| text.len());
+        let end = max(start, min(self.location.end, line_end));
+        let line = text[line_start..line_end].to_string();
+        let underline = format!(
+            "{}{}",
+            " ".repeat(text[line_start..start].chars().count()),
+            "^".repeat(max(1, text[start..end].chars().count()))
+        );
+        (line, underline)
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file) && self.location == other.location
+    }
+}
+
+impl Eq for Source {}
+
+impl Debug for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.name(), self.line(), self.column())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_and_column() {
+        let file = SourceFile::new("test", "echo 1\nls | json:from foo\n");
+        let source = Source::new(&file, Location::new(12, 25));
+        assert_eq!(source.line(), 2);
+        assert_eq!(source.column(), 6);
+        assert_eq!(
+            source.excerpt(),
+            (
+                "ls | json:from foo".to_string(),
+                "     ^^^^^^^^^^^^^".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn multi_line_excerpt() {
+        let file = SourceFile::new("test", "for (seq 3) {\n  echo value\n}");
+        let source = Source::new(&file, Location::new(0, 28));
+        assert_eq!(source.line(), 1);
+        assert_eq!(source.column(), 1);
+        assert_eq!(source.excerpt().1, "^".repeat(13));
+    }
+}
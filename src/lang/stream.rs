@@ -22,9 +22,32 @@ pub fn black_hole() -> ValueSender {
 #[derive(Clone)]
 pub struct ValueSender {
     sender: Sender<Value>,
+    is_terminal: bool,
 }
 
 impl ValueSender {
+    /// Returns a copy of this sender that is marked as printing directly to the terminal
+    /// of an interactive session. External commands writing to such a sender may take over
+    /// the terminal instead of sending their output through the sender.
+    pub fn terminal(&self) -> ValueSender {
+        ValueSender {
+            sender: self.sender.clone(),
+            is_terminal: true,
+        }
+    }
+
+    /// Returns a copy of this sender that is not marked as printing to the terminal.
+    pub fn without_terminal(&self) -> ValueSender {
+        ValueSender {
+            sender: self.sender.clone(),
+            is_terminal: false,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.is_terminal
+    }
+
     pub fn send(&self, cell: Value) -> CrushResult<()> {
         match self.sender.send(cell) {
             Ok(_) => Ok(()),
@@ -110,7 +133,8 @@ impl InputStream {
                     return error("Wrong number of columns in io");
                 }
                 for (c, ct) in row.cells().iter().zip(self.types.iter()) {
-                    if !ct.cell_type.is(c) {
+                    // Columns of any type may hold empty values, e.g. for missing fields
+                    if !matches!(c, Value::Empty()) && !ct.cell_type.is(c) {
                         return error(
                             format!(
                                 "Wrong cell type in io column {:?}, expected {:?}, got {:?}",
@@ -132,7 +156,10 @@ impl InputStream {
 pub fn channels() -> (ValueSender, ValueReceiver) {
     let (send, recv) = bounded(1);
     (
-        ValueSender { sender: send },
+        ValueSender {
+            sender: send,
+            is_terminal: false,
+        },
         ValueReceiver { receiver: recv },
     )
 }
@@ -1,4 +1,5 @@
 mod value_definition;
+mod value_key;
 mod value_type;
 
 use std::cmp::Ordering;
@@ -34,6 +35,7 @@ use crate::lang::printer::Printer;
 use crate::util::regex::RegexFileMatcher;
 use ordered_map::OrderedMap;
 pub use value_definition::ValueDefinition;
+pub use value_key::ValueKey;
 pub use value_type::ValueType;
 use std::fmt::{Display, Formatter};
 
@@ -129,8 +129,21 @@ impl Display for ValueDefinition {
         match &self {
             ValueDefinition::Value(v) => v.fmt(f),
             ValueDefinition::Label(v) => v.fmt(f),
-            ValueDefinition::ClosureDefinition(_, _, _) => f.write_str("<closure>"),
-            ValueDefinition::JobDefinition(_) => f.write_str("<job>"),
+            ValueDefinition::ClosureDefinition(_, _, jobs) => {
+                f.write_str("{")?;
+                for (idx, j) in jobs.iter().enumerate() {
+                    if idx != 0 {
+                        f.write_str("; ")?;
+                    }
+                    j.fmt(f)?;
+                }
+                f.write_str("}")
+            }
+            ValueDefinition::JobDefinition(j) => {
+                f.write_str("(")?;
+                j.fmt(f)?;
+                f.write_str(")")
+            }
             ValueDefinition::GetAttr(v, l) => {
                 v.fmt(f)?;
                 f.write_str(":")?;
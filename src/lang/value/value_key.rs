@@ -0,0 +1,50 @@
+use crate::lang::value::Value;
+use std::path::PathBuf;
+
+/// A value used as the key of a hash map, to group equal values together. Empty values, NaN
+/// and files never compare equal to themselves, so they are given their own variants to keep
+/// all occurrences of them in a single group. Zero and negative zero are equal, but hash
+/// differently, so both are stored as zero.
+#[derive(PartialEq, Eq, Hash)]
+pub enum ValueKey {
+    Empty,
+    NaN,
+    File(PathBuf),
+    Value(Value),
+}
+
+impl ValueKey {
+    pub fn new(value: &Value) -> ValueKey {
+        match value {
+            Value::Empty() => ValueKey::Empty,
+            Value::Float(f) if f.is_nan() => ValueKey::NaN,
+            Value::Float(f) if *f == 0.0 => ValueKey::Value(Value::Float(0.0)),
+            Value::File(f) => ValueKey::File(f.clone()),
+            value => ValueKey::Value(value.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn equal_values_have_equal_keys() {
+        let values = vec![
+            Value::Empty(),
+            Value::Empty(),
+            Value::Float(f64::NAN),
+            Value::Float(f64::NAN),
+            Value::Float(0.0),
+            Value::Float(-0.0),
+            Value::File(PathBuf::from("a")),
+            Value::File(PathBuf::from("a")),
+            Value::Integer(1),
+            Value::Integer(1),
+        ];
+        let keys: HashSet<ValueKey> = values.iter().map(ValueKey::new).collect();
+        assert_eq!(keys.len(), 5);
+    }
+}
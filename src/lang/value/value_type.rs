@@ -56,7 +56,7 @@ impl ValueType {
             ValueType::Time => &types::time::METHODS,
             ValueType::Table(_) => &types::table::METHODS,
             ValueType::TableStream(_) => &types::table_stream::METHODS,
-            ValueType::Binary => &types::binary::METHODS,
+            ValueType::Binary | ValueType::BinaryStream => &types::binary::METHODS,
             ValueType::Scope => &types::scope::METHODS,
             _ => &EMPTY_METHODS,
         }
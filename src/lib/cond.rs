@@ -23,6 +23,7 @@ pub fn and(mut context: CommandContext) -> CrushResult<()> {
                     scope: context.scope.clone(),
                     this: None,
                     printer: context.printer.clone(),
+                    background: context.background,
                 };
                 c.invoke(cc)?;
                 match receiver.recv()? {
@@ -61,6 +62,7 @@ pub fn or(mut context: CommandContext) -> CrushResult<()> {
                     scope: context.scope.clone(),
                     this: None,
                     printer: context.printer.clone(),
+                    background: context.background,
                 };
                 c.invoke(cc)?;
                 match receiver.recv()? {
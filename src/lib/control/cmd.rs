@@ -0,0 +1,411 @@
+use crate::lang::binary::binary_channel;
+use crate::lang::errors::{
+    argument_error, external_command_error, mandate, to_crush_error, CrushResult,
+};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::printer::Printer;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::Scope;
+use crate::lang::value::Value;
+use crate::lib::env;
+use crate::util::thread::build;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+static LAST_STATUS: AtomicI32 = AtomicI32::new(0);
+static STATUS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The exit status of the most recently finished external command of a foreground job.
+/// Commands killed by a signal get the status 128 + the signal number, like in other shells.
+pub fn last_status() -> i32 {
+    LAST_STATUS.load(Ordering::Relaxed)
+}
+
+/// The number of exit statuses recorded so far. If it hasn't changed while running something,
+/// no external command of a foreground job finished in the meantime.
+pub fn status_count() -> usize {
+    STATUS_COUNT.load(Ordering::Relaxed)
+}
+
+fn record_status(status: ExitStatus) {
+    let code = status
+        .code()
+        .or_else(|| status.signal().map(|s| 128 + s))
+        .unwrap_or(1);
+    LAST_STATUS.store(code, Ordering::Relaxed);
+    STATUS_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn cmd(mut context: CommandContext) -> CrushResult<()> {
+    // Named arguments before the external command are options to cmd itself
+    let mut capture = false;
+    while !context.arguments.is_empty() {
+        match (
+            &context.arguments[0].argument_type,
+            &context.arguments[0].value,
+        ) {
+            (Some(name), Value::Bool(value)) if name == "capture" => {
+                capture = *value;
+                context.arguments.remove(0);
+            }
+            (Some(name), _) => return argument_error(format!("Unknown argument {}", name)),
+            (None, _) => break,
+        }
+    }
+    if context.arguments.is_empty() {
+        return argument_error("No command given");
+    }
+    match context.arguments.remove(0).value {
+        Value::File(f) => {
+            let mut cmd = Command::new(f.as_os_str());
+            env::apply(&context.scope, &mut cmd)?;
+            for a in context.arguments.drain(..) {
+                match a.argument_type {
+                    None => {
+                        cmd.arg(a.value.to_string());
+                    }
+                    Some(name) => {
+                        if name.len() == 1 {
+                            cmd.arg(format!("-{}", name));
+                        } else {
+                            cmd.arg(format!("--{}", name));
+                        }
+                        match a.value {
+                            Value::Bool(true) => {}
+                            _ => {
+                                cmd.arg(a.value.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            let input = context.input.recv()?;
+            if has_input(&input)? {
+                cmd.stdin(Stdio::piped());
+            } else if !capture && context.output.is_terminal() && is_interactive() {
+                cmd.stdin(Stdio::inherit());
+            } else {
+                cmd.stdin(Stdio::null());
+            }
+
+            let scope = context.scope.clone();
+            let background = context.background;
+            if capture {
+                run_captured(cmd, input, context)?;
+                Ok(())
+            } else {
+                let status = if context.output.is_terminal() && is_interactive() {
+                    run_in_terminal(cmd, input, context)?
+                } else if use_pty(&scope)? {
+                    run_in_pty(cmd, input, context)?
+                } else {
+                    run_with_pipes(cmd, input, context)?
+                };
+                if !background {
+                    record_status(status);
+                }
+                check_status(&f, status, &scope)
+            }
+        }
+        _ => argument_error("Not a valid command"),
+    }
+}
+
+fn is_interactive() -> bool {
+    termion::is_tty(&std::io::stdin()) && termion::is_tty(&std::io::stdout())
+}
+
+fn use_pty(scope: &Scope) -> CrushResult<bool> {
+    Ok(matches!(scope.get("cmd_pty")?, Some(Value::Bool(true))))
+}
+
+fn use_strict(scope: &Scope) -> CrushResult<bool> {
+    Ok(matches!(scope.get("cmd_strict")?, Some(Value::Bool(true))))
+}
+
+fn status_code(status: ExitStatus) -> Value {
+    status
+        .code()
+        .map(|c| Value::Integer(c as i128))
+        .unwrap_or(Value::Empty())
+}
+
+fn status_signal(status: ExitStatus) -> Value {
+    status
+        .signal()
+        .map(|s| Value::Integer(s as i128))
+        .unwrap_or(Value::Empty())
+}
+
+/// In strict mode, an external command that fails is an error.
+fn check_status(command: &Path, status: ExitStatus, scope: &Scope) -> CrushResult<()> {
+    if status.success() || !use_strict(scope)? {
+        return Ok(());
+    }
+    let name = command.to_str().unwrap_or("<invalid filename>");
+    match (status.code(), status.signal()) {
+        (Some(code), _) => {
+            external_command_error(format!("{} exited with status {}", name, code))
+        }
+        (None, Some(signal)) => {
+            external_command_error(format!("{} was killed by signal {}", name, signal))
+        }
+        (None, None) => external_command_error(format!("{} failed", name)),
+    }
+}
+
+/// Check if the input of the command is something that should be written to the standard
+/// input of the external command. The first command of a job receives an empty table stream
+/// without any columns, which is treated as no input.
+fn has_input(input: &Value) -> CrushResult<bool> {
+    match input {
+        Value::Empty() => Ok(false),
+        Value::TableStream(s) if s.types().is_empty() => Ok(false),
+        Value::String(_) | Value::Binary(_) | Value::BinaryStream(_) => Ok(true),
+        v => match v.stream() {
+            Some(s) if s.types().len() == 1 => Ok(true),
+            Some(_) => argument_error("Expected a stream with a single column as input"),
+            None => argument_error(format!(
+                "Can't write a value of type {} to an external command",
+                v.value_type()
+            )),
+        },
+    }
+}
+
+/// Run the rest of a command on its spawned child. If that fails, the child is killed and
+/// reaped before the error is returned, so that it is not left behind as a zombie.
+fn supervise<T>(
+    child: &mut Child,
+    run: impl FnOnce(&mut Child) -> CrushResult<T>,
+) -> CrushResult<T> {
+    let res = run(child);
+    if res.is_err() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    res
+}
+
+/// Write the input to the standard input of the external command on a separate thread, so
+/// that commands that read and write at the same time don't deadlock.
+fn feed_input(child: &mut Child, input: Value, printer: &Printer) -> CrushResult<()> {
+    if let Some(stdin) = child.stdin.take() {
+        let my_printer = printer.clone();
+        to_crush_error(
+            build("cmd:stdin").spawn(move || my_printer.handle_error(write_input(input, stdin))),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_input(input: Value, mut stdin: ChildStdin) -> CrushResult<()> {
+    let res = match input {
+        Value::String(s) => {
+            if s.ends_with('\n') {
+                stdin.write_all(s.as_bytes())
+            } else {
+                stdin
+                    .write_all(s.as_bytes())
+                    .and_then(|_| stdin.write_all(b"\n"))
+            }
+        }
+        Value::Binary(b) => stdin.write_all(&b),
+        Value::BinaryStream(mut b) => std::io::copy(&mut b, &mut stdin).map(|_| ()),
+        v => {
+            let mut stream = mandate(v.stream(), "Expected a stream")?;
+            let mut res = Ok(());
+            while let Ok(row) = stream.read() {
+                res = stdin.write_all(format!("{}\n", row.cells()[0]).as_bytes());
+                if res.is_err() {
+                    break;
+                }
+            }
+            res
+        }
+    };
+    match res {
+        // The command exited without reading all of its input, which is fine
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        r => to_crush_error(r),
+    }
+}
+
+/// The command is the last command of an interactive job, so we let it take over the
+/// terminal. This is what makes e.g. editors and pagers work.
+fn run_in_terminal(
+    mut cmd: Command,
+    input: Value,
+    context: CommandContext,
+) -> CrushResult<ExitStatus> {
+    let mut child = to_crush_error(cmd.spawn())?;
+    let status = supervise(&mut child, |child| {
+        feed_input(child, input, &context.printer)?;
+        to_crush_error(child.wait())
+    })?;
+    context.output.send(Value::Empty())?;
+    Ok(status)
+}
+
+/// Wait for the command to finish and return a struct with its exit status and all of its
+/// output, instead of streaming the output.
+fn run_captured(mut cmd: Command, input: Value, context: CommandContext) -> CrushResult<()> {
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = to_crush_error(cmd.spawn())?;
+    let (status, output, errors) = supervise(&mut child, |child| {
+        feed_input(child, input, &context.printer)?;
+        let mut stdout = mandate(
+            child.stdout.take(),
+            "Missing output stream of external command",
+        )?;
+        let mut stderr = mandate(
+            child.stderr.take(),
+            "Missing error stream of external command",
+        )?;
+
+        let error_handle = to_crush_error(build("cmd:stderr").spawn(move || {
+            let mut errors = Vec::new();
+            let _ = stderr.read_to_end(&mut errors);
+            errors
+        }))?;
+        let mut output = Vec::new();
+        to_crush_error(stdout.read_to_end(&mut output))?;
+        let status = to_crush_error(child.wait())?;
+        Ok((status, output, error_handle.join().unwrap_or_default()))
+    })?;
+    if !context.background {
+        record_status(status);
+    }
+
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("status".to_string(), status_code(status)),
+            ("signal".to_string(), status_signal(status)),
+            ("stdout".to_string(), Value::Binary(output)),
+            ("stderr".to_string(), Value::Binary(errors)),
+        ],
+        None,
+    )))
+}
+
+fn run_with_pipes(
+    mut cmd: Command,
+    input: Value,
+    context: CommandContext,
+) -> CrushResult<ExitStatus> {
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = to_crush_error(cmd.spawn())?;
+    supervise(&mut child, |child| {
+        feed_input(child, input, &context.printer)?;
+        let stdout = mandate(
+            child.stdout.take(),
+            "Missing output stream of external command",
+        )?;
+        let stderr = mandate(
+            child.stderr.take(),
+            "Missing error stream of external command",
+        )?;
+
+        let printer = context.printer.clone();
+        let error_handle =
+            to_crush_error(build("cmd:stderr").spawn(move || print_errors(stderr, &printer)))?;
+
+        let (writer, reader) = binary_channel();
+        context.output.send(Value::BinaryStream(reader))?;
+        copy(stdout, writer, false)?;
+
+        let status = to_crush_error(child.wait())?;
+        let _ = error_handle.join();
+        Ok(status)
+    })
+}
+
+fn print_errors(stream: impl Read, printer: &Printer) {
+    for line in BufReader::new(stream).lines() {
+        match line {
+            Ok(line) => {
+                let err = line.trim();
+                if !err.is_empty() {
+                    printer.error(err);
+                }
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+/// Copy the output of an external command to a binary stream, as it becomes available.
+fn copy(mut from: impl Read, mut to: Box<dyn Write>, pty: bool) -> CrushResult<()> {
+    let mut buff = [0u8; 4096];
+    loop {
+        match from.read(&mut buff) {
+            Ok(0) => break,
+            Ok(len) => to_crush_error(to.write_all(&buff[0..len]))?,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            // Reading from the master side of a pty fails with EIO once the child has exited
+            Err(e) if pty && e.raw_os_error() == Some(nix::libc::EIO) => break,
+            Err(e) => return to_crush_error(Err(e)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn run_in_pty(mut cmd: Command, input: Value, context: CommandContext) -> CrushResult<ExitStatus> {
+    use nix::libc;
+    use nix::pty::{openpty, Winsize};
+    use nix::sys::termios::{tcgetattr, tcsetattr, OutputFlags, SetArg};
+    use nix::unistd::setsid;
+    use std::fs::File;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::os::unix::process::CommandExt;
+
+    let size = termion::terminal_size().ok().map(|(cols, rows)| Winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    });
+    let pty = to_crush_error(openpty(size.as_ref(), None))?;
+    // Own both sides right away, so that they are closed if anything below fails
+    let master = unsafe { File::from_raw_fd(pty.master) };
+    let slave = unsafe { File::from_raw_fd(pty.slave) };
+    // Don't turn newlines into carriage return + newline, the output is not going to a terminal
+    let mut termios = to_crush_error(tcgetattr(slave.as_raw_fd()))?;
+    termios.output_flags.remove(OutputFlags::ONLCR);
+    to_crush_error(tcsetattr(slave.as_raw_fd(), SetArg::TCSANOW, &termios))?;
+    let slave_copy = to_crush_error(slave.try_clone())?;
+    cmd.stdout(Stdio::from(slave))
+        .stderr(Stdio::from(slave_copy));
+    unsafe {
+        cmd.pre_exec(|| {
+            // Detach from the terminal of the shell, and make the pty, which is already the
+            // standard output, the controlling terminal of the new session
+            setsid().map_err(|_| std::io::Error::last_os_error())?;
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCSCTTY, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = to_crush_error(cmd.spawn())?;
+    // Close our copies of the slave side, or we will never see the end of the output
+    drop(cmd);
+    supervise(&mut child, |child| {
+        feed_input(child, input, &context.printer)?;
+
+        let (writer, reader) = binary_channel();
+        context.output.send(Value::BinaryStream(reader))?;
+        copy(master, writer, true)?;
+
+        to_crush_error(child.wait())
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_in_pty(cmd: Command, input: Value, context: CommandContext) -> CrushResult<ExitStatus> {
+    run_with_pipes(cmd, input, context)
+}
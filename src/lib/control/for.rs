@@ -44,6 +44,7 @@ pub fn r#for(mut context: CommandContext) -> CrushResult<()> {
             scope: env.clone(),
             this: None,
             printer: context.printer.clone(),
+            background: context.background,
         })?;
         if env.is_stopped() {
             break;
@@ -1,19 +1,23 @@
-use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::errors::{to_crush_error, CrushResult};
 use crate::lang::scope::Scope;
 use crate::lang::{
-    binary::BinaryReader, execution_context::CommandContext, list::List, value::Value,
+    execution_context::CommandContext, list::List, value::Value,
     value::ValueType,
 };
 use signature::signature;
 use std::env;
 
+mod cmd;
 mod r#for;
 mod r#if;
 mod r#loop;
+mod r#try;
 mod r#while;
 
+pub use cmd::{last_status, status_count};
+
 use crate::lang::argument::ArgumentHandler;
-use crate::lang::command::OutputType::Known;
+use crate::lang::command::OutputType::{Known, Unknown};
 use chrono::Duration;
 use std::path::PathBuf;
 
@@ -27,49 +31,6 @@ pub fn r#continue(context: CommandContext) -> CrushResult<()> {
     context.output.empty()
 }
 
-pub fn cmd(mut context: CommandContext) -> CrushResult<()> {
-    if context.arguments.is_empty() {
-        return argument_error("No command given");
-    }
-    match context.arguments.remove(0).value {
-        Value::File(f) => {
-            let mut cmd = std::process::Command::new(f.as_os_str());
-            for a in context.arguments.drain(..) {
-                match a.argument_type {
-                    None => {
-                        cmd.arg(a.value.to_string());
-                    }
-                    Some(name) => {
-                        if name.len() == 1 {
-                            cmd.arg(format!("-{}", name));
-                        } else {
-                            cmd.arg(format!("--{}", name));
-                        }
-                        match a.value {
-                            Value::Bool(true) => {}
-                            _ => {
-                                cmd.arg(a.value.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-            let output = to_crush_error(cmd.output())?;
-            let errors = String::from_utf8_lossy(&output.stderr);
-            for e in errors.split('\n') {
-                let err = e.trim();
-                if !err.is_empty() {
-                    context.printer.error(err);
-                }
-            }
-            context
-                .output
-                .send(Value::BinaryStream(BinaryReader::vec(&output.stdout)))
-        }
-        _ => argument_error("Not a valid command"),
-    }
-}
-
 #[signature(
     sleep,
     can_block = true,
@@ -88,22 +49,29 @@ pub fn sleep(context: CommandContext) -> CrushResult<()> {
     Ok(())
 }
 
+/// The directories of a colon separated search path, in the format used by `cmd_path`.
+pub fn path_dirs(path: &str) -> Vec<Value> {
+    path.split(':')
+        .map(|s| Value::File(PathBuf::from(s)))
+        .collect()
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_namespace(
         "control",
         Box::new(move |env| {
             let path = List::new(ValueType::File, vec![]);
             to_crush_error(env::var("PATH").map(|v| {
-                let mut dirs: Vec<Value> = v
-                    .split(':')
-                    .map(|s| Value::File(PathBuf::from(s)))
-                    .collect();
-                let _ = path.append(&mut dirs);
+                let _ = path.append(&mut path_dirs(&v));
             }))?;
             env.declare("cmd_path", Value::List(path))?;
+            env.declare("cmd_pty", Value::Bool(false))?;
+            env.declare("cmd_strict", Value::Bool(false))?;
             r#if::If::declare(env)?;
             r#while::While::declare(env)?;
             r#loop::Loop::declare(env)?;
+            r#try::Try::declare(env)?;
+            r#try::Raise::declare(env)?;
 
             env.declare_condition_command(
                 "for",
@@ -139,12 +107,33 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             )?;
             env.declare_command(
                 "cmd",
-                cmd,
+                cmd::cmd,
                 true,
-                "cmd external_command:(file|string) @arguments:any",
+                "cmd [capture=bool] external_command:(file|string) @arguments:any",
                 "Execute external commands",
-                None,
-                Known(ValueType::BinaryStream),
+                Some(
+                    r#"    The output of the command is returned as a binary stream, which is
+    filled in as the command produces it.
+
+    If the input of the command is a string, a binary, a binary stream or
+    a stream with a single column, it is written to the standard input of
+    the command. Streams are written one row per line.
+
+    If capture is true, cmd waits for the command to finish and returns a
+    struct with the fields status, signal, stdout and stderr instead.
+
+    If a variable named cmd_strict with the value true is in scope, an
+    external command that exits with a non-zero status is an error.
+
+    If the command is the last command of an interactive job, it is
+    instead given direct access to the terminal. If a variable named
+    cmd_pty with the value true is in scope, commands that do not have
+    access to the terminal are run inside of a pseudo-terminal, e.g.
+
+    cmd_pty := true
+    git:log | lines:from"#,
+                ),
+                Unknown,
             )?;
             Sleep::declare(env)?;
             Ok(())
@@ -0,0 +1,95 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::command::Command;
+use crate::lang::errors::{CrushError, CrushErrorType, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::r#struct::Struct;
+use crate::lang::value::Value;
+use signature::signature;
+
+#[signature(
+    r#try,
+    can_block = true,
+    short = "Execute a command, and recover if it fails.",
+    long = "If the body, or any command it runs, fails, the error is not printed. Instead, the catch command is invoked with a named argument error, a struct with the fields kind, message, command and location. The command and location fields are empty if unknown, otherwise location is a struct with the fields file, line and column.",
+    long = "If more than one error happens in the body, the catch command is invoked with the first one. Without a catch command, the error is simply ignored.",
+    long = "Background jobs started by the body are not waited for, and their errors are not caught.",
+    example = "try {json:from ./missing.json} catch={echo (\"Failed: {}\":format error:message)}"
+)]
+pub struct Try {
+    #[description("the command to run.")]
+    body: Command,
+    #[description("the command to run if body fails.")]
+    catch: Option<Command>,
+}
+
+fn error_value(err: &CrushError) -> Value {
+    let location = match err.source() {
+        Some(source) => Value::Struct(Struct::new(
+            vec![
+                ("file".to_string(), Value::string(source.name())),
+                ("line".to_string(), Value::Integer(source.line() as i128)),
+                ("column".to_string(), Value::Integer(source.column() as i128)),
+            ],
+            None,
+        )),
+        None => Value::Empty(),
+    };
+    Value::Struct(Struct::new(
+        vec![
+            ("kind".to_string(), Value::string(err.kind())),
+            ("message".to_string(), Value::string(&err.message())),
+            (
+                "command".to_string(),
+                err.command().map(Value::string).unwrap_or(Value::Empty()),
+            ),
+            ("location".to_string(), location),
+        ],
+        None,
+    ))
+}
+
+fn r#try(context: CommandContext) -> CrushResult<()> {
+    let cfg: Try = Try::parse(context.arguments.clone(), &context.printer)?;
+    let (printer, errors) = context.printer.capture_errors();
+
+    let mut first = cfg
+        .body
+        .invoke(context.clone().with_args(vec![], None).with_printer(printer))
+        .err();
+    // Wait for everything started by the body to finish
+    for err in errors.iter() {
+        if first.is_none() {
+            first = Some(err);
+        }
+    }
+
+    match (first, cfg.catch) {
+        (None, _) | (Some(_), None) => Ok(()),
+        (Some(err), Some(catch)) => catch.invoke(
+            context.with_args(vec![Argument::named("error", error_value(&err))], None),
+        ),
+    }
+}
+
+#[signature(
+    raise,
+    can_block = false,
+    short = "Fail with the specified error message.",
+    long = "The error can be handled using the try command, which sees the kind as error:kind.",
+    example = "if a < 0 {raise \"a must not be negative\" kind=\"negative\"}"
+)]
+pub struct Raise {
+    #[description("the error message.")]
+    message: String,
+    #[default("generic")]
+    #[description("the kind of the error.")]
+    kind: String,
+}
+
+fn raise(context: CommandContext) -> CrushResult<()> {
+    let cfg: Raise = Raise::parse(context.arguments, &context.printer)?;
+    Err(CrushError::new(CrushErrorType::RaisedError(
+        cfg.kind,
+        cfg.message,
+    )))
+}
@@ -3,7 +3,7 @@ use crate::lang::command::CrushCommand;
 use crate::lang::command::OutputType::*;
 use crate::lang::dict::Dict;
 use crate::lang::errors::{
-    argument_error, data_error, eof_error, error, mandate, to_crush_error, CrushError,
+    argument_error, data_error, eof_error, error, mandate, to_crush_error, CrushErrorType,
     CrushResult,
 };
 use crate::lang::execution_context::CommandContext;
@@ -438,7 +438,7 @@ fn deserialize(iter: &mut dbus::arg::Iter) -> CrushResult<Value> {
                             entry.next();
                             value
                         }
-                        Err(CrushError::EOFError) => break,
+                        Err(e) if e.is(CrushErrorType::EOFError) => break,
                         Err(e) => return Err(e),
                     };
 
@@ -448,7 +448,7 @@ fn deserialize(iter: &mut dbus::arg::Iter) -> CrushResult<Value> {
                             entry.next();
                             value
                         }
-                        Err(CrushError::EOFError) => {
+                        Err(e) if e.is(CrushErrorType::EOFError) => {
                             return error("Unexpected EOF in dbus message")
                         }
                         Err(e) => return Err(e),
@@ -487,7 +487,7 @@ fn deserialize(iter: &mut dbus::arg::Iter) -> CrushResult<Value> {
                             res.push(value);
                             sub.next();
                         }
-                        Err(CrushError::EOFError) => break,
+                        Err(e) if e.is(CrushErrorType::EOFError) => break,
                         Err(e) => return Err(e),
                     }
                 }
@@ -507,7 +507,7 @@ fn deserialize(iter: &mut dbus::arg::Iter) -> CrushResult<Value> {
                     sub.next();
                     value
                 }
-                Err(CrushError::EOFError) => return error("Unexpected EOF in DBUS message"),
+                Err(e) if e.is(CrushErrorType::EOFError) => return error("Unexpected EOF in DBUS message"),
                 Err(e) => return Err(e),
             }
         }
@@ -523,7 +523,7 @@ fn deserialize(iter: &mut dbus::arg::Iter) -> CrushResult<Value> {
                         res.push(value);
                         sub.next();
                     }
-                    Err(CrushError::EOFError) => break,
+                    Err(e) if e.is(CrushErrorType::EOFError) => break,
                     Err(e) => return Err(e),
                 }
             }
@@ -550,7 +550,7 @@ impl DBusMethod {
                     res.push(value);
                     iter.next();
                 }
-                Err(CrushError::EOFError) => break,
+                Err(e) if e.is(CrushErrorType::EOFError) => break,
                 Err(e) => return Err(e),
             }
         }
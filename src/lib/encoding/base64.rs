@@ -0,0 +1,207 @@
+use super::Transform;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{data_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::ValueType;
+use ::base64::Config;
+use signature::signature;
+
+fn config(url_safe: bool) -> Config {
+    if url_safe {
+        ::base64::URL_SAFE
+    } else {
+        ::base64::STANDARD
+    }
+}
+
+struct Encoder {
+    config: Config,
+    pending: Vec<u8>,
+}
+
+impl Transform for Encoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        self.pending.extend_from_slice(input);
+        // Every three bytes of input become four characters of output
+        let len = self.pending.len() / 3 * 3;
+        let mut encoded = String::new();
+        ::base64::encode_config_buf(&self.pending[..len], self.config, &mut encoded);
+        output.extend_from_slice(encoded.as_bytes());
+        self.pending.drain(..len);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> CrushResult<()> {
+        let mut encoded = String::new();
+        ::base64::encode_config_buf(&self.pending, self.config, &mut encoded);
+        output.extend_from_slice(encoded.as_bytes());
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+struct Decoder {
+    config: Config,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn decode(&mut self, len: usize, output: &mut Vec<u8>) -> CrushResult<()> {
+        match ::base64::decode_config_buf(&self.pending[..len], self.config, output) {
+            Ok(()) => {
+                self.pending.drain(..len);
+                Ok(())
+            }
+            Err(e) => data_error(format!("Invalid base64 data: {}", e)),
+        }
+    }
+}
+
+impl Transform for Decoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        // Line breaks are common in base64 encoded data, and carry no meaning
+        self.pending
+            .extend(input.iter().filter(|b| !b.is_ascii_whitespace()));
+        let len = self.pending.len() / 4 * 4;
+        self.decode(len, output)
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> CrushResult<()> {
+        self.decode(self.pending.len(), output)
+    }
+}
+
+#[signature(
+    encode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Encode binary data as base64",
+    example = "\"hello\":to_binary | encoding:base64:encode"
+)]
+struct Encode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+    #[description("use the url and filename safe alphabet, with - and _ instead of + and /.")]
+    #[default(false)]
+    url_safe: bool,
+}
+
+fn encode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Encode = Encode::parse(context.arguments, &context.printer)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Encoder {
+            config: config(cfg.url_safe),
+            pending: Vec::new(),
+        },
+    )
+}
+
+#[signature(
+    decode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Decode base64 encoded data",
+    long = "Whitespace, such as line breaks, is ignored. Padding at the end is optional.",
+    example = "\"aGVsbG8=\" | encoding:base64:decode | bin:to ./hello.txt"
+)]
+struct Decode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+    #[description("use the url and filename safe alphabet, with - and _ instead of + and /.")]
+    #[default(false)]
+    url_safe: bool,
+}
+
+fn decode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Decode = Decode::parse(context.arguments, &context.printer)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Decoder {
+            config: config(cfg.url_safe),
+            pending: Vec::new(),
+        },
+    )
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "base64",
+        Box::new(move |env| {
+            Encode::declare(env)?;
+            Decode::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut transform: impl Transform, input: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chunk in input.chunks(chunk_size) {
+            transform.update(chunk, &mut output).unwrap();
+        }
+        transform.finish(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn round_trip_in_chunks() {
+        let data = (0..=255u8).collect::<Vec<_>>();
+        for url_safe in &[false, true] {
+            for chunk_size in &[1, 2, 5, 1000] {
+                let encoded = run(
+                    Encoder {
+                        config: config(*url_safe),
+                        pending: Vec::new(),
+                    },
+                    &data,
+                    *chunk_size,
+                );
+                assert_eq!(
+                    encoded,
+                    ::base64::encode_config(&data, config(*url_safe)).into_bytes()
+                );
+                let decoded = run(
+                    Decoder {
+                        config: config(*url_safe),
+                        pending: Vec::new(),
+                    },
+                    &encoded,
+                    *chunk_size,
+                );
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_ignores_whitespace() {
+        let decoded = run(
+            Decoder {
+                config: config(false),
+                pending: Vec::new(),
+            },
+            b"aGVs\nbG8=\n",
+            3,
+        );
+        assert_eq!(decoded, b"hello");
+    }
+}
@@ -0,0 +1,338 @@
+use super::Transform;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{argument_error, data_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::ValueType;
+use signature::signature;
+use std::str::from_utf8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Charset {
+    Utf8,
+    /// UTF-16 with a byte order mark. Big endian if there is none.
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Charset {
+    fn parse(name: &str) -> CrushResult<Charset> {
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Charset::Utf8),
+            "utf-16" | "utf16" => Ok(Charset::Utf16),
+            "utf-16le" | "utf16le" => Ok(Charset::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Charset::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Charset::Latin1),
+            _ => argument_error(format!(
+                "Unknown character set {}. Expected one of utf-8, utf-16, utf-16le, utf-16be and latin-1",
+                name
+            )),
+        }
+    }
+}
+
+/// Move the longest valid UTF-8 prefix of the pending bytes into a string. An incomplete
+/// character at the end is left pending.
+fn take_utf8(pending: &mut Vec<u8>) -> CrushResult<String> {
+    let len = match from_utf8(pending) {
+        Ok(s) => s.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(e) => return data_error(format!("Invalid UTF-8 at byte {}", e.valid_up_to())),
+    };
+    let res = String::from_utf8(pending.drain(..len).collect()).unwrap();
+    Ok(res)
+}
+
+/// Converts from a character set to UTF-8.
+struct Decoder {
+    charset: Charset,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn decode_utf16(&mut self, big_endian: bool, output: &mut Vec<u8>) -> CrushResult<()> {
+        let mut units = self
+            .pending
+            .chunks_exact(2)
+            .map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                }
+            })
+            .collect::<Vec<_>>();
+        // A surrogate pair may be split between two chunks
+        if let Some(0xd800..=0xdbff) = units.last() {
+            units.pop();
+        }
+        self.pending.drain(..units.len() * 2);
+        for ch in std::char::decode_utf16(units) {
+            match ch {
+                Ok(ch) => output.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+                Err(e) => {
+                    return data_error(format!(
+                        "Invalid UTF-16: unpaired surrogate {:x}",
+                        e.unpaired_surrogate()
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transform for Decoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        self.pending.extend_from_slice(input);
+        if self.charset == Charset::Utf16 {
+            if self.pending.len() < 2 {
+                return Ok(());
+            }
+            self.charset = if self.pending.starts_with(&[0xff, 0xfe]) {
+                Charset::Utf16Le
+            } else {
+                Charset::Utf16Be
+            };
+            if self.pending.starts_with(&[0xff, 0xfe]) || self.pending.starts_with(&[0xfe, 0xff]) {
+                self.pending.drain(..2);
+            }
+        }
+        match self.charset {
+            Charset::Utf8 => output.extend_from_slice(take_utf8(&mut self.pending)?.as_bytes()),
+            Charset::Utf16Le => self.decode_utf16(false, output)?,
+            Charset::Utf16 | Charset::Utf16Be => self.decode_utf16(true, output)?,
+            Charset::Latin1 => {
+                for byte in self.pending.drain(..) {
+                    output.extend_from_slice((byte as char).encode_utf8(&mut [0; 4]).as_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> CrushResult<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            data_error("Incomplete character at the end of the input")
+        }
+    }
+}
+
+/// Converts from UTF-8 to a character set.
+struct Encoder {
+    charset: Charset,
+    pending: Vec<u8>,
+}
+
+impl Transform for Encoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        if self.charset == Charset::Utf16 {
+            // Write a byte order mark, and then stick to big endian
+            output.extend_from_slice(&[0xfe, 0xff]);
+            self.charset = Charset::Utf16Be;
+        }
+        self.pending.extend_from_slice(input);
+        let s = take_utf8(&mut self.pending)?;
+        match self.charset {
+            Charset::Utf8 => output.extend_from_slice(s.as_bytes()),
+            Charset::Utf16Le => {
+                for unit in s.encode_utf16() {
+                    output.extend_from_slice(&unit.to_le_bytes());
+                }
+            }
+            Charset::Utf16 | Charset::Utf16Be => {
+                for unit in s.encode_utf16() {
+                    output.extend_from_slice(&unit.to_be_bytes());
+                }
+            }
+            Charset::Latin1 => {
+                for ch in s.chars() {
+                    if ch as u32 > 0xff {
+                        return data_error(format!("The character {} is not in latin-1", ch));
+                    }
+                    output.push(ch as u8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> CrushResult<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            data_error("Incomplete character at the end of the input")
+        }
+    }
+}
+
+#[signature(
+    decode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Convert text in the specified character set to UTF-8",
+    long = "Supported character sets are utf-8, utf-16, utf-16le, utf-16be and latin-1. utf-16\n    uses the byte order mark at the start of the text, if any, and is otherwise big endian.",
+    example = "(encoding:charset:decode ./notes.txt charset=\"utf-16\"):to_string"
+)]
+struct Decode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[description("the character set to convert from.")]
+    charset: String,
+}
+
+fn decode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Decode = Decode::parse(context.arguments, &context.printer)?;
+    let charset = Charset::parse(&cfg.charset)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Decoder {
+            charset,
+            pending: Vec::new(),
+        },
+    )
+}
+
+#[signature(
+    encode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Convert UTF-8 text to the specified character set",
+    long = "Supported character sets are utf-8, utf-16, utf-16le, utf-16be and latin-1. utf-16\n    is written as big endian, with a byte order mark.",
+    example = "lines:from ./notes.txt | lines:to | encoding:charset:encode charset=\"latin-1\""
+)]
+struct Encode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+    #[description("the character set to convert to.")]
+    charset: String,
+}
+
+fn encode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Encode = Encode::parse(context.arguments, &context.printer)?;
+    let charset = Charset::parse(&cfg.charset)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Encoder {
+            charset,
+            pending: Vec::new(),
+        },
+    )
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "charset",
+        Box::new(move |env| {
+            Decode::declare(env)?;
+            Encode::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut transform: impl Transform, input: &[u8]) -> CrushResult<Vec<u8>> {
+        let mut output = Vec::new();
+        // Feed one byte at a time, to split every character that can be split
+        for byte in input {
+            transform.update(&[*byte], &mut output)?;
+        }
+        transform.finish(&mut output)?;
+        Ok(output)
+    }
+
+    fn decode(charset: Charset, input: &[u8]) -> CrushResult<Vec<u8>> {
+        run(
+            Decoder {
+                charset,
+                pending: Vec::new(),
+            },
+            input,
+        )
+    }
+
+    fn encode(charset: Charset, input: &str) -> CrushResult<Vec<u8>> {
+        run(
+            Encoder {
+                charset,
+                pending: Vec::new(),
+            },
+            input.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "Smörgåsbord 🦀";
+        for charset in &[
+            Charset::Utf8,
+            Charset::Utf16,
+            Charset::Utf16Le,
+            Charset::Utf16Be,
+        ] {
+            let encoded = encode(*charset, text).unwrap();
+            assert_eq!(decode(*charset, &encoded).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn utf16_byte_order_mark() {
+        assert_eq!(
+            decode(Charset::Utf16, &[0xff, 0xfe, b'a', 0]).unwrap(),
+            b"a"
+        );
+        assert_eq!(
+            decode(Charset::Utf16, &[0xfe, 0xff, 0, b'a']).unwrap(),
+            b"a"
+        );
+        assert_eq!(decode(Charset::Utf16, &[0, b'a']).unwrap(), b"a");
+        assert!(decode(Charset::Utf16Le, b"a").is_err());
+    }
+
+    #[test]
+    fn latin1() {
+        assert_eq!(
+            decode(Charset::Latin1, &[0x53, 0x6d, 0xf6]).unwrap(),
+            "Smö".as_bytes()
+        );
+        assert_eq!(
+            encode(Charset::Latin1, "Smö").unwrap(),
+            vec![0x53, 0x6d, 0xf6]
+        );
+        assert!(encode(Charset::Latin1, "🦀").is_err());
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert!(encode(Charset::Utf16Be, "a").is_ok());
+        assert!(run(
+            Encoder {
+                charset: Charset::Latin1,
+                pending: Vec::new(),
+            },
+            &[b'a', 0xff],
+        )
+        .is_err());
+    }
+}
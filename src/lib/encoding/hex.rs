@@ -0,0 +1,116 @@
+use super::Transform;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{data_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::ValueType;
+use signature::signature;
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+struct Encoder {}
+
+impl Transform for Encoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        for byte in input {
+            output.push(DIGITS[(byte >> 4) as usize]);
+            output.push(DIGITS[(byte & 0xf) as usize]);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> CrushResult<()> {
+        Ok(())
+    }
+}
+
+struct Decoder {
+    /// The high half of a byte whose low half is in the next chunk.
+    pending: Option<u8>,
+}
+
+impl Transform for Decoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        for ch in input.iter().filter(|b| !b.is_ascii_whitespace()) {
+            let digit = match (*ch as char).to_digit(16) {
+                Some(digit) => digit as u8,
+                None => return data_error(format!("Invalid hex digit {}", *ch as char)),
+            };
+            self.pending = match self.pending {
+                None => Some(digit),
+                Some(high) => {
+                    output.push(high << 4 | digit);
+                    None
+                }
+            };
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> CrushResult<()> {
+        match self.pending {
+            None => Ok(()),
+            Some(_) => data_error("Odd number of hex digits"),
+        }
+    }
+}
+
+#[signature(
+    encode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Encode binary data as lower case hexadecimal digits",
+    example = "\"hello\":to_binary | encoding:hex:encode"
+)]
+struct Encode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+}
+
+fn encode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Encode = Encode::parse(context.arguments, &context.printer)?;
+    super::transform(context.input, context.output, cfg.files, Encoder {})
+}
+
+#[signature(
+    decode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Decode hexadecimal digits into binary data",
+    long = "Digits may be upper or lower case. Whitespace is ignored.",
+    example = "\"68656c6c6f\" | encoding:hex:decode"
+)]
+struct Decode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+}
+
+fn decode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Decode = Decode::parse(context.arguments, &context.printer)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Decoder { pending: None },
+    )
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "hex",
+        Box::new(move |env| {
+            Encode::declare(env)?;
+            Decode::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
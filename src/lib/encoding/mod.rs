@@ -0,0 +1,76 @@
+use crate::lang::binary::{binary_channel, BinaryReader};
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::files::Files;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::value::Value;
+use std::io::{Read, Write};
+
+mod base64;
+mod charset;
+mod hex;
+mod url;
+
+/// A conversion of a stream of bytes that is fed one chunk at a time, so that the whole
+/// stream never needs to be in memory.
+trait Transform {
+    /// Convert the next chunk of input. Input that can't be converted until more of the
+    /// stream is known is kept until the next call.
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()>;
+
+    /// Convert whatever is left at the end of the stream.
+    fn finish(&mut self, output: &mut Vec<u8>) -> CrushResult<()>;
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Run the specified transform over the files, or over the input if no files were given,
+/// and send the result as a binary stream.
+fn transform(
+    input: ValueReceiver,
+    output: ValueSender,
+    files: Files,
+    mut transform: impl Transform,
+) -> CrushResult<()> {
+    let mut reader = if files.had_entries() {
+        files.raw_reader(input)?
+    } else {
+        match input.recv()? {
+            Value::BinaryStream(reader) => reader,
+            Value::Binary(data) => BinaryReader::vec(&data),
+            Value::String(data) => BinaryReader::vec(&data.into_bytes()),
+            _ => return argument_error("Expected a string, a binary or a binary stream"),
+        }
+    };
+    let (mut writer, result) = binary_channel();
+    output.send(Value::BinaryStream(result))?;
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut converted = Vec::new();
+    loop {
+        let len = to_crush_error(reader.read(&mut buf))?;
+        if len == 0 {
+            break;
+        }
+        converted.clear();
+        transform.update(&buf[..len], &mut converted)?;
+        to_crush_error(writer.write_all(&converted))?;
+    }
+    converted.clear();
+    transform.finish(&mut converted)?;
+    to_crush_error(writer.write_all(&converted))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_namespace(
+        "encoding",
+        Box::new(move |env| {
+            base64::declare(env)?;
+            charset::declare(env)?;
+            hex::declare(env)?;
+            url::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use super::Transform;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::CrushResult;
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::ValueType;
+use percent_encoding::{percent_decode, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use signature::signature;
+
+/// Everything but the unreserved characters of RFC 3986.
+const RESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+struct Encoder {}
+
+impl Transform for Encoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        for part in percent_encode(input, RESERVED) {
+            output.extend_from_slice(part.as_bytes());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> CrushResult<()> {
+        Ok(())
+    }
+}
+
+struct Decoder {
+    form: bool,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn decode(&mut self, len: usize, output: &mut Vec<u8>) {
+        if self.form {
+            for byte in self.pending[..len].iter_mut() {
+                if *byte == b'+' {
+                    *byte = b' ';
+                }
+            }
+        }
+        output.extend(percent_decode(&self.pending[..len]));
+        self.pending.drain(..len);
+    }
+}
+
+impl Transform for Decoder {
+    fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> CrushResult<()> {
+        self.pending.extend_from_slice(input);
+        // Don't split an escape sequence between two chunks
+        let len = match self.pending.iter().rev().take(2).position(|b| *b == b'%') {
+            Some(idx) => self.pending.len() - idx - 1,
+            None => self.pending.len(),
+        };
+        self.decode(len, output);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> CrushResult<()> {
+        self.decode(self.pending.len(), output);
+        Ok(())
+    }
+}
+
+#[signature(
+    encode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Percent-encode data for use in a URL",
+    long = "All bytes except ASCII letters, digits and the characters -._~ are encoded.",
+    example = "\"rock & roll\" | encoding:url:encode"
+)]
+struct Encode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+}
+
+fn encode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Encode = Encode::parse(context.arguments, &context.printer)?;
+    super::transform(context.input, context.output, cfg.files, Encoder {})
+}
+
+#[signature(
+    decode,
+    can_block = true,
+    output = Known(ValueType::BinaryStream),
+    short = "Decode percent-encoded data",
+    long = "Percent signs that are not followed by two hexadecimal digits are left as is.",
+    example = "\"rock%20%26%20roll\" | encoding:url:decode"
+)]
+struct Decode {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a string, binary or binary_stream."
+    )]
+    files: Files,
+    #[description("decode + as space, as in HTML form data.")]
+    #[default(false)]
+    form: bool,
+}
+
+fn decode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Decode = Decode::parse(context.arguments, &context.printer)?;
+    super::transform(
+        context.input,
+        context.output,
+        cfg.files,
+        Decoder {
+            form: cfg.form,
+            pending: Vec::new(),
+        },
+    )
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "url",
+        Box::new(move |env| {
+            Encode::declare(env)?;
+            Decode::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_split_between_chunks() {
+        let mut decoder = Decoder {
+            form: true,
+            pending: Vec::new(),
+        };
+        let mut output = Vec::new();
+        for chunk in &["rock%2", "0%", "26+roll%"] {
+            decoder.update(chunk.as_bytes(), &mut output).unwrap();
+        }
+        decoder.finish(&mut output).unwrap();
+        assert_eq!(output, b"rock & roll%");
+    }
+}
@@ -0,0 +1,384 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::command::{Command, CrushCommand};
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::{ArgumentVector, CommandContext};
+use crate::lang::files::Files;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use signature::signature;
+use std::collections::BTreeMap;
+
+use super::control::path_dirs;
+
+/// The name of the variable that holds the overrides of env:with. It is looked up through the
+/// calling scopes, so that the overrides apply to everything that runs inside the body, but
+/// not to closures that merely were defined there.
+const OVERRIDES: &str = "__env__";
+
+lazy_static! {
+    /// The environment of the crush process, copied from the operating system at startup.
+    /// Changing the environment of a running process is not thread safe, so changes are made
+    /// here instead, and passed on to external commands when they are started.
+    static ref ENVIRONMENT: Dict = {
+        let dict = Dict::new(ValueType::String, ValueType::String);
+        let vars: BTreeMap<String, String> = std::env::vars_os()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().to_string(),
+                    v.to_string_lossy().to_string(),
+                )
+            })
+            .collect();
+        for (name, value) in vars {
+            let _ = dict.insert(Value::String(name), Value::String(value));
+        }
+        dict
+    };
+
+    /// The environment as a struct with one member per variable, exposed as env:vars.
+    /// Assigning to a member sets the variable like env:set does, and set_var keeps the
+    /// members up to date.
+    static ref VIEW: Struct = Struct::new(
+        ENVIRONMENT
+            .elements()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        Some(view_class()),
+    );
+}
+
+/// The methods of env:vars.
+fn view_class() -> Struct {
+    let full_name = |name: &str| {
+        vec![
+            "global".to_string(),
+            "env".to_string(),
+            "vars".to_string(),
+            name.to_string(),
+        ]
+    };
+    Struct::new(
+        vec![
+            (
+                "__setattr__".to_string(),
+                Value::Command(CrushCommand::command(
+                    view_set,
+                    false,
+                    full_name("__setattr__"),
+                    "vars:__setattr__ name:string value:any",
+                    "Set the specified environment variable, or remove it if the value is empty",
+                    None,
+                    Known(ValueType::Empty),
+                )),
+            ),
+            (
+                "__setitem__".to_string(),
+                Value::Command(CrushCommand::command(
+                    view_set,
+                    false,
+                    full_name("__setitem__"),
+                    "vars:__setitem__ name:string value:any",
+                    "Set the specified environment variable, or remove it if the value is empty",
+                    None,
+                    Known(ValueType::Empty),
+                )),
+            ),
+            (
+                "__getitem__".to_string(),
+                Value::Command(CrushCommand::command(
+                    view_get,
+                    false,
+                    full_name("__getitem__"),
+                    "vars:__getitem__ name:string",
+                    "The value of the specified environment variable, or empty if it is not set",
+                    None,
+                    Unknown,
+                )),
+            ),
+        ],
+        None,
+    )
+}
+
+fn view_set(mut context: CommandContext) -> CrushResult<()> {
+    context.arguments.check_len(2)?;
+    let name = context.arguments.string(0)?;
+    match context.arguments.value(1)? {
+        Value::Empty() => set_var(&context.scope, &name, None)?,
+        value => set_var(&context.scope, &name, Some(&value.to_string()))?,
+    }
+    context.output.send(Value::Empty())
+}
+
+fn view_get(mut context: CommandContext) -> CrushResult<()> {
+    context.arguments.check_len(1)?;
+    let name = context.arguments.string(0)?;
+    context.output.send(
+        ENVIRONMENT
+            .get(&Value::string(&name))
+            .unwrap_or(Value::Empty()),
+    )
+}
+
+/// The environment variables set by all enclosing invocations of env:with.
+fn overrides(scope: &Scope) -> CrushResult<Vec<(String, String)>> {
+    Ok(match scope.get_calling(OVERRIDES)? {
+        Some(Value::Dict(d)) => d
+            .elements()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        _ => vec![],
+    })
+}
+
+/// The environment as seen by external commands started from the specified scope.
+pub fn variables(scope: &Scope) -> CrushResult<BTreeMap<String, String>> {
+    let mut res: BTreeMap<String, String> = ENVIRONMENT
+        .elements()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    res.extend(overrides(scope)?);
+    Ok(res)
+}
+
+/// Pass the environment on to an external command started from the specified scope. The
+/// command inherits the variables that crush didn't change as they are, so that values that
+/// aren't valid unicode reach it unchanged.
+pub fn apply(scope: &Scope, cmd: &mut std::process::Command) -> CrushResult<()> {
+    let mut variables = variables(scope)?;
+    for (name, value) in std::env::vars_os() {
+        match variables.remove(&*name.to_string_lossy()) {
+            Some(new_value) if new_value == value.to_string_lossy() => {}
+            Some(new_value) => {
+                cmd.env(&name, new_value);
+            }
+            None => {
+                cmd.env_remove(&name);
+            }
+        }
+    }
+    cmd.envs(variables);
+    Ok(())
+}
+
+fn validate(name: &str) -> CrushResult<()> {
+    if name.is_empty() || name.contains('=') || name.contains('\0') {
+        argument_error(format!("Invalid environment variable name {:?}", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Set an environment variable of the crush process. Changing PATH also changes the
+/// directories that crush looks for external commands in. Removed variables are left as empty
+/// members of env:vars, since members can't be removed from a struct.
+fn set_var(scope: &Scope, name: &str, value: Option<&str>) -> CrushResult<()> {
+    validate(name)?;
+    match value {
+        Some(value) => {
+            ENVIRONMENT.insert(Value::string(name), Value::string(value))?;
+            VIEW.set(name, Value::string(value));
+        }
+        None => {
+            ENVIRONMENT.remove(&Value::string(name));
+            VIEW.set(name, Value::Empty());
+        }
+    }
+    if name == "PATH" {
+        if let Some(Value::List(cmd_path)) = scope.get("cmd_path")? {
+            cmd_path.clear();
+            cmd_path.append(&mut value.map(path_dirs).unwrap_or_default())?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+get,
+can_block = false,
+output = Unknown,
+short = "The value of an environment variable, or empty if it is not set.",
+example = "env:get \"HOME\""
+)]
+struct Get {
+    #[description("the name of the environment variable.")]
+    name: String,
+}
+
+fn get(context: CommandContext) -> CrushResult<()> {
+    let cfg: Get = Get::parse(context.arguments, &context.printer)?;
+    context.output.send(
+        variables(&context.scope)?
+            .remove(&cfg.name)
+            .map(Value::String)
+            .unwrap_or(Value::Empty()),
+    )
+}
+
+#[signature(
+set,
+can_block = false,
+short = "Set environment variables.",
+long = "The variables are set in the environment of the crush process, and are inherited
+    by all external commands that are started after this. Setting PATH also updates cmd_path.
+    Use env:with to set variables for a single command.",
+example = "env:set EDITOR=\"vim\" PAGER=\"less\""
+)]
+struct Set {
+    #[named()]
+    #[description("the names and values of the variables to set.")]
+    variables: OrderedStringMap<Value>,
+}
+
+fn set(context: CommandContext) -> CrushResult<()> {
+    let cfg: Set = Set::parse(context.arguments, &context.printer)?;
+    for (name, value) in cfg.variables.iter() {
+        set_var(&context.scope, name, Some(&value.to_string()))?;
+    }
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+unset,
+can_block = false,
+short = "Remove environment variables.",
+example = "env:unset \"http_proxy\" \"https_proxy\""
+)]
+struct Unset {
+    #[unnamed()]
+    #[description("the names of the variables to remove.")]
+    names: Vec<String>,
+}
+
+fn unset(context: CommandContext) -> CrushResult<()> {
+    let cfg: Unset = Unset::parse(context.arguments, &context.printer)?;
+    for name in &cfg.names {
+        set_var(&context.scope, name, None)?;
+    }
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+list,
+can_block = false,
+output = Known(ValueType::TableStream(vec![
+    ColumnType::new("name", ValueType::String),
+    ColumnType::new("value", ValueType::String),
+])),
+short = "All environment variables, sorted by name.",
+example = "env:list | where {name =~ re\"LC_.*\"}"
+)]
+struct List {}
+
+fn list(context: CommandContext) -> CrushResult<()> {
+    let output = context.output.initialize(vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("value", ValueType::String),
+    ])?;
+    for (name, value) in variables(&context.scope)? {
+        output.send(Row::new(vec![Value::String(name), Value::String(value)]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+with,
+can_block = true,
+output = Unknown,
+short = "Invoke a command with additional environment variables.",
+long = "The variables are passed to all external commands started by the body, including
+    commands started by closures called from it, and are visible to env:get and env:list
+    inside of it. The environment of the crush process itself, and the directories in
+    cmd_path, are left unchanged.",
+example = "env:with LANG=\"C\" TZ=\"UTC\" {date}"
+)]
+struct With {
+    #[named()]
+    #[description("the names and values of the variables to set.")]
+    variables: OrderedStringMap<Value>,
+    #[description("the command to invoke.")]
+    body: Command,
+}
+
+fn with(context: CommandContext) -> CrushResult<()> {
+    let cfg: With = With::parse(context.arguments, &context.printer)?;
+    let dict = Dict::new(ValueType::String, ValueType::String);
+    for (name, value) in overrides(&context.scope)? {
+        dict.insert(Value::String(name), Value::String(value))?;
+    }
+    for (name, value) in cfg.variables.iter() {
+        validate(name)?;
+        dict.insert(
+            Value::String(name.clone()),
+            Value::String(value.to_string()),
+        )?;
+    }
+    let scope = context.scope.create_child(&context.scope, false);
+    scope.declare(OVERRIDES, Value::Dict(dict))?;
+    cfg.body.invoke(CommandContext {
+        input: context.input,
+        output: context.output,
+        arguments: Vec::new(),
+        scope,
+        this: None,
+        printer: context.printer,
+        background: context.background,
+    })
+}
+
+#[signature(
+load,
+can_block = true,
+short = "Set environment variables from a .env file.",
+long = "Every line of the file is of the form NAME=value. Lines starting with # are comments,
+    values may be quoted, and may refer to previously defined variables using ${NAME}.
+    Variables that are already set are left unchanged unless overwrite is true.",
+example = "env:load ./.env --overwrite"
+)]
+struct Load {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[default(false)]
+    #[description("replace the values of variables that are already set.")]
+    overwrite: bool,
+}
+
+fn load(context: CommandContext) -> CrushResult<()> {
+    let cfg: Load = Load::parse(context.arguments, &context.printer)?;
+    for entry in dotenvy::Iter::new(cfg.files.reader(context.input)?) {
+        let (name, value) = to_crush_error(entry)?;
+        if cfg.overwrite || ENVIRONMENT.get(&Value::string(&name)).is_none() {
+            set_var(&context.scope, &name, Some(&value))?;
+        }
+    }
+    context.output.send(Value::Empty())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_namespace(
+        "env",
+        Box::new(move |env| {
+            Get::declare(env)?;
+            Set::declare(env)?;
+            Unset::declare(env)?;
+            List::declare(env)?;
+            With::declare(env)?;
+            Load::declare(env)?;
+            env.declare("vars", Value::Struct(VIEW.clone()))?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use sha2::Digest;
+use signature::signature;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    Blake2b,
+    Blake3,
+    Crc32,
+}
+
+/// The state of a hash function that has consumed part of its input.
+enum Hasher {
+    Md5(::md5::Context),
+    Sha1(::sha1::Sha1),
+    Sha256(::sha2::Sha256),
+    Sha512(::sha2::Sha512),
+    Blake2b(::blake2b_simd::State),
+    Blake3(Box<::blake3::Hasher>),
+    Crc32(::crc32fast::Hasher),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Hasher {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(::md5::Context::new()),
+            Algorithm::Sha1 => Hasher::Sha1(::sha1::Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(::sha2::Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(::sha2::Sha512::new()),
+            Algorithm::Blake2b => Hasher::Blake2b(::blake2b_simd::State::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::from(::blake3::Hasher::new())),
+            Algorithm::Crc32 => Hasher::Crc32(::crc32fast::Hasher::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.consume(data),
+            Hasher::Sha1(h) => h.input(data),
+            Hasher::Sha256(h) => h.input(data),
+            Hasher::Sha512(h) => h.input(data),
+            Hasher::Blake2b(h) => {
+                h.update(data);
+            }
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Crc32(h) => h.update(data),
+        }
+    }
+
+    /// The hash of all input, as lower case hexadecimal digits.
+    fn finish(self) -> String {
+        let bytes = match self {
+            Hasher::Md5(h) => h.compute().to_vec(),
+            Hasher::Sha1(h) => h.result().to_vec(),
+            Hasher::Sha256(h) => h.result().to_vec(),
+            Hasher::Sha512(h) => h.result().to_vec(),
+            Hasher::Blake2b(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Crc32(h) => h.finalize().to_be_bytes().to_vec(),
+        };
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+fn hash_reader(algorithm: Algorithm, reader: &mut dyn Read) -> CrushResult<String> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let len = to_crush_error(reader.read(&mut buf))?;
+        if len == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.update(&buf[..len]);
+    }
+}
+
+fn hash_file(algorithm: Algorithm, file: &PathBuf) -> CrushResult<String> {
+    hash_reader(algorithm, &mut to_crush_error(File::open(file))?)
+}
+
+fn output_columns() -> Vec<ColumnType> {
+    vec![
+        ColumnType::new("file", ValueType::File),
+        ColumnType::new("hash", ValueType::String),
+    ]
+}
+
+/// Hash the files, or the input if no files were given. A single file, string or binary gives
+/// a single hash, while several files, a glob or a table of files give a table stream of files
+/// and their hashes, even if they only hold one file.
+fn hash(
+    algorithm: Algorithm,
+    files: Files,
+    input: ValueReceiver,
+    output: ValueSender,
+) -> CrushResult<()> {
+    if files.had_entries() {
+        let had_patterns = files.had_patterns();
+        let mut files = files.into_vec();
+        if files.len() == 1 && !had_patterns {
+            return output.send(Value::String(hash_file(algorithm, &files[0])?));
+        }
+        let output = output.initialize(output_columns())?;
+        for file in files.drain(..) {
+            let hash = hash_file(algorithm, &file)?;
+            output.send(Row::new(vec![Value::File(file), Value::String(hash)]))?;
+        }
+        return Ok(());
+    }
+
+    match input.recv()? {
+        Value::String(s) => output.send(Value::String(hash_reader(algorithm, &mut s.as_bytes())?)),
+        Value::Binary(b) => output.send(Value::String(hash_reader(algorithm, &mut &b[..])?)),
+        Value::BinaryStream(mut b) => {
+            output.send(Value::String(hash_reader(algorithm, b.as_mut())?))
+        }
+        value => match value.stream() {
+            Some(mut stream) => {
+                let types = stream.types().to_vec();
+                let idx = match types
+                    .iter()
+                    .position(|c| c.name == "file" && c.cell_type == ValueType::File)
+                {
+                    Some(idx) => idx,
+                    None if types.len() == 1 && types[0].cell_type == ValueType::File => 0,
+                    None => {
+                        return argument_error(
+                            "Expected a table stream with a column named file of type file",
+                        )
+                    }
+                };
+                let output = output.initialize(output_columns())?;
+                while let Ok(row) = stream.read() {
+                    if let Value::File(file) = row.into_vec().remove(idx) {
+                        let hash = hash_file(algorithm, &file)?;
+                        output.send(Row::new(vec![Value::File(file), Value::String(hash)]))?;
+                    }
+                }
+                Ok(())
+            }
+            None => argument_error(
+                "Expected a string, a binary, a binary stream or a table stream of files",
+            ),
+        },
+    }
+}
+
+/// Declare the signature and command function of a hash algorithm.
+macro_rules! hash_command {
+    ($signature:ident, $name:ident, $algorithm:expr, $short:tt, $example:tt) => {
+        #[signature(
+            $name,
+            can_block = true,
+            output = Unknown,
+            short = $short,
+            long = "Hashes a single file, string, binary or binary stream into a string of hexadecimal\n    digits. Several files, a glob, or a table stream with a column named file, are hashed into\n    a table stream with the columns file and hash, however many files they hold.",
+            example = $example
+        )]
+        struct $signature {
+            #[unnamed()]
+            #[description("the files to hash. If unspecified, will hash the input.")]
+            files: Files,
+        }
+
+        fn $name(context: CommandContext) -> CrushResult<()> {
+            let cfg: $signature = $signature::parse(context.arguments, &context.printer)?;
+            hash($algorithm, cfg.files, context.input, context.output)
+        }
+    };
+}
+
+hash_command!(
+    Md5,
+    md5,
+    Algorithm::Md5,
+    "Calculate the MD5 hash of data",
+    "hash:md5 ./download.iso"
+);
+
+hash_command!(
+    Sha1,
+    sha1,
+    Algorithm::Sha1,
+    "Calculate the SHA-1 hash of data",
+    "hash:sha1 ./download.iso"
+);
+
+hash_command!(
+    Sha256,
+    sha256,
+    Algorithm::Sha256,
+    "Calculate the SHA-256 hash of data",
+    "find . | where {type == \"file\"} | hash:sha256 | group ^hash"
+);
+
+hash_command!(
+    Sha512,
+    sha512,
+    Algorithm::Sha512,
+    "Calculate the SHA-512 hash of data",
+    "hash:sha512 ./download.iso"
+);
+
+hash_command!(
+    Blake2b,
+    blake2b,
+    Algorithm::Blake2b,
+    "Calculate the BLAKE2b hash of data",
+    "hash:blake2b ./download.iso"
+);
+
+hash_command!(
+    Blake3,
+    blake3,
+    Algorithm::Blake3,
+    "Calculate the BLAKE3 hash of data",
+    "hash:blake3 ./download.iso"
+);
+
+hash_command!(
+    Crc32,
+    crc32,
+    Algorithm::Crc32,
+    "Calculate the CRC-32 checksum of data",
+    "hash:crc32 ./download.iso"
+);
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_namespace(
+        "hash",
+        Box::new(move |env| {
+            Md5::declare(env)?;
+            Sha1::declare(env)?;
+            Sha256::declare(env)?;
+            Sha512::declare(env)?;
+            Blake2b::declare(env)?;
+            Blake3::declare(env)?;
+            Crc32::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_string(algorithm: Algorithm, s: &str) -> String {
+        hash_reader(algorithm, &mut s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn known_hashes() {
+        assert_eq!(
+            hash_string(Algorithm::Md5, "abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_string(Algorithm::Sha1, "abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hash_string(Algorithm::Sha256, "abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hash_string(Algorithm::Crc32, "abc"), "352441c2");
+        assert_eq!(
+            hash_string(Algorithm::Blake3, ""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(hash_string(Algorithm::Sha512, "").len(), 128);
+        assert_eq!(hash_string(Algorithm::Blake2b, "").len(), 128);
+    }
+}
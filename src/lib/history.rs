@@ -0,0 +1,72 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::CrushResult;
+use crate::lang::execution_context::{ArgumentVector, CommandContext};
+use crate::lang::history;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use signature::signature;
+
+lazy_static! {
+    static ref LIST_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("command", ValueType::String),
+        ColumnType::new("start", ValueType::Time),
+        ColumnType::new("duration", ValueType::Duration),
+        ColumnType::new("cwd", ValueType::File),
+        ColumnType::new("success", ValueType::Bool),
+    ];
+}
+
+#[signature(
+list,
+can_block = false,
+short = "Return a table stream containing all previous interactive invocations",
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())),
+example = "history:list | where {success == false}",
+long = "The oldest invocation is listed first. An invocation is successful if no errors were reported
+    while running it. Invocations imported from the history file of older versions of Crush have
+    empty start, duration, cwd and success columns.")]
+struct List {}
+
+fn list(context: CommandContext) -> CrushResult<()> {
+    context.arguments.check_len(0)?;
+    let entries = history::load(&context.printer)?;
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    for entry in entries {
+        output.send(Row::new(vec![
+            Value::string(&entry.command),
+            entry.start.map(Value::Time).unwrap_or(Value::Empty()),
+            entry.duration.map(Value::Duration).unwrap_or(Value::Empty()),
+            entry.cwd.map(Value::File).unwrap_or(Value::Empty()),
+            entry.success.map(Value::Bool).unwrap_or(Value::Empty()),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+clear,
+can_block = false,
+short = "Remove all previous invocations from the history",
+output = Known(ValueType::Empty))]
+struct Clear {}
+
+fn clear(context: CommandContext) -> CrushResult<()> {
+    context.arguments.check_len(0)?;
+    history::clear()?;
+    context.output.send(Value::Empty())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_namespace(
+        "history",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Clear::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
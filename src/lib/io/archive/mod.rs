@@ -0,0 +1,92 @@
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::ValueReceiver;
+use crate::lang::table::ColumnType;
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use std::fs::{read_dir, symlink_metadata};
+use std::path::{Component, Path, PathBuf};
+
+mod tar;
+mod zip;
+
+lazy_static! {
+    /// The members of an archive are listed using the same columns as the find command uses.
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("user", ValueType::String),
+        ColumnType::new("size", ValueType::Integer),
+        ColumnType::new("modified", ValueType::Time),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("file", ValueType::File),
+    ];
+}
+
+/// The name to store a file under in an archive, which is always a relative path.
+fn member_name(path: &Path) -> PathBuf {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn walk(path: PathBuf, res: &mut Vec<PathBuf>) -> CrushResult<()> {
+    let is_dir = to_crush_error(symlink_metadata(&path))?.is_dir();
+    res.push(path.clone());
+    if is_dir {
+        let mut children = Vec::new();
+        for entry in to_crush_error(read_dir(&path))? {
+            children.push(to_crush_error(entry)?.path());
+        }
+        children.sort();
+        for child in children {
+            walk(child, res)?;
+        }
+    }
+    Ok(())
+}
+
+/// The files to add to an archive. Directories given as arguments are added with all their
+/// content, while a table stream is expected to already list every file to add, e.g. the
+/// output of find.
+fn sources(files: Files, input: ValueReceiver) -> CrushResult<Vec<PathBuf>> {
+    let mut res = Vec::new();
+    if files.had_entries() {
+        for file in files.into_vec() {
+            walk(file, &mut res)?;
+        }
+        return Ok(res);
+    }
+    match input.recv()?.stream() {
+        Some(mut stream) => {
+            let types = stream.types();
+            let idx = match types
+                .iter()
+                .position(|c| c.name == "file" && c.cell_type == ValueType::File)
+            {
+                Some(idx) => idx,
+                None if types.len() == 1 && types[0].cell_type == ValueType::File => 0,
+                None => {
+                    return argument_error(
+                        "Expected a table stream with a column named file of type file",
+                    )
+                }
+            };
+            while let Ok(row) = stream.read() {
+                if let Value::File(file) = row.into_vec().remove(idx) {
+                    res.push(file);
+                }
+            }
+            Ok(res)
+        }
+        None => argument_error("Expected files to archive, or a table stream of files"),
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    tar::declare(root)?;
+    zip::declare(root)?;
+    Ok(())
+}
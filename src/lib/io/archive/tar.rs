@@ -0,0 +1,163 @@
+use super::{member_name, sources, OUTPUT_TYPE};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::binary_channel;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::Row;
+use crate::lang::value::{Value, ValueType};
+use crate::util::user_map::{create_user_map, UserMap};
+use ::tar::{Archive, Builder};
+use chrono::{Local, TimeZone};
+use signature::signature;
+use std::io::copy;
+use std::path::PathBuf;
+
+/// The modification time of a member, or empty if it is out of range.
+fn to_time(mtime: u64) -> Value {
+    match Local.timestamp_opt(mtime as i64, 0).single() {
+        Some(time) => Value::Time(time),
+        None => Value::Empty(),
+    }
+}
+
+#[signature(
+    list,
+    can_block = true,
+    output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+    short = "List the members of a tar archive",
+    long = "Compressed archives, e.g. .tar.gz files, are decompressed on the fly.",
+    example = "io:tar:list ./release.tar.gz | where {type == \"file\"}"
+)]
+struct List {
+    #[unnamed()]
+    #[description(
+        "the archive. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    file: Files,
+}
+
+fn list(context: CommandContext) -> CrushResult<()> {
+    let cfg: List = List::parse(context.arguments, &context.printer)?;
+    let mut archive = Archive::new(cfg.file.reader(context.input)?);
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let users = create_user_map();
+    for entry in to_crush_error(archive.entries())? {
+        let entry = to_crush_error(entry)?;
+        let header = entry.header();
+        let user = match header.username() {
+            Ok(Some(name)) if !name.is_empty() => Value::string(name),
+            _ => users.get_name(to_crush_error(header.uid())? as _),
+        };
+        let entry_type = header.entry_type();
+        let type_str = if entry_type.is_dir() {
+            "directory"
+        } else if entry_type.is_symlink() {
+            "symlink"
+        } else {
+            "file"
+        };
+        output.send(Row::new(vec![
+            user,
+            Value::Integer(to_crush_error(header.size())? as i128),
+            to_time(to_crush_error(header.mtime())?),
+            Value::string(type_str),
+            Value::File(to_crush_error(entry.path())?.into_owned()),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+    extract,
+    can_block = true,
+    output = Unknown,
+    short = "Extract the members of a tar archive",
+    long = "Extracts every member into a directory, or a single member as a binary stream.\n    Compressed archives, e.g. .tar.gz files, are decompressed on the fly.",
+    example = "io:tar:extract ./release.tar.gz member=\"release/README.md\" | lines:from"
+)]
+struct Extract {
+    #[unnamed()]
+    #[description(
+        "the archive. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    file: Files,
+    #[description("the member to extract as a binary stream.")]
+    member: Option<String>,
+    #[description("the directory to extract all members into. Defaults to the current directory.")]
+    directory: Files,
+}
+
+fn extract(context: CommandContext) -> CrushResult<()> {
+    let cfg: Extract = Extract::parse(context.arguments, &context.printer)?;
+    let mut archive = Archive::new(cfg.file.reader(context.input)?);
+    match cfg.member {
+        None => {
+            let directory = if cfg.directory.had_entries() {
+                cfg.directory.into_file()?
+            } else {
+                PathBuf::from(".")
+            };
+            to_crush_error(archive.unpack(directory))?;
+            context.output.send(Value::Empty())
+        }
+        Some(member) => {
+            let member = PathBuf::from(member);
+            for entry in to_crush_error(archive.entries())? {
+                let mut entry = to_crush_error(entry)?;
+                if to_crush_error(entry.path())? == member {
+                    let (mut writer, reader) = binary_channel();
+                    context.output.send(Value::BinaryStream(reader))?;
+                    to_crush_error(copy(&mut entry, &mut writer))?;
+                    return Ok(());
+                }
+            }
+            argument_error(format!("No member named {}", member.display()))
+        }
+    }
+}
+
+#[signature(
+    create,
+    can_block = true,
+    output = Unknown,
+    short = "Create a tar archive",
+    long = "Directories given as arguments are added with all their content. If no files are\n    given, every file in the input table stream is added, e.g. the output of find. The archive\n    is compressed if its extension is that of a compression format, e.g. .tar.gz.",
+    example = "find ./src | where {type == \"file\"} | io:tar:create archive=./src.tar.gz"
+)]
+struct Create {
+    #[unnamed()]
+    #[description("the files to add.")]
+    files: Files,
+    #[description("the archive to create. If unspecified, will write to io as a binary_stream.")]
+    archive: Files,
+}
+
+fn create(context: CommandContext) -> CrushResult<()> {
+    let cfg: Create = Create::parse(context.arguments, &context.printer)?;
+    let sources = sources(cfg.files, context.input)?;
+    let mut builder = Builder::new(cfg.archive.writer(context.output)?);
+    builder.follow_symlinks(false);
+    for source in sources {
+        let name = member_name(&source);
+        if !name.as_os_str().is_empty() {
+            to_crush_error(builder.append_path_with_name(&source, name))?;
+        }
+    }
+    to_crush_error(builder.into_inner())?.finish()
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "tar",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Extract::declare(env)?;
+            Create::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
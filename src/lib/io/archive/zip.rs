@@ -0,0 +1,344 @@
+use super::{member_name, sources, OUTPUT_TYPE};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::binary_channel;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{argument_error, error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::table::Row;
+use crate::lang::value::{Value, ValueType};
+use ::zip::result::ZipResult;
+use ::zip::write::FileOptions;
+use ::zip::{DateTime, ZipArchive, ZipWriter};
+use chrono::{Datelike, Local, TimeZone, Timelike};
+use signature::signature;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::{read_link, symlink_metadata, File, OpenOptions};
+use std::io::{copy, Cursor, Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// The unix file type of a symlink, as stored in the upper half of the external attributes of
+/// a member.
+const S_IFLNK: u32 = 0o120000;
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Open the archive. Zip archives can't be read front to back, so an archive that is read
+/// from io is kept in memory.
+fn open(file: Files, input: ValueReceiver) -> CrushResult<ZipArchive<Box<dyn ReadSeek>>> {
+    let reader: Box<dyn ReadSeek> = if file.had_entries() {
+        Box::from(to_crush_error(File::open(file.into_file()?))?)
+    } else {
+        let mut data = Vec::new();
+        to_crush_error(file.raw_reader(input)?.read_to_end(&mut data))?;
+        Box::from(Cursor::new(data))
+    };
+    to_crush_error(ZipArchive::new(reader))
+}
+
+/// The modification time of a member, or empty if it isn't a valid local time.
+fn to_time(time: DateTime) -> Value {
+    match Local
+        .ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)
+        .and_hms_opt(
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )
+        .single()
+    {
+        Some(time) => Value::Time(time),
+        None => Value::Empty(),
+    }
+}
+
+#[signature(
+    list,
+    can_block = true,
+    output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+    short = "List the members of a zip archive",
+    long = "Zip archives don't record who owns their members, so the user column is always empty.",
+    example = "io:zip:list ./release.zip | where {type == \"file\"}"
+)]
+struct List {
+    #[unnamed()]
+    #[description(
+        "the archive. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    file: Files,
+}
+
+fn list(context: CommandContext) -> CrushResult<()> {
+    let cfg: List = List::parse(context.arguments, &context.printer)?;
+    let mut archive = open(cfg.file, context.input)?;
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    for idx in 0..archive.len() {
+        let member = to_crush_error(archive.by_index(idx))?;
+        let is_symlink = member
+            .unix_mode()
+            .map(|mode| mode & 0o170000 == 0o120000)
+            .unwrap_or(false);
+        let type_str = if member.is_dir() {
+            "directory"
+        } else if is_symlink {
+            "symlink"
+        } else {
+            "file"
+        };
+        output.send(Row::new(vec![
+            Value::string(""),
+            Value::Integer(member.size() as i128),
+            to_time(member.last_modified()),
+            Value::string(type_str),
+            Value::File(PathBuf::from(member.name().trim_end_matches('/'))),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+    extract,
+    can_block = true,
+    output = Unknown,
+    short = "Extract the members of a zip archive",
+    long = "Extracts every member into a directory, or a single member as a binary stream.",
+    example = "io:zip:extract ./release.zip member=\"release/README.md\" | lines:from"
+)]
+struct Extract {
+    #[unnamed()]
+    #[description(
+        "the archive. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    file: Files,
+    #[description("the member to extract as a binary stream.")]
+    member: Option<String>,
+    #[description("the directory to extract all members into. Defaults to the current directory.")]
+    directory: Files,
+}
+
+fn extract(context: CommandContext) -> CrushResult<()> {
+    let cfg: Extract = Extract::parse(context.arguments, &context.printer)?;
+    let mut archive = open(cfg.file, context.input)?;
+    match cfg.member {
+        None => {
+            let directory = if cfg.directory.had_entries() {
+                cfg.directory.into_file()?
+            } else {
+                PathBuf::from(".")
+            };
+            to_crush_error(archive.extract(directory))?;
+            context.output.send(Value::Empty())
+        }
+        Some(member) => match archive.by_name(&member) {
+            Ok(mut member) => {
+                let (mut writer, reader) = binary_channel();
+                context.output.send(Value::BinaryStream(reader))?;
+                to_crush_error(copy(&mut member, &mut writer))?;
+                Ok(())
+            }
+            Err(_) => argument_error(format!("No member named {}", member)),
+        },
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap()) as usize
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u64 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as u64
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Read the specified number of bytes at the specified offset.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> CrushResult<Vec<u8>> {
+    let mut data = vec![0u8; len];
+    to_crush_error(reader.seek(SeekFrom::Start(offset)))?;
+    to_crush_error(reader.read_exact(&mut data))?;
+    Ok(data)
+}
+
+/// The zip crate can only write regular files and directories, so symlinks are written as
+/// files containing the target of the link, like zip stores them, and are then marked as
+/// symlinks in the central directory of the finished archive, which is where the type of a
+/// member is read from.
+fn mark_symlinks<W: Read + Write + Seek>(
+    mut writer: W,
+    symlinks: &HashSet<String>,
+) -> CrushResult<W> {
+    // No archive comment is written, so the end of central directory record is the last
+    // 22 bytes, possibly preceded by a zip64 locator
+    let end = to_crush_error(writer.seek(SeekFrom::End(-22)))?;
+    let record = read_at(&mut writer, end, 22)?;
+    let (mut size, mut offset) = (u32_at(&record, 12), u32_at(&record, 16));
+    if size == 0xFFFFFFFF || offset == 0xFFFFFFFF {
+        let locator = read_at(&mut writer, end - 20, 20)?;
+        let record = read_at(&mut writer, u64_at(&locator, 8), 56)?;
+        size = u64_at(&record, 40);
+        offset = u64_at(&record, 48);
+    }
+    let mut directory = read_at(&mut writer, offset, size as usize)?;
+    let mut pos = 0;
+    while pos + 46 <= directory.len() && u32_at(&directory, pos) == 0x02014b50 {
+        let name_len = u16_at(&directory, pos + 28);
+        let extra_len = u16_at(&directory, pos + 30) + u16_at(&directory, pos + 32);
+        let name = &directory[pos + 46..pos + 46 + name_len];
+        if symlinks.contains(&*String::from_utf8_lossy(name)) {
+            let mode = (u32_at(&directory, pos + 38) >> 16) as u32 & 0o777;
+            directory[pos + 38..pos + 42].copy_from_slice(&((S_IFLNK | mode) << 16).to_le_bytes());
+        }
+        pos += 46 + name_len + extra_len;
+    }
+    to_crush_error(writer.seek(SeekFrom::Start(offset)))?;
+    to_crush_error(writer.write_all(&directory))?;
+    to_crush_error(writer.seek(SeekFrom::End(0)))?;
+    Ok(writer)
+}
+
+/// Write an archive of the specified files. Like io:tar:create, symlinks are stored as
+/// symlinks instead of being followed.
+fn write_archive<W: Read + Write + Seek>(sources: Vec<PathBuf>, writer: W) -> CrushResult<W> {
+    let mut zip = ZipWriter::new(writer);
+    let mut symlinks = HashSet::new();
+    for source in sources {
+        let name = member_name(&source);
+        let name = match name.to_str() {
+            Some("") => continue,
+            Some(name) => name.to_string(),
+            None => return error(format!("Invalid file name {}", source.display())),
+        };
+        let meta = to_crush_error(symlink_metadata(&source))?;
+        let modified = chrono::DateTime::<Local>::from(to_crush_error(meta.modified())?);
+        let mut options =
+            FileOptions::default().unix_permissions(meta.permissions().mode() & 0o777);
+        if let Ok(time) = DateTime::from_date_and_time(
+            modified.year() as u16,
+            modified.month() as u8,
+            modified.day() as u8,
+            modified.hour() as u8,
+            modified.minute() as u8,
+            modified.second() as u8,
+        ) {
+            options = options.last_modified_time(time);
+        }
+        let result: ZipResult<()> = if meta.file_type().is_symlink() {
+            let target = to_crush_error(read_link(&source))?;
+            symlinks.insert(name.clone());
+            zip.start_file(name, options).and_then(|()| {
+                zip.write_all(target.as_os_str().as_bytes())?;
+                Ok(())
+            })
+        } else if meta.is_dir() {
+            zip.add_directory(name, options)
+        } else {
+            zip.start_file(name, options).and_then(|()| {
+                copy(&mut File::open(&source)?, &mut zip)?;
+                Ok(())
+            })
+        };
+        to_crush_error(result)?;
+    }
+    let writer = to_crush_error(zip.finish())?;
+    if symlinks.is_empty() {
+        Ok(writer)
+    } else {
+        mark_symlinks(writer, &symlinks)
+    }
+}
+
+#[signature(
+    create,
+    can_block = true,
+    output = Unknown,
+    short = "Create a zip archive",
+    long = "Directories given as arguments are added with all their content. If no files are\n    given, every file in the input table stream is added, e.g. the output of find.",
+    example = "find ./src | where {type == \"file\"} | io:zip:create archive=./src.zip"
+)]
+struct Create {
+    #[unnamed()]
+    #[description("the files to add.")]
+    files: Files,
+    #[description("the archive to create. If unspecified, will write to io as a binary.")]
+    archive: Files,
+}
+
+fn create(context: CommandContext) -> CrushResult<()> {
+    let cfg: Create = Create::parse(context.arguments, &context.printer)?;
+    let sources = sources(cfg.files, context.input)?;
+    create_archive(sources, cfg.archive, context.output)
+}
+
+/// Zip archives are written out of order, so an archive that is written to io is built in
+/// memory.
+fn create_archive(sources: Vec<PathBuf>, archive: Files, output: ValueSender) -> CrushResult<()> {
+    if archive.had_entries() {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(archive.into_file()?);
+        write_archive(sources, to_crush_error(file)?)?;
+        output.send(Value::Empty())
+    } else {
+        let data = write_archive(sources, Cursor::new(Vec::new()))?;
+        output.send(Value::Binary(data.into_inner()))
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "zip",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Extract::declare(env)?;
+            Create::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_symlinks_round_trip() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().unix_permissions(0o644);
+        zip.start_file("file", options).unwrap();
+        zip.write_all(b"data").unwrap();
+        zip.start_file("link", options).unwrap();
+        zip.write_all(b"file").unwrap();
+        let writer = zip.finish().unwrap();
+
+        let symlinks = vec!["link".to_string()].into_iter().collect();
+        let writer = mark_symlinks(writer, &symlinks).unwrap();
+
+        let mut archive = ZipArchive::new(writer).unwrap();
+        assert_eq!(
+            archive.by_name("link").unwrap().unix_mode(),
+            Some(S_IFLNK | 0o644)
+        );
+        let mode = archive.by_name("file").unwrap().unix_mode().unwrap();
+        assert_eq!(mode & 0o170000, 0o100000, "the file type of a regular file");
+        let mut target = String::new();
+        archive
+            .by_name("link")
+            .unwrap()
+            .read_to_string(&mut target)
+            .unwrap();
+        assert_eq!(target, "file");
+    }
+}
@@ -0,0 +1,1027 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, data_error, error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use chrono::offset::TimeZone;
+use chrono::{DateTime, Local};
+use signature::signature;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufReader, Read, Write};
+
+const MAGIC: &[u8] = b"Obj\x01";
+
+/// The number of records written per block by avro:to.
+const BLOCK_SIZE: usize = 1000;
+
+/// How deeply records, arrays, maps and unions may be nested, so that a malicious schema,
+/// e.g. a record that contains itself, can't overflow the stack.
+const MAX_DEPTH: usize = 100;
+
+/// The largest number of values that take up no space, like nulls, that a block may hold.
+/// Other values take up at least a byte each, so their number is limited by the size of the
+/// block.
+const MAX_EMPTY_VALUES: usize = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record(Vec<(String, Schema)>),
+    Enum(Vec<String>),
+    Array(Box<Schema>),
+    Map(Box<Schema>),
+    Union(Vec<Schema>),
+    Fixed(usize),
+    /// Days since the epoch, stored as an int.
+    Date,
+    TimestampMillis,
+    TimestampMicros,
+    /// A reference to a named type, by its full name.
+    Named(String),
+}
+
+/// The named types (records, enums and fixed) of a schema, by their full names.
+#[derive(Default)]
+struct Names {
+    types: HashMap<String, Schema>,
+}
+
+fn full_name(name: &str, namespace: &str) -> String {
+    if name.contains('.') || namespace.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", namespace, name)
+    }
+}
+
+impl Names {
+    fn parse(&mut self, json: &serde_json::Value, namespace: &str) -> CrushResult<Schema> {
+        match json {
+            serde_json::Value::String(name) => Ok(match name.as_str() {
+                "null" => Schema::Null,
+                "boolean" => Schema::Boolean,
+                "int" => Schema::Int,
+                "long" => Schema::Long,
+                "float" => Schema::Float,
+                "double" => Schema::Double,
+                "bytes" => Schema::Bytes,
+                "string" => Schema::String,
+                name => {
+                    let full = full_name(name, namespace);
+                    if self.types.contains_key(&full) {
+                        Schema::Named(full)
+                    } else if self.types.contains_key(name) {
+                        Schema::Named(name.to_string())
+                    } else {
+                        return data_error(format!("Unknown avro type {}", name));
+                    }
+                }
+            }),
+            serde_json::Value::Array(variants) => Ok(Schema::Union(
+                variants
+                    .iter()
+                    .map(|v| self.parse(v, namespace))
+                    .collect::<CrushResult<Vec<_>>>()?,
+            )),
+            serde_json::Value::Object(o) => {
+                let type_name = match o.get("type") {
+                    Some(serde_json::Value::String(t)) => t.as_str(),
+                    Some(t) => return self.parse(t, namespace),
+                    None => return data_error("Missing type in avro schema"),
+                };
+                match (type_name, o.get("logicalType").and_then(|t| t.as_str())) {
+                    ("int", Some("date")) => return Ok(Schema::Date),
+                    ("long", Some("timestamp-millis")) => return Ok(Schema::TimestampMillis),
+                    ("long", Some("timestamp-micros")) => return Ok(Schema::TimestampMicros),
+                    _ => {}
+                }
+                match type_name {
+                    "record" | "error" | "enum" | "fixed" => {
+                        let name = match o.get("name").and_then(|n| n.as_str()) {
+                            Some(name) => name,
+                            None => return data_error("Missing name of avro type"),
+                        };
+                        let namespace = o
+                            .get("namespace")
+                            .and_then(|n| n.as_str())
+                            .unwrap_or(namespace);
+                        let full = full_name(name, namespace);
+                        let namespace = match full.rfind('.') {
+                            Some(idx) => full[..idx].to_string(),
+                            None => String::new(),
+                        };
+                        // Register the name first, so that records can refer to themselves
+                        self.types.insert(full.clone(), Schema::Null);
+                        let schema = match type_name {
+                            "enum" => Schema::Enum(
+                                o.get("symbols")
+                                    .and_then(|s| s.as_array())
+                                    .map(|s| {
+                                        s.iter()
+                                            .map(|s| s.as_str().unwrap_or("").to_string())
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                            ),
+                            "fixed" => match o.get("size").and_then(|s| s.as_u64()) {
+                                Some(size) => Schema::Fixed(size as usize),
+                                None => return data_error("Missing size of avro fixed type"),
+                            },
+                            _ => {
+                                let mut fields = Vec::new();
+                                for field in o
+                                    .get("fields")
+                                    .and_then(|f| f.as_array())
+                                    .map(|f| f.as_slice())
+                                    .unwrap_or(&[])
+                                {
+                                    let field_name = match field
+                                        .get("name")
+                                        .and_then(|n| n.as_str())
+                                    {
+                                        Some(n) => n.to_string(),
+                                        None => return data_error("Missing name of avro field"),
+                                    };
+                                    let field_type = match field.get("type") {
+                                        Some(t) => self.parse(t, &namespace)?,
+                                        None => return data_error("Missing type of avro field"),
+                                    };
+                                    fields.push((field_name, field_type));
+                                }
+                                Schema::Record(fields)
+                            }
+                        };
+                        self.types.insert(full, schema.clone());
+                        Ok(schema)
+                    }
+                    "array" => match o.get("items") {
+                        Some(items) => Ok(Schema::Array(Box::from(self.parse(items, namespace)?))),
+                        None => data_error("Missing items of avro array"),
+                    },
+                    "map" => match o.get("values") {
+                        Some(values) => Ok(Schema::Map(Box::from(self.parse(values, namespace)?))),
+                        None => data_error("Missing values of avro map"),
+                    },
+                    t => self.parse(&serde_json::Value::String(t.to_string()), namespace),
+                }
+            }
+            _ => data_error("Invalid avro schema"),
+        }
+    }
+
+    fn resolve<'a>(&'a self, schema: &'a Schema) -> &'a Schema {
+        match schema {
+            Schema::Named(name) => self.types.get(name).unwrap_or(&Schema::Null),
+            s => s,
+        }
+    }
+
+    fn value_type(&self, schema: &Schema) -> ValueType {
+        match self.resolve(schema) {
+            Schema::Null => ValueType::Empty,
+            Schema::Boolean => ValueType::Bool,
+            Schema::Int | Schema::Long => ValueType::Integer,
+            Schema::Float | Schema::Double => ValueType::Float,
+            Schema::Bytes | Schema::Fixed(_) => ValueType::Binary,
+            Schema::String | Schema::Enum(_) => ValueType::String,
+            Schema::Record(_) => ValueType::Struct,
+            Schema::Array(items) => ValueType::List(Box::from(self.value_type(items))),
+            Schema::Map(values) => ValueType::Dict(
+                Box::from(ValueType::String),
+                Box::from(self.value_type(values)),
+            ),
+            // Unions are of type any. Nullable columns are the exception, see column_type.
+            Schema::Union(_) => ValueType::Any,
+            Schema::Date | Schema::TimestampMillis | Schema::TimestampMicros => ValueType::Time,
+            Schema::Named(_) => ValueType::Any,
+        }
+    }
+
+    /// The type of a column. Nullable columns, i.e. unions of null and one other type, are of
+    /// that other type, and their null values are empty.
+    fn column_type(&self, schema: &Schema) -> ValueType {
+        match self.resolve(schema) {
+            Schema::Union(variants) => match variants.as_slice() {
+                [Schema::Null, schema] | [schema, Schema::Null] => self.value_type(schema),
+                _ => ValueType::Any,
+            },
+            schema => self.value_type(schema),
+        }
+    }
+
+    /// Whether every value of the schema takes up at least one byte. Records nested in
+    /// records are not looked into, which only means that more of them may be in a block.
+    fn is_sized(&self, schema: &Schema, nested: bool) -> bool {
+        match self.resolve(schema) {
+            Schema::Null | Schema::Fixed(0) => false,
+            Schema::Record(fields) => {
+                !nested && fields.iter().any(|(_, field)| self.is_sized(field, true))
+            }
+            _ => true,
+        }
+    }
+
+    /// Check the number of values in a block, which is the number of items of an array, map
+    /// entries or records, against the number of bytes left for them.
+    fn check_count(&self, schema: &Schema, count: usize, remaining: usize) -> CrushResult<usize> {
+        let limit = if self.is_sized(schema, false) {
+            remaining
+        } else {
+            MAX_EMPTY_VALUES
+        };
+        if count > limit {
+            data_error("Invalid avro block count")
+        } else {
+            Ok(count)
+        }
+    }
+
+    fn decode(&self, schema: &Schema, input: &mut Input) -> CrushResult<Value> {
+        self.decode_nested(schema, input, 0)
+    }
+
+    fn decode_nested(
+        &self,
+        schema: &Schema,
+        input: &mut Input,
+        depth: usize,
+    ) -> CrushResult<Value> {
+        if depth >= MAX_DEPTH {
+            return data_error("Avro values nested too deeply");
+        }
+        Ok(match self.resolve(schema) {
+            Schema::Null => Value::Empty(),
+            Schema::Boolean => Value::Bool(input.bytes(1)?[0] != 0),
+            Schema::Int | Schema::Long => Value::Integer(input.long()? as i128),
+            Schema::Float => {
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(input.bytes(4)?);
+                Value::Float(f32::from_le_bytes(buf) as f64)
+            }
+            Schema::Double => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(input.bytes(8)?);
+                Value::Float(f64::from_le_bytes(buf))
+            }
+            Schema::Bytes => Value::Binary(input.length_delimited()?.to_vec()),
+            Schema::String => Value::String(input.string()?),
+            Schema::Record(fields) => Value::Struct(Struct::new(
+                fields
+                    .iter()
+                    .map(|(name, field)| {
+                        Ok((name.clone(), self.decode_nested(field, input, depth + 1)?))
+                    })
+                    .collect::<CrushResult<Vec<_>>>()?,
+                None,
+            )),
+            Schema::Enum(symbols) => match symbols.get(input.long()? as usize) {
+                Some(symbol) => Value::string(symbol),
+                None => return data_error("Invalid avro enum index"),
+            },
+            Schema::Array(items) => {
+                let mut values = Vec::new();
+                while let Some(count) = input.block_count()? {
+                    for _ in 0..self.check_count(items, count, input.remaining())? {
+                        values.push(self.decode_nested(items, input, depth + 1)?);
+                    }
+                }
+                Value::List(List::new(self.value_type(items), values))
+            }
+            Schema::Map(values) => {
+                let dict = Dict::new(ValueType::String, self.value_type(values));
+                // Every entry starts with its key, so entries always take up space
+                while let Some(count) = input.block_count()? {
+                    for _ in 0..self.check_count(&Schema::String, count, input.remaining())? {
+                        let key = Value::String(input.string()?);
+                        dict.insert(key, self.decode_nested(values, input, depth + 1)?)?;
+                    }
+                }
+                Value::Dict(dict)
+            }
+            Schema::Union(variants) => match variants.get(input.long()? as usize) {
+                Some(variant) => self.decode_nested(variant, input, depth + 1)?,
+                None => return data_error("Invalid avro union index"),
+            },
+            Schema::Fixed(size) => Value::Binary(input.bytes(*size)?.to_vec()),
+            Schema::Date => time(input.long()?, 86_400 * 1_000_000_000)?,
+            Schema::TimestampMillis => time(input.long()?, 1_000_000)?,
+            Schema::TimestampMicros => time(input.long()?, 1_000)?,
+            Schema::Named(name) => return data_error(format!("Unknown avro type {}", name)),
+        })
+    }
+
+    /// Whether a value can be written using the specified schema.
+    fn accepts(&self, schema: &Schema, value: &Value) -> bool {
+        matches!(
+            (self.resolve(schema), value),
+            (Schema::Null, Value::Empty())
+                | (Schema::Boolean, Value::Bool(_))
+                | (Schema::Int, Value::Integer(_))
+                | (Schema::Long, Value::Integer(_))
+                | (Schema::Float, Value::Float(_))
+                | (Schema::Double, Value::Float(_))
+                | (Schema::Bytes, Value::Binary(_))
+                | (Schema::Fixed(_), Value::Binary(_))
+                | (Schema::String, Value::String(_))
+                | (Schema::String, Value::File(_))
+                | (Schema::Enum(_), Value::String(_))
+                | (Schema::Record(_), Value::Struct(_))
+                | (Schema::Array(_), Value::List(_))
+                | (Schema::Map(_), Value::Dict(_))
+                | (Schema::TimestampMillis, Value::Time(_))
+                | (Schema::TimestampMicros, Value::Time(_))
+        )
+    }
+
+    fn encode(&self, schema: &Schema, value: Value, out: &mut Vec<u8>) -> CrushResult<()> {
+        match (self.resolve(schema), value) {
+            (Schema::Null, Value::Empty()) => {}
+            (Schema::Union(variants), value) => {
+                match variants.iter().position(|v| self.accepts(v, &value)) {
+                    Some(idx) => {
+                        write_long(out, idx as i64);
+                        self.encode(&variants[idx], value, out)?;
+                    }
+                    None => {
+                        return data_error(format!(
+                            "A value of type {} does not match any type of the avro union",
+                            value.value_type()
+                        ))
+                    }
+                }
+            }
+            (Schema::Record(fields), Value::Struct(s)) => {
+                for (name, field) in fields {
+                    self.encode(field, s.get(name).unwrap_or(Value::Empty()), out)?;
+                }
+            }
+            (Schema::Boolean, Value::Bool(b)) => out.push(b as u8),
+            (Schema::Int, Value::Integer(i)) => match i32::try_from(i) {
+                Ok(i) => write_long(out, i as i64),
+                Err(_) => return data_error("Integer out of range for an avro int"),
+            },
+            (Schema::Long, Value::Integer(i)) => match i64::try_from(i) {
+                Ok(i) => write_long(out, i),
+                Err(_) => return data_error("Integer out of range for an avro long"),
+            },
+            (Schema::Float, Value::Float(f)) => out.extend_from_slice(&(f as f32).to_le_bytes()),
+            (Schema::Double, Value::Float(f)) => out.extend_from_slice(&f.to_le_bytes()),
+            (Schema::Double, Value::Integer(i)) => out.extend_from_slice(&(i as f64).to_le_bytes()),
+            (Schema::Bytes, Value::Binary(b)) => write_bytes(out, &b),
+            (Schema::String, Value::String(s)) => write_bytes(out, s.as_bytes()),
+            (Schema::String, Value::File(f)) => write_bytes(out, f.to_string_lossy().as_bytes()),
+            (Schema::TimestampMicros, Value::Time(t)) => write_long(out, timestamp(&t, 1_000_000)?),
+            (Schema::TimestampMillis, Value::Time(t)) => write_long(out, timestamp(&t, 1_000)?),
+            (Schema::Array(items), Value::List(l)) => {
+                let values = l.dump();
+                if !values.is_empty() {
+                    write_long(out, values.len() as i64);
+                    for value in values {
+                        self.encode(items, value, out)?;
+                    }
+                }
+                write_long(out, 0);
+            }
+            (Schema::Map(values), Value::Dict(d)) => {
+                let elements = d.elements();
+                if !elements.is_empty() {
+                    write_long(out, elements.len() as i64);
+                    for (key, value) in elements {
+                        write_bytes(out, key.to_string().as_bytes());
+                        self.encode(values, value, out)?;
+                    }
+                }
+                write_long(out, 0);
+            }
+            (_, value) => {
+                return data_error(format!(
+                    "Can't write a value of type {} as avro",
+                    value.value_type()
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The avro schema used to write values of the specified type.
+fn schema_for(value_type: &ValueType) -> CrushResult<(Schema, serde_json::Value)> {
+    Ok(match value_type {
+        ValueType::Integer => (Schema::Long, serde_json::json!("long")),
+        ValueType::Float => (Schema::Double, serde_json::json!("double")),
+        ValueType::Bool => (Schema::Boolean, serde_json::json!("boolean")),
+        ValueType::String | ValueType::File => (Schema::String, serde_json::json!("string")),
+        ValueType::Binary => (Schema::Bytes, serde_json::json!("bytes")),
+        ValueType::Time => (
+            Schema::TimestampMicros,
+            serde_json::json!({"type": "long", "logicalType": "timestamp-micros"}),
+        ),
+        ValueType::List(items) => {
+            let (schema, json) = schema_for(items)?;
+            (
+                Schema::Array(Box::from(schema)),
+                serde_json::json!({"type": "array", "items": json}),
+            )
+        }
+        ValueType::Dict(key, values) if **key == ValueType::String => {
+            let (schema, json) = schema_for(values)?;
+            (
+                Schema::Map(Box::from(schema)),
+                serde_json::json!({"type": "map", "values": json}),
+            )
+        }
+        ValueType::Any => {
+            let types = [
+                ValueType::Bool,
+                ValueType::Integer,
+                ValueType::Float,
+                ValueType::String,
+                ValueType::Binary,
+                ValueType::Time,
+            ];
+            let mut schemas = vec![Schema::Null];
+            let mut json = vec![serde_json::json!("null")];
+            for t in types.iter() {
+                let (schema, schema_json) = schema_for(t)?;
+                schemas.push(schema);
+                json.push(schema_json);
+            }
+            (Schema::Union(schemas), serde_json::Value::Array(json))
+        }
+        t => return error(format!("Unsupported column type {} for avro", t)),
+    })
+}
+
+/// Convert a date or timestamp, counted in units of the specified number of nanoseconds since
+/// the epoch, to a time.
+fn time(value: i64, nanoseconds: i64) -> CrushResult<Value> {
+    let total = value as i128 * nanoseconds as i128;
+    let seconds = i64::try_from(total.div_euclid(1_000_000_000)).ok();
+    let nanoseconds = total.rem_euclid(1_000_000_000) as u32;
+    match seconds.and_then(|seconds| Local.timestamp_opt(seconds, nanoseconds).single()) {
+        Some(t) => Ok(Value::Time(t)),
+        None => data_error("Avro date or timestamp out of range"),
+    }
+}
+
+/// Convert a time to a timestamp, counted in units of the specified fraction of a second since
+/// the epoch.
+fn timestamp(t: &DateTime<Local>, per_second: i64) -> CrushResult<i64> {
+    let fraction = t.timestamp_subsec_nanos() as i64 / (1_000_000_000 / per_second);
+    match t
+        .timestamp()
+        .checked_mul(per_second)
+        .and_then(|units| units.checked_add(fraction))
+    {
+        Some(units) => Ok(units),
+        None => data_error("Time out of range for an avro timestamp"),
+    }
+}
+
+/// A cursor over a block of encoded records.
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// The number of bytes left.
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn long(&mut self) -> CrushResult<i64> {
+        let mut res = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            res |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(((res >> 1) as i64) ^ -((res & 1) as i64));
+            }
+        }
+        data_error("Invalid avro long")
+    }
+
+    fn bytes(&mut self, len: usize) -> CrushResult<&'a [u8]> {
+        if self.remaining() < len {
+            return data_error("Truncated avro data");
+        }
+        let res = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(res)
+    }
+
+    fn length_delimited(&mut self) -> CrushResult<&'a [u8]> {
+        let len = self.long()?;
+        if len < 0 {
+            return data_error("Negative avro length");
+        }
+        self.bytes(len as usize)
+    }
+
+    fn string(&mut self) -> CrushResult<String> {
+        match String::from_utf8(self.length_delimited()?.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => data_error("Invalid UTF-8 in avro string"),
+        }
+    }
+
+    /// Read the item count of the next block of an array or map, or None after the last
+    /// block. Blocks with a negative count are followed by their size in bytes.
+    fn block_count(&mut self) -> CrushResult<Option<usize>> {
+        match self.long()? {
+            0 => Ok(None),
+            count if count < 0 => {
+                self.long()?;
+                match count.checked_neg() {
+                    Some(count) => Ok(Some(count as usize)),
+                    None => data_error("Invalid avro block count"),
+                }
+            }
+            count => Ok(Some(count as usize)),
+        }
+    }
+}
+
+/// Read a long from a reader, or None if the reader is at its end.
+fn read_long(reader: &mut impl Read) -> CrushResult<Option<i64>> {
+    let mut data = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        if to_crush_error(reader.read(&mut byte))? == 0 {
+            return if data.is_empty() {
+                Ok(None)
+            } else {
+                data_error("Truncated avro data")
+            };
+        }
+        data.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(
+                Input {
+                    data: &data,
+                    pos: 0,
+                }
+                .long()?,
+            ));
+        }
+    }
+}
+
+/// Read a length from a reader. Lengths are never negative.
+fn read_length(reader: &mut impl Read) -> CrushResult<usize> {
+    match read_long(reader)? {
+        Some(len) if len >= 0 => Ok(len as usize),
+        Some(_) => data_error("Negative avro length"),
+        None => data_error("Truncated avro data"),
+    }
+}
+
+/// Read the specified number of bytes. The buffer only grows as data arrives, so a corrupt
+/// length fails with an error instead of allocating more memory than the input holds.
+fn read_exact(reader: &mut impl Read, len: usize) -> CrushResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    to_crush_error(reader.by_ref().take(len as u64).read_to_end(&mut buf))?;
+    if buf.len() != len {
+        return data_error("Truncated avro data");
+    }
+    Ok(buf)
+}
+
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    write_long(out, data.len() as i64);
+    out.extend_from_slice(data);
+}
+
+#[signature(
+from,
+can_block = true,
+output = Unknown,
+short = "Parse avro container files",
+example = "avro:from ./events.avro | where {kind == \"click\"}",
+long = "The column names and types are taken from the schema embedded in the file. Records
+    become rows, nested records become structs, arrays become lists and maps become dicts.
+    Nullable columns are of the type of their values, and null values are empty. Columns of other
+    union types are of type any. Blocks may be uncompressed or compressed with the deflate codec;
+    other codecs are not supported.")]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+}
+
+fn from(context: CommandContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+
+    if read_exact(&mut reader, MAGIC.len())? != MAGIC {
+        return data_error("Not an avro container file");
+    }
+    let mut metadata = HashMap::new();
+    while let Some(count) = read_long(&mut reader)? {
+        let count = match count {
+            0 => break,
+            count if count < 0 => {
+                read_long(&mut reader)?;
+                match count.checked_neg() {
+                    Some(count) => count,
+                    None => return data_error("Invalid avro block count"),
+                }
+            }
+            count => count,
+        };
+        for _ in 0..count {
+            let key_len = read_length(&mut reader)?;
+            let key = String::from_utf8_lossy(&read_exact(&mut reader, key_len)?).to_string();
+            let value_len = read_length(&mut reader)?;
+            metadata.insert(key, read_exact(&mut reader, value_len)?);
+        }
+    }
+    let sync = read_exact(&mut reader, 16)?;
+
+    let deflate = match metadata.get("avro.codec").map(|c| c.as_slice()) {
+        None | Some(b"null") => false,
+        Some(b"deflate") => true,
+        Some(codec) => {
+            return data_error(format!(
+                "Unsupported avro codec {}",
+                String::from_utf8_lossy(codec)
+            ))
+        }
+    };
+    let schema_json: serde_json::Value = match metadata.get("avro.schema") {
+        Some(schema) => to_crush_error(serde_json::from_slice(schema))?,
+        None => return data_error("Missing avro schema"),
+    };
+    let mut names = Names::default();
+    let schema = names.parse(&schema_json, "")?;
+
+    let columns = match names.resolve(&schema) {
+        Schema::Record(fields) => fields
+            .iter()
+            .map(|(name, field)| ColumnType::new(name, names.column_type(field)))
+            .collect(),
+        s => vec![ColumnType::new("value", names.column_type(s))],
+    };
+    let output = context.output.initialize(columns)?;
+
+    while let Some(count) = read_long(&mut reader)? {
+        let size = match read_long(&mut reader)? {
+            Some(size) if size >= 0 => size as usize,
+            _ => return data_error("Invalid avro block size"),
+        };
+        let mut data = read_exact(&mut reader, size)?;
+        if deflate {
+            let mut inflated = Vec::new();
+            to_crush_error(
+                flate2::read::DeflateDecoder::new(data.as_slice()).read_to_end(&mut inflated),
+            )?;
+            data = inflated;
+        }
+        let mut input = Input {
+            data: &data,
+            pos: 0,
+        };
+        let count = match usize::try_from(count) {
+            Ok(count) => names.check_count(&schema, count, data.len())?,
+            Err(_) => return data_error("Invalid avro block count"),
+        };
+        for _ in 0..count {
+            let row = match names.decode(&schema, &mut input)? {
+                Value::Struct(s) => {
+                    Row::new(s.local_elements().drain(..).map(|(_, v)| v).collect())
+                }
+                value => Row::new(vec![value]),
+            };
+            output.send(row)?;
+        }
+        if !input.is_empty() {
+            return data_error("Unexpected data at the end of avro block");
+        }
+        if read_exact(&mut reader, 16)? != sync {
+            return data_error("Invalid avro sync marker");
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize a table or table stream to an avro container file",
+example = "ps | avro:to ./processes.avro",
+long = "The schema of the file is derived from the column types of the input. All columns
+    are nullable. Integers are written as longs, floats as doubles, times as timestamps in
+    microseconds, lists as arrays and dicts with string keys as maps. Columns of type any are
+    written as a union of the basic types. Blocks are uncompressed unless --deflate is given.")]
+struct To {
+    #[unnamed()]
+    #[description("destination. If unspecified, will write to io as a binary_stream.")]
+    file: Files,
+    #[default(false)]
+    #[description("compress blocks with the avro deflate codec.")]
+    deflate: bool,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let mut input = match context.input.recv()?.stream() {
+        Some(input) => input,
+        None => return argument_error("Expected a table or a table stream"),
+    };
+
+    let mut fields = Vec::new();
+    let mut fields_json = Vec::new();
+    for column in input.types() {
+        let (schema, json) = match schema_for(&column.cell_type)? {
+            // Unions can't contain other unions, and already include null
+            (Schema::Union(variants), json) => (Schema::Union(variants), json),
+            (schema, json) => (
+                Schema::Union(vec![Schema::Null, schema]),
+                serde_json::json!(["null", json]),
+            ),
+        };
+        fields.push((column.name.clone(), schema));
+        fields_json.push(serde_json::json!({"name": column.name, "type": json}));
+    }
+    let schema = Schema::Record(fields);
+    let schema_json =
+        serde_json::json!({"type": "record", "name": "Row", "fields": fields_json}).to_string();
+    let names = Names::default();
+    let sync = rand::random::<[u8; 16]>();
+
+    let mut header = MAGIC.to_vec();
+    write_long(&mut header, 2);
+    write_bytes(&mut header, b"avro.schema");
+    write_bytes(&mut header, schema_json.as_bytes());
+    write_bytes(&mut header, b"avro.codec");
+    write_bytes(
+        &mut header,
+        if cfg.deflate {
+            b"deflate".as_ref()
+        } else {
+            b"null".as_ref()
+        },
+    );
+    write_long(&mut header, 0);
+    header.extend_from_slice(&sync);
+
+    let mut writer = cfg.file.writer(context.output)?;
+    to_crush_error(writer.write_all(&header))?;
+
+    let types = input.types().to_vec();
+    let mut block = Vec::new();
+    let mut count = 0;
+    loop {
+        let row = input.read().ok();
+        if let Some(row) = &row {
+            names.encode(
+                &schema,
+                Value::Struct(row.clone().into_struct(&types)),
+                &mut block,
+            )?;
+            count += 1;
+        }
+        if count > 0 && (count == BLOCK_SIZE || row.is_none()) {
+            let mut data = Vec::new();
+            write_long(&mut data, count as i64);
+            if cfg.deflate {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                to_crush_error(encoder.write_all(&block))?;
+                write_bytes(&mut data, &to_crush_error(encoder.finish())?);
+            } else {
+                write_bytes(&mut data, &block);
+            }
+            data.extend_from_slice(&sync);
+            to_crush_error(writer.write_all(&data))?;
+            block.clear();
+            count = 0;
+        }
+        if row.is_none() {
+            return writer.finish();
+        }
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "avro",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_schema() {
+        let json = serde_json::json!({
+            "type": "record",
+            "name": "Node",
+            "namespace": "tree",
+            "fields": [
+                {"name": "label", "type": ["null", "string"]},
+                {"name": "children", "type": {"type": "array", "items": "Node"}},
+                {"name": "created", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            ]
+        });
+        let mut names = Names::default();
+        let schema = names.parse(&json, "").unwrap();
+        match schema {
+            Schema::Record(fields) => {
+                assert_eq!(names.value_type(&fields[0].1), ValueType::Any);
+                assert_eq!(names.column_type(&fields[0].1), ValueType::String);
+                assert_eq!(
+                    fields[1].1,
+                    Schema::Array(Box::from(Schema::Named("tree.Node".to_string())))
+                );
+                assert_eq!(names.value_type(&fields[2].1), ValueType::Time);
+            }
+            s => panic!("Unexpected schema {:?}", s),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let names = Names::default();
+        let schema = Schema::Record(vec![
+            (
+                "a".to_string(),
+                Schema::Union(vec![Schema::Null, Schema::Long]),
+            ),
+            ("b".to_string(), Schema::Array(Box::from(Schema::String))),
+        ]);
+        let value = Value::Struct(Struct::new(
+            vec![
+                ("a".to_string(), Value::Integer(-300)),
+                (
+                    "b".to_string(),
+                    Value::List(List::new(
+                        ValueType::String,
+                        vec![Value::string("x"), Value::string("y")],
+                    )),
+                ),
+            ],
+            None,
+        ));
+        let mut data = Vec::new();
+        names.encode(&schema, value.clone(), &mut data).unwrap();
+        let decoded = names
+            .decode(
+                &schema,
+                &mut Input {
+                    data: &data,
+                    pos: 0,
+                },
+            )
+            .unwrap();
+        assert!(decoded == value);
+    }
+
+    #[test]
+    fn timestamp_out_of_range() {
+        assert!(time(i64::MAX / 1_000, 1_000).is_ok());
+        assert!(time(i64::MIN, 1).is_ok());
+        assert!(time(i64::MAX, 1_000_000).is_err());
+        assert!(time(i64::MAX, 86_400 * 1_000_000_000).is_err());
+    }
+
+    #[test]
+    fn integer_out_of_range() {
+        let names = Names::default();
+        let mut data = Vec::new();
+        assert!(names
+            .encode(&Schema::Int, Value::Integer(1 << 40), &mut data)
+            .is_err());
+        assert!(names
+            .encode(&Schema::Long, Value::Integer(1 << 40), &mut data)
+            .is_ok());
+        assert!(names
+            .encode(&Schema::Long, Value::Integer(1 << 80), &mut data)
+            .is_err());
+    }
+
+    #[test]
+    fn timestamp_far_from_epoch() {
+        let names = Names::default();
+        let t = Local.ymd(3000, 1, 1).and_hms_micro(12, 0, 0, 250);
+        let mut data = Vec::new();
+        names
+            .encode(&Schema::TimestampMicros, Value::Time(t), &mut data)
+            .unwrap();
+        let decoded = names
+            .decode(
+                &Schema::TimestampMicros,
+                &mut Input {
+                    data: &data,
+                    pos: 0,
+                },
+            )
+            .unwrap();
+        assert!(decoded == Value::Time(t));
+        assert!(timestamp(&Local.ymd(1600, 1, 1).and_hms(0, 0, 0), 1_000).is_ok());
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        let mut data = Vec::new();
+        write_long(&mut data, -5);
+        assert!(read_length(&mut data.as_slice()).is_err());
+        assert!(read_exact(&mut &b"abc"[..], usize::MAX).is_err());
+        assert_eq!(read_exact(&mut &b"abc"[..], 2).unwrap(), b"ab");
+
+        let mut data = Vec::new();
+        write_long(&mut data, i64::MIN);
+        write_long(&mut data, 0);
+        let mut input = Input {
+            data: &data,
+            pos: 0,
+        };
+        assert!(input.block_count().is_err());
+    }
+
+    #[test]
+    fn nested_too_deeply() {
+        let json = serde_json::json!({
+            "type": "record",
+            "name": "Loop",
+            "fields": [{"name": "next", "type": "Loop"}]
+        });
+        let mut names = Names::default();
+        let schema = names.parse(&json, "").unwrap();
+        let mut input = Input { data: &[], pos: 0 };
+        assert!(names.decode(&schema, &mut input).is_err());
+    }
+
+    #[test]
+    fn invalid_block_counts() {
+        let mut names = Names::default();
+        let nulls = names
+            .parse(&serde_json::json!({"type": "array", "items": "null"}), "")
+            .unwrap();
+        let longs = names
+            .parse(&serde_json::json!({"type": "array", "items": "long"}), "")
+            .unwrap();
+
+        let mut data = Vec::new();
+        write_long(&mut data, 3);
+        write_long(&mut data, 0);
+        let decode = |schema: &Schema| {
+            names.decode(
+                schema,
+                &mut Input {
+                    data: &data,
+                    pos: 0,
+                },
+            )
+        };
+        assert!(decode(&nulls).is_ok());
+        assert!(decode(&longs).is_err());
+
+        let mut data = Vec::new();
+        write_long(&mut data, i64::MAX);
+        write_long(&mut data, 0);
+        let mut input = Input {
+            data: &data,
+            pos: 0,
+        };
+        assert!(names.decode(&nulls, &mut input).is_err());
+    }
+}
@@ -19,7 +19,7 @@ pub fn from(context: CommandContext) -> CrushResult<()> {
     let cfg: From = From::parse(context.arguments, &context.printer)?;
     context
         .output
-        .send(Value::BinaryStream(cfg.files.reader(context.input)?))
+        .send(Value::BinaryStream(cfg.files.raw_reader(context.input)?))
 }
 
 #[signature(
@@ -37,7 +37,7 @@ pub fn to(context: CommandContext) -> CrushResult<()> {
 
     match context.input.recv()? {
         Value::BinaryStream(mut input) => {
-            let mut out = cfg.file.writer(context.output)?;
+            let mut out = cfg.file.raw_writer(context.output)?;
             to_crush_error(std::io::copy(input.as_mut(), out.as_mut()))?;
             Ok(())
         }
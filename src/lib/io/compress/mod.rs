@@ -0,0 +1,152 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::{binary_channel, BinaryReader};
+use crate::lang::compression::Compression;
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::value::Value;
+use signature::signature;
+use std::io::{copy, Write};
+
+/// Decompress the specified files, or the input if no files were given, into a binary stream.
+fn decompress(
+    input: ValueReceiver,
+    output: ValueSender,
+    files: Files,
+    compression: Compression,
+) -> CrushResult<()> {
+    let mut decoder = compression.decoder(files.raw_reader(input)?)?;
+    let (mut writer, reader) = binary_channel();
+    output.send(Value::BinaryStream(reader))?;
+    to_crush_error(copy(&mut decoder, &mut writer))?;
+    Ok(())
+}
+
+/// Compress the input into the specified file, or into a binary stream if no file was given.
+fn compress(
+    input: ValueReceiver,
+    output: ValueSender,
+    file: Files,
+    compression: Compression,
+) -> CrushResult<()> {
+    let mut reader = match input.recv()? {
+        Value::BinaryStream(reader) => reader,
+        Value::Binary(data) => BinaryReader::vec(&data),
+        Value::String(data) => BinaryReader::vec(&data.into_bytes()),
+        _ => return argument_error("Expected a binary stream"),
+    };
+    let mut encoder = compression.encoder(file.raw_writer(output)?)?;
+    to_crush_error(copy(&mut reader, &mut encoder))?;
+    to_crush_error(encoder.finish()?.flush())
+}
+
+/// Declare a namespace named after the module with a from command that decompresses, and a to
+/// command that compresses, using the specified format.
+macro_rules! compression_namespace {
+    (
+        $module:ident,
+        $compression:expr,
+        $from_short:tt,
+        $from_example:tt,
+        $to_short:tt,
+        $to_example:tt
+    ) => {
+        mod $module {
+            use super::*;
+
+            #[signature(
+                from,
+                can_block = true,
+                short = $from_short,
+                example = $from_example
+            )]
+            struct From {
+                #[unnamed()]
+                #[description(
+                    "source. If unspecified, will read from io, which must be a binary or binary_stream."
+                )]
+                files: Files,
+            }
+
+            fn from(context: CommandContext) -> CrushResult<()> {
+                let cfg: From = From::parse(context.arguments, &context.printer)?;
+                decompress(context.input, context.output, cfg.files, $compression)
+            }
+
+            #[signature(
+                to,
+                can_block = true,
+                short = $to_short,
+                example = $to_example
+            )]
+            struct To {
+                #[unnamed()]
+                #[description("destination. If unspecified, will write to io as a binary_stream.")]
+                file: Files,
+            }
+
+            fn to(context: CommandContext) -> CrushResult<()> {
+                let cfg: To = To::parse(context.arguments, &context.printer)?;
+                compress(context.input, context.output, cfg.file, $compression)
+            }
+
+            pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+                root.create_namespace(
+                    stringify!($module),
+                    Box::new(move |env| {
+                        From::declare(env)?;
+                        To::declare(env)?;
+                        Ok(())
+                    }),
+                )?;
+                Ok(())
+            }
+        }
+    };
+}
+
+compression_namespace!(
+    bz2,
+    Compression::Bzip2,
+    "Decompress bzip2 data into a binary stream",
+    "bin:from ./access.log.bz2 | bz2:from | lines:from",
+    "Compress a binary stream using bzip2",
+    "ls | pup:to | bz2:to ./files.pup.bz2"
+);
+
+compression_namespace!(
+    gz,
+    Compression::Gzip,
+    "Decompress gzip data into a binary stream",
+    "bin:from ./access.log.gz | gz:from | lines:from",
+    "Compress a binary stream using gzip",
+    "ls | pup:to | gz:to ./files.pup.gz"
+);
+
+compression_namespace!(
+    xz,
+    Compression::Xz,
+    "Decompress xz data into a binary stream",
+    "bin:from ./access.log.xz | xz:from | lines:from",
+    "Compress a binary stream using xz",
+    "ls | pup:to | xz:to ./files.pup.xz"
+);
+
+compression_namespace!(
+    zstd,
+    Compression::Zstd,
+    "Decompress zstd data into a binary stream",
+    "bin:from ./access.log.zst | zstd:from | lines:from",
+    "Compress a binary stream using zstd",
+    "ls | pup:to | zstd:to ./files.pup.zst"
+);
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    bz2::declare(root)?;
+    gz::declare(root)?;
+    xz::declare(root)?;
+    zstd::declare(root)?;
+    Ok(())
+}
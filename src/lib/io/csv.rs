@@ -3,9 +3,10 @@ use crate::{
     lang::errors::CrushError,
     lang::{table::Row, value::Value},
 };
+use std::cmp::max;
 use std::{io::prelude::*, io::BufReader};
 
-use crate::lang::errors::{error, to_crush_error, CrushResult};
+use crate::lang::errors::{argument_error, data_error, error, to_crush_error, CrushResult};
 use crate::lang::table::ColumnType;
 
 use crate::lang::argument::ArgumentHandler;
@@ -15,10 +16,123 @@ use crate::lang::scope::ScopeLoader;
 use crate::lang::value::ValueType;
 use signature::signature;
 
+/// Reads the records of a CSV file as described in RFC 4180. Fields may be quoted, in which
+/// case they can contain separators, newlines and escaped (doubled) quotes. Both LF and CRLF
+/// line endings are accepted.
+struct CsvReader<R: BufRead> {
+    reader: R,
+    separator: char,
+    /// The line number of the last line read, counting from one.
+    line: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    fn new(reader: R, separator: char) -> CsvReader<R> {
+        CsvReader {
+            reader,
+            separator,
+            line: 0,
+        }
+    }
+
+    /// Read the next record, skipping empty lines. Returns None at the end of the input.
+    fn next(&mut self) -> CrushResult<Option<Vec<String>>> {
+        loop {
+            match self.next_record()? {
+                Some(record) if record.is_empty() => continue,
+                record => return Ok(record),
+            }
+        }
+    }
+
+    /// Read the next record. An empty line is a record without any fields, unlike a line
+    /// containing only "", which is a record with one empty field.
+    fn next_record(&mut self) -> CrushResult<Option<Vec<String>>> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut quoted = false;
+        let mut line = String::new();
+        let start_line = self.line + 1;
+        loop {
+            line.clear();
+            if to_crush_error(self.reader.read_line(&mut line))? == 0 {
+                if in_quotes {
+                    return data_error(format!(
+                        "Unterminated quoted field starting on line {}",
+                        start_line
+                    ));
+                }
+                if self.line < start_line {
+                    return Ok(None);
+                }
+                break;
+            }
+            self.line += 1;
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    if c == '"' {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                            field.push('"');
+                        } else {
+                            in_quotes = false;
+                        }
+                    } else {
+                        field.push(c);
+                    }
+                } else if c == '"' && field.is_empty() && !quoted {
+                    in_quotes = true;
+                    quoted = true;
+                } else if c == self.separator {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                } else if c == '\n' || (c == '\r' && matches!(chars.peek(), Some('\n') | None)) {
+                    // The end of the record, unless we're inside a quoted field
+                } else {
+                    field.push(c);
+                }
+            }
+            if !in_quotes {
+                break;
+            }
+        }
+        if fields.is_empty() && field.is_empty() && !quoted {
+            return Ok(Some(fields));
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+/// Guess the type of a column from a set of sample values. Columns where all values are
+/// integers, floats or booleans get that type, all other columns are strings.
+fn infer_type<'a>(mut values: impl Iterator<Item = &'a str> + Clone) -> ValueType {
+    if values.clone().all(|v| v.parse::<i128>().is_ok()) {
+        ValueType::Integer
+    } else if values.clone().all(|v| v.parse::<f64>().is_ok()) {
+        ValueType::Float
+    } else if values.all(|v| v.parse::<bool>().is_ok()) {
+        ValueType::Bool
+    } else {
+        ValueType::String
+    }
+}
+
 #[signature(
     from,
-    example = "csv:from separator=\",\" head=1 name=string age=integer nick=string",
-    short = "Parse specified files as CSV files"
+    can_block = true,
+    example = "csv:from ./people.csv --header infer=100",
+    short = "Parse specified files as CSV files",
+    long = "The column names and types can be given explicitly as named arguments, e.g.
+    `name=string age=integer`. Otherwise, the column names are taken from the header row if
+    `header` is set, or are named _1, _2, etc. The types of columns that are not given
+    explicitly are inferred from the first `infer` rows, or are strings if `infer` is zero.
+
+    Quoted fields may contain separators, newlines and quotes, which are escaped by doubling
+    them, as described in RFC 4180."
 )]
 #[derive(Debug)]
 struct From {
@@ -33,8 +147,14 @@ struct From {
     #[description("column separator.")]
     #[default(',')]
     separator: char,
+    #[default(false)]
+    #[description("the first row contains the names of the columns.")]
+    header: bool,
     #[default(0usize)]
-    #[description("skip this many lines of inpit from the beginning.")]
+    #[description("infer the types of the columns from this many rows.")]
+    infer: usize,
+    #[default(0usize)]
+    #[description("skip this many records of input from the beginning.")]
     head: usize,
     #[description("trim this character from start and end of every value.")]
     trim: Option<char>,
@@ -42,60 +162,168 @@ struct From {
 
 fn from(context: CommandContext) -> CrushResult<()> {
     let cfg: From = From::parse(context.arguments, &context.printer)?;
-    let columns = cfg
-        .columns
-        .iter()
-        .map(|(k, v)| ColumnType::new(k, v.clone()))
-        .collect::<Vec<_>>();
-    let output = context.output.initialize(columns.clone())?;
+    let mut reader = CsvReader::new(
+        BufReader::new(cfg.files.reader(context.input)?),
+        cfg.separator,
+    );
+    let trim = cfg.trim;
+    let infer = cfg.infer;
+    let mut next = || -> CrushResult<Option<Vec<String>>> {
+        Ok(reader.next()?.map(|mut record| match trim {
+            Some(c) => record.drain(..).map(|s| s.trim_matches(c).to_string()).collect(),
+            None => record,
+        }))
+    };
 
-    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    for _ in 0..cfg.head {
+        next()?;
+    }
 
-    let separator = cfg.separator;
-    let trim = cfg.trim;
-    let skip = cfg.head;
+    let header = if cfg.header { next()? } else { None };
 
-    let mut line = String::new();
-    let mut skipped = 0usize;
-    loop {
-        line.clear();
-        to_crush_error(reader.read_line(&mut line))?;
-        if line.is_empty() {
-            break;
-        }
-        if skipped < skip {
-            skipped += 1;
-            continue;
-        }
-        let line_without_newline = &line[0..line.len() - 1];
-        let mut split: Vec<&str> = line_without_newline
-            .split(separator)
-            .map(|s| trim.map(|c| s.trim_matches(c)).unwrap_or(s))
-            .collect();
-
-        if split.len() != columns.len() {
-            return error("csv: Wrong number of columns in CSV file");
+    // Rows that are read before the output is initialized, in order to infer column types
+    let mut sample = Vec::new();
+    let columns = if cfg.columns.is_empty() {
+        // Without a header, at least one row is needed to count the columns
+        let needed = max(infer, if header.is_none() { 1 } else { 0 });
+        while sample.len() < needed {
+            match next()? {
+                Some(record) => sample.push(record),
+                None => break,
+            }
         }
+        let names = match &header {
+            Some(names) => names.clone(),
+            None => {
+                let len = sample.first().map(|r| r.len()).unwrap_or(0);
+                (1..=len).map(|idx| format!("_{}", idx)).collect()
+            }
+        };
+        names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let cell_type = if infer == 0 || sample.is_empty() {
+                    ValueType::String
+                } else {
+                    infer_type(
+                        sample
+                            .iter()
+                            .map(move |r| r.get(idx).map(|s| s.as_str()).unwrap_or("")),
+                    )
+                };
+                ColumnType::new(name, cell_type)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        cfg.columns
+            .iter()
+            .map(|(k, v)| ColumnType::new(k, v.clone()))
+            .collect::<Vec<_>>()
+    };
 
-        if let Some(trim) = trim {
-            split = split.iter().map(|s| s.trim_matches(trim)).collect();
+    let output = context.output.initialize(columns.clone())?;
+
+    let mut sample = sample.into_iter();
+    loop {
+        let record = match sample.next() {
+            Some(record) => Some(record),
+            None => next()?,
+        };
+        let record = match record {
+            Some(record) => record,
+            None => break,
+        };
+        if record.len() != columns.len() {
+            return error(format!(
+                "csv: Wrong number of columns in CSV file, expected {}, found {}",
+                columns.len(),
+                record.len()
+            ));
         }
 
-        match split
+        let cells = record
             .iter()
             .zip(columns.iter())
-            .map(|(s, t)| t.cell_type.parse(*s))
-            .collect::<Result<Vec<Value>, CrushError>>()
-        {
-            Ok(cells) => {
-                let _ = output.send(Row::new(cells));
+            .map(|(s, t)| t.cell_type.parse(s))
+            .collect::<Result<Vec<Value>, CrushError>>()?;
+        output.send(Row::new(cells))?;
+    }
+    Ok(())
+}
+
+/// Format a value as a CSV field, quoting it if needed.
+fn to_field(value: Value, separator: char) -> String {
+    let s = match value {
+        Value::Empty() => String::new(),
+        v => v.to_string(),
+    };
+    if s.contains(&[separator, '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+fn to_record(fields: impl Iterator<Item = String>, separator: char) -> String {
+    let fields = fields.collect::<Vec<_>>();
+    // A record with a single empty field is quoted, since an empty line would be skipped
+    let mut res = if fields.len() == 1 && fields[0].is_empty() {
+        "\"\"".to_string()
+    } else {
+        fields.join(&separator.to_string())
+    };
+    res.push('\n');
+    res
+}
+
+#[signature(
+    to,
+    can_block = true,
+    example = "ps | csv:to ./processes.tsv separator=\"\\t\"",
+    short = "Serialize a table or table stream to CSV format",
+    long = "Fields containing the separator, quotes or newlines are quoted as described in
+    RFC 4180."
+)]
+struct To {
+    #[unnamed()]
+    #[description("destination. If unspecified, will write to io as a binary_stream.")]
+    file: Files,
+    #[description("column separator.")]
+    #[default(',')]
+    separator: char,
+    #[default(true)]
+    #[description("write the names of the columns as the first row.")]
+    header: bool,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let separator = cfg.separator;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let mut out = cfg.file.writer(context.output)?;
+            if cfg.header {
+                let header = to_record(
+                    input
+                        .types()
+                        .iter()
+                        .map(|t| to_field(Value::string(&t.name), separator)),
+                    separator,
+                );
+                to_crush_error(out.write_all(header.as_bytes()))?;
             }
-            Err(err) => {
-                return Err(err);
+            while let Ok(row) = input.read() {
+                let record = to_record(
+                    row.into_vec().drain(..).map(|v| to_field(v, separator)),
+                    separator,
+                );
+                to_crush_error(out.write_all(record.as_bytes()))?;
             }
+            out.finish()
         }
+        None => argument_error("Expected a table or a table stream"),
     }
-    Ok(())
 }
 
 pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
@@ -103,8 +331,58 @@ pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
         "csv",
         Box::new(move |env| {
             From::declare(env)?;
+            To::declare(env)?;
             Ok(())
         }),
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(data: &str) -> Vec<Vec<String>> {
+        let mut reader = CsvReader::new(data.as_bytes(), ',');
+        let mut res = Vec::new();
+        while let Some(record) = reader.next().unwrap() {
+            res.push(record);
+        }
+        res
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(
+            records("a,\"b,c\",\"say \"\"hi\"\"\"\r\n\n\"multi\nline\",,x\n"),
+            vec![
+                vec!["a", "b,c", "say \"hi\""],
+                vec!["multi\nline", "", "x"],
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_lines_and_empty_fields() {
+        assert_eq!(
+            records("a\n\n\"\"\n\r\nb\n"),
+            vec![vec!["a"], vec![""], vec!["b"]]
+        );
+        assert_eq!(to_record(vec![String::new()].into_iter(), ','), "\"\"\n");
+        assert_eq!(to_record(vec![String::new(); 2].into_iter(), ','), ",\n");
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let mut reader = CsvReader::new("a,\"b\nc".as_bytes(), ',');
+        assert!(reader.next().is_err());
+    }
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        assert_eq!(to_field(Value::string("a,b"), ','), "\"a,b\"");
+        assert_eq!(to_field(Value::string("a,b"), '\t'), "a,b");
+        assert_eq!(to_field(Value::string("say \"hi\""), ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(to_field(Value::Integer(3), ','), "3");
+    }
+}
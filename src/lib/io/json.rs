@@ -1,6 +1,6 @@
 use crate::lang::execution_context::CommandContext;
 use crate::{
-    lang::errors::CrushError,
+    lang::errors::{CrushError, CrushErrorType},
     lang::{table::Row, value::Value, value::ValueType},
 };
 use std::io::{BufReader, Write};
@@ -16,7 +16,44 @@ use signature::signature;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 
-fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
+/// Convert a deserialized array to a value. Arrays of structs with identical signatures
+/// become tables, all other arrays become lists.
+pub fn list_or_table(mut lst: Vec<Value>) -> CrushResult<Value> {
+    let types: HashSet<ValueType> = lst.iter().map(|v| v.value_type()).collect();
+    let struct_types: HashSet<Vec<ColumnType>> = lst
+        .iter()
+        .flat_map(|v| match v {
+            Value::Struct(r) => vec![r.local_signature()],
+            _ => vec![],
+        })
+        .collect();
+
+    match types.len() {
+        0 => Ok(Value::Empty()),
+        1 => {
+            let list_type = types.iter().next().unwrap();
+            match (list_type, struct_types.len()) {
+                (ValueType::Struct, 1) => {
+                    let row_list = lst
+                        .drain(..)
+                        .map(|v| match v {
+                            Value::Struct(r) => Ok(r.to_row()),
+                            _ => error("Impossible!"),
+                        })
+                        .collect::<CrushResult<Vec<Row>>>()?;
+                    Ok(Value::Table(Table::new(
+                        struct_types.iter().next().unwrap().clone(),
+                        row_list,
+                    )))
+                }
+                _ => Ok(Value::List(List::new(list_type.clone(), lst))),
+            }
+        }
+        _ => Ok(Value::List(List::new(ValueType::Any, lst))),
+    }
+}
+
+pub fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     match json_value {
         serde_json::Value::Null => Ok(Value::Empty()),
         serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
@@ -26,50 +63,17 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
             } else if f.is_i64() {
                 Ok(Value::Integer(f.as_i64().expect("") as i128))
             } else {
-                Ok(Value::Float(f.as_f64().ok_or(CrushError::InvalidData(
-                    "Not a valid number".to_string(),
+                Ok(Value::Float(f.as_f64().ok_or(CrushError::new(
+                    CrushErrorType::InvalidData("Not a valid number".to_string()),
                 ))?))
             }
         }
         serde_json::Value::String(s) => Ok(Value::string(s.as_str())),
-        serde_json::Value::Array(arr) => {
-            let mut lst = arr
-                .iter()
+        serde_json::Value::Array(arr) => list_or_table(
+            arr.iter()
                 .map(|v| from_json(v))
-                .collect::<CrushResult<Vec<Value>>>()?;
-            let types: HashSet<ValueType> = lst.iter().map(|v| v.value_type()).collect();
-            let struct_types: HashSet<Vec<ColumnType>> = lst
-                .iter()
-                .flat_map(|v| match v {
-                    Value::Struct(r) => vec![r.local_signature()],
-                    _ => vec![],
-                })
-                .collect();
-
-            match types.len() {
-                0 => Ok(Value::Empty()),
-                1 => {
-                    let list_type = types.iter().next().unwrap();
-                    match (list_type, struct_types.len()) {
-                        (ValueType::Struct, 1) => {
-                            let row_list = lst
-                                .drain(..)
-                                .map(|v| match v {
-                                    Value::Struct(r) => Ok(r.to_row()),
-                                    _ => error("Impossible!"),
-                                })
-                                .collect::<CrushResult<Vec<Row>>>()?;
-                            Ok(Value::Table(Table::new(
-                                struct_types.iter().next().unwrap().clone(),
-                                row_list,
-                            )))
-                        }
-                        _ => Ok(Value::List(List::new(list_type.clone(), lst))),
-                    }
-                }
-                _ => Ok(Value::List(List::new(ValueType::Any, lst))),
-            }
-        }
+                .collect::<CrushResult<Vec<Value>>>()?,
+        ),
         serde_json::Value::Object(o) => Ok(Value::Struct(Struct::new(
             o.iter()
                 .map(|(k, v)| (k.to_string(), from_json(v)))
@@ -83,8 +87,10 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     }
 }
 
-fn to_json(value: Value) -> CrushResult<serde_json::Value> {
+pub fn to_json(value: Value) -> CrushResult<serde_json::Value> {
     match value.materialize() {
+        Value::Empty() => Ok(serde_json::Value::Null),
+
         Value::File(s) => Ok(serde_json::Value::from(mandate(
             s.to_str(),
             "Invalid filename",
@@ -174,7 +180,7 @@ fn to(context: CommandContext) -> CrushResult<()> {
     let value = context.input.recv()?;
     let json_value = to_json(value)?;
     to_crush_error(writer.write(json_value.to_string().as_bytes()))?;
-    Ok(())
+    writer.finish()
 }
 
 pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
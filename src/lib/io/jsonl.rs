@@ -0,0 +1,154 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::errors::{argument_error, data_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::OutputStream;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+use std::io::{BufRead, BufReader, Write};
+
+use super::json::{from_json, to_json};
+
+/// Convert a value to the type of its column, if possible. Missing members and nulls are
+/// empty in columns of every type.
+fn to_cell(column: &ColumnType, value: Value, line: usize) -> CrushResult<Value> {
+    if matches!(value, Value::Empty()) || column.cell_type.is(&value) {
+        return Ok(value);
+    }
+    match (&column.cell_type, value) {
+        (ValueType::Float, Value::Integer(i)) => Ok(Value::Float(i as f64)),
+        (t, Value::String(s)) => t.parse(&s),
+        (t, value) => data_error(format!(
+            "jsonl: Line {}: expected {} in column {}, found {}",
+            line,
+            t,
+            column.name,
+            value.value_type()
+        )),
+    }
+}
+
+#[signature(
+from,
+can_block = true,
+output = Unknown,
+short = "Parse JSON Lines, one JSON object per line",
+example = "jsonl:from ./events.jsonl | where {level == \"error\"}",
+long = "Rows are emitted as soon as each line has been read, so this command can be used on
+    very large files and on streams that never end.
+
+    The column names and types can be given explicitly as named arguments, e.g.
+    `time=string latency=float`. Strings are parsed as the type of their column, and any other
+    value that doesn't match the type of its column is an error. Otherwise, the columns are the
+    members of the object on the first line, sorted by name, and are of type any. Members that
+    are not columns are ignored, and missing members are empty.")]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[named()]
+    #[description("name and type of all columns.")]
+    columns: OrderedStringMap<ValueType>,
+}
+
+fn from(context: CommandContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut columns = cfg
+        .columns
+        .iter()
+        .map(|(k, v)| ColumnType::new(k, v.clone()))
+        .collect::<Vec<_>>();
+    let mut output: Option<OutputStream> = None;
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = to_crush_error(line)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let object = match to_crush_error(serde_json::from_str(&line))? {
+            serde_json::Value::Object(object) => object,
+            _ => return data_error(format!("jsonl: Line {}: expected an object", idx + 1)),
+        };
+        let out = match &output {
+            Some(out) => out,
+            None => {
+                if columns.is_empty() {
+                    for name in object.keys() {
+                        columns.push(ColumnType::new(name, ValueType::Any));
+                    }
+                }
+                output = Some(context.output.initialize(columns.clone())?);
+                output.as_ref().unwrap()
+            }
+        };
+        let cells = columns
+            .iter()
+            .map(|column| {
+                let value = match object.get(&column.name) {
+                    Some(value) => from_json(value)?,
+                    None => Value::Empty(),
+                };
+                to_cell(column, value, idx + 1)
+            })
+            .collect::<CrushResult<Vec<_>>>()?;
+        out.send(Row::new(cells))?;
+    }
+
+    if output.is_none() {
+        context.output.initialize(columns)?;
+    }
+    Ok(())
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize a table or table stream to JSON Lines",
+example = "journal --follow | jsonl:to ./journal.jsonl",
+long = "Every row is written as soon as it is read, as a JSON object with one member per
+    column.")]
+struct To {
+    #[unnamed()]
+    #[description("destination. If unspecified, will write to io as a binary_stream.")]
+    file: Files,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let mut input = match context.input.recv()?.stream() {
+        Some(input) => input,
+        None => return argument_error("Expected a table or a table stream"),
+    };
+    let mut writer = cfg.file.writer(context.output)?;
+    let types = input.types().to_vec();
+    while let Ok(row) = input.read() {
+        let mut object = serde_json::Map::new();
+        for (column, value) in types.iter().zip(row.into_vec()) {
+            object.insert(column.name.clone(), to_json(value)?);
+        }
+        let mut line = serde_json::Value::Object(object).to_string();
+        line.push('\n');
+        to_crush_error(writer.write_all(line.as_bytes()))?;
+    }
+    writer.finish()
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "jsonl",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
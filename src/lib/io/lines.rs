@@ -7,7 +7,7 @@ use crate::lang::{
     value::ValueType,
 };
 use signature::signature;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 
 #[signature(
     from,
@@ -82,7 +82,7 @@ pub fn to(context: CommandContext) -> CrushResult<()> {
                     }
                 }
             }
-            Ok(())
+            out.finish()
         }
         None => argument_error("Expected a stream"),
     }
@@ -8,15 +8,22 @@ use crate::lang::value::{Field, ValueType};
 use crate::lang::{execution_context::CommandContext, value::Value};
 use signature::signature;
 
+mod archive;
+mod avro;
 mod bin;
+mod compress;
 mod csv;
 mod http;
 mod json;
+mod jsonl;
 mod lines;
+mod pbuf;
 mod pup;
 mod split;
 mod toml;
 mod words;
+mod xml;
+mod yaml;
 
 #[signature(val,
 can_block = false,
@@ -101,14 +108,21 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_namespace(
         "io",
         Box::new(move |env| {
+            archive::declare(env)?;
+            avro::declare(env)?;
             bin::declare(env)?;
+            compress::declare(env)?;
             csv::declare(env)?;
             pup::declare(env)?;
             toml::declare(env)?;
             json::declare(env)?;
+            jsonl::declare(env)?;
             lines::declare(env)?;
+            pbuf::declare(env)?;
             split::declare(env)?;
             words::declare(env)?;
+            xml::declare(env)?;
+            yaml::declare(env)?;
 
             http::Http::declare(env)?;
             Echo::declare(env)?;
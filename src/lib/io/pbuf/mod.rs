@@ -0,0 +1,657 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, data_error, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row, Table};
+use crate::lang::value::{Value, ValueType};
+use schema::{Field, FieldType, Schema};
+use signature::signature;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use wire::Input;
+
+mod schema;
+mod wire;
+
+/// Converts between encoded protocol buffer messages and Crush values, using the message
+/// definitions of a schema.
+struct Codec<'a> {
+    schema: &'a Schema,
+    /// The column types of every message type decoded so far.
+    columns: RefCell<HashMap<String, Vec<ColumnType>>>,
+}
+
+fn wire_type(field_type: &FieldType) -> u64 {
+    match field_type {
+        FieldType::Double | FieldType::Fixed64 | FieldType::Sfixed64 => wire::FIXED64,
+        FieldType::Float | FieldType::Fixed32 | FieldType::Sfixed32 => wire::FIXED32,
+        FieldType::String | FieldType::Bytes | FieldType::Message(_) | FieldType::Map(_, _) => {
+            wire::LENGTH_DELIMITED
+        }
+        _ => wire::VARINT,
+    }
+}
+
+fn integer<T: TryFrom<i128>>(value: &Value) -> CrushResult<T> {
+    match value {
+        Value::Integer(i) => match T::try_from(*i) {
+            Ok(v) => Ok(v),
+            Err(_) => data_error(format!("Integer {} is out of range", i)),
+        },
+        v => data_error(format!("Expected an integer, got a {}", v.value_type())),
+    }
+}
+
+fn float(value: &Value) -> CrushResult<f64> {
+    match value {
+        Value::Float(f) => Ok(*f),
+        Value::Integer(i) => Ok(*i as f64),
+        v => data_error(format!("Expected a float, got a {}", v.value_type())),
+    }
+}
+
+impl<'a> Codec<'a> {
+    fn new(schema: &'a Schema) -> Codec<'a> {
+        Codec {
+            schema,
+            columns: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn value_type(&self, field_type: &FieldType) -> CrushResult<ValueType> {
+        Ok(match field_type {
+            FieldType::Double | FieldType::Float => ValueType::Float,
+            FieldType::Bool => ValueType::Bool,
+            FieldType::String | FieldType::Enum(_) => ValueType::String,
+            FieldType::Bytes => ValueType::Binary,
+            FieldType::Message(_) => ValueType::Struct,
+            FieldType::Map(key, value) => ValueType::Dict(
+                Box::from(self.value_type(key)?),
+                Box::from(self.value_type(value)?),
+            ),
+            FieldType::Named(name) => return data_error(format!("Unresolved type {}", name)),
+            _ => ValueType::Integer,
+        })
+    }
+
+    fn field_value_type(
+        &self,
+        field: &Field,
+        visiting: &mut Vec<String>,
+    ) -> CrushResult<ValueType> {
+        match (&field.field_type, field.repeated) {
+            (FieldType::Message(name), true) => {
+                if visiting.contains(name) {
+                    // Recursive messages have no finite table type
+                    Ok(ValueType::Any)
+                } else {
+                    Ok(ValueType::Table(self.columns_inner(name, visiting)?))
+                }
+            }
+            // Fields that track presence are empty when missing
+            (_, false) if field.presence => Ok(ValueType::Any),
+            (FieldType::Map(_, _), _) | (_, false) => self.value_type(&field.field_type),
+            (t, true) => Ok(ValueType::List(Box::from(self.value_type(t)?))),
+        }
+    }
+
+    /// The column types of the rows that a message is decoded into.
+    fn columns(&self, message: &str) -> CrushResult<Vec<ColumnType>> {
+        if let Some(columns) = self.columns.borrow().get(message) {
+            return Ok(columns.clone());
+        }
+        let columns = self.columns_inner(message, &mut Vec::new())?;
+        self.columns
+            .borrow_mut()
+            .insert(message.to_string(), columns.clone());
+        Ok(columns)
+    }
+
+    fn columns_inner(
+        &self,
+        message: &str,
+        visiting: &mut Vec<String>,
+    ) -> CrushResult<Vec<ColumnType>> {
+        visiting.push(message.to_string());
+        let res = self
+            .schema
+            .message(message)?
+            .fields
+            .iter()
+            .map(|f| {
+                Ok(ColumnType::new(
+                    &f.name,
+                    self.field_value_type(f, visiting)?,
+                ))
+            })
+            .collect::<CrushResult<Vec<_>>>();
+        visiting.pop();
+        res
+    }
+
+    fn default(&self, field_type: &FieldType) -> CrushResult<Value> {
+        Ok(match field_type {
+            FieldType::Double | FieldType::Float => Value::Float(0.0),
+            FieldType::Bool => Value::Bool(false),
+            FieldType::String => Value::string(""),
+            FieldType::Bytes => Value::Binary(vec![]),
+            FieldType::Enum(name) => match self.schema.enumeration(name)?.values.first() {
+                Some((value_name, _)) => Value::string(value_name),
+                None => Value::string("0"),
+            },
+            FieldType::Message(_) | FieldType::Map(_, _) | FieldType::Named(_) => Value::Empty(),
+            _ => Value::Integer(0),
+        })
+    }
+
+    fn decode_scalar(
+        &self,
+        field_type: &FieldType,
+        actual_wire_type: u64,
+        input: &mut Input,
+        depth: usize,
+    ) -> CrushResult<Value> {
+        if actual_wire_type != wire_type(field_type) {
+            return data_error(format!(
+                "Unexpected wire type {} for a field of type {:?}",
+                actual_wire_type, field_type
+            ));
+        }
+        Ok(match field_type {
+            FieldType::Double => Value::Float(f64::from_bits(input.fixed64()?)),
+            FieldType::Float => Value::Float(f32::from_bits(input.fixed32()?) as f64),
+            FieldType::Int32 => Value::Integer(input.varint()? as i32 as i128),
+            FieldType::Int64 => Value::Integer(input.varint()? as i64 as i128),
+            FieldType::Uint32 => Value::Integer(input.varint()? as u32 as i128),
+            FieldType::Uint64 => Value::Integer(input.varint()? as i128),
+            FieldType::Sint32 | FieldType::Sint64 => {
+                Value::Integer(wire::zigzag_decode(input.varint()?) as i128)
+            }
+            FieldType::Fixed32 => Value::Integer(input.fixed32()? as i128),
+            FieldType::Fixed64 => Value::Integer(input.fixed64()? as i128),
+            FieldType::Sfixed32 => Value::Integer(input.fixed32()? as i32 as i128),
+            FieldType::Sfixed64 => Value::Integer(input.fixed64()? as i64 as i128),
+            FieldType::Bool => Value::Bool(input.varint()? != 0),
+            FieldType::Enum(name) => {
+                let number = input.varint()? as i32 as i64;
+                match self.schema.enumeration(name)?.name(number) {
+                    Some(value_name) => Value::string(value_name),
+                    None => Value::String(number.to_string()),
+                }
+            }
+            FieldType::String => match String::from_utf8(input.length_delimited()?.to_vec()) {
+                Ok(s) => Value::String(s),
+                Err(_) => return data_error("Invalid UTF-8 in string field"),
+            },
+            FieldType::Bytes => Value::Binary(input.length_delimited()?.to_vec()),
+            FieldType::Message(name) => Value::Struct(
+                self.decode_nested(name, input.length_delimited()?, depth + 1)?
+                    .into_struct(&self.columns(name)?),
+            ),
+            FieldType::Map(_, _) | FieldType::Named(_) => {
+                return data_error(format!("Unexpected field type {:?}", field_type))
+            }
+        })
+    }
+
+    fn decode_map_entry(
+        &self,
+        key_type: &FieldType,
+        value_type: &FieldType,
+        data: &[u8],
+        depth: usize,
+    ) -> CrushResult<(Value, Value)> {
+        let mut input = Input::new(data);
+        let mut key = None;
+        let mut value = None;
+        while !input.is_empty() {
+            match input.key()? {
+                (1, t) => key = Some(self.decode_scalar(key_type, t, &mut input, depth)?),
+                (2, t) => value = Some(self.decode_scalar(value_type, t, &mut input, depth)?),
+                (number, t) => input.skip(number, t)?,
+            }
+        }
+        Ok((
+            match key {
+                Some(key) => key,
+                None => self.default(key_type)?,
+            },
+            match value {
+                Some(value) => value,
+                None => self.default(value_type)?,
+            },
+        ))
+    }
+
+    /// Decode a message into a row with one cell per field, in the order the fields are
+    /// declared.
+    fn decode(&self, message: &str, data: &[u8]) -> CrushResult<Row> {
+        self.decode_nested(message, data, 0)
+    }
+
+    fn decode_nested(&self, message: &str, data: &[u8], depth: usize) -> CrushResult<Row> {
+        if depth >= wire::MAX_DEPTH {
+            return data_error("Messages nested too deeply");
+        }
+        let fields = &self.schema.message(message)?.fields;
+        let mut values: Vec<Vec<Value>> = fields.iter().map(|_| Vec::new()).collect();
+        let mut entries: Vec<Vec<(Value, Value)>> = fields.iter().map(|_| Vec::new()).collect();
+        let mut input = Input::new(data);
+        while !input.is_empty() {
+            let (number, actual_wire_type) = input.key()?;
+            let idx = match fields.iter().position(|f| f.number == number) {
+                Some(idx) => idx,
+                None => {
+                    input.skip(number, actual_wire_type)?;
+                    continue;
+                }
+            };
+            match &fields[idx].field_type {
+                FieldType::Map(key_type, value_type) => {
+                    let data = input.length_delimited()?;
+                    entries[idx].push(self.decode_map_entry(key_type, value_type, data, depth)?);
+                }
+                t if actual_wire_type == wire::LENGTH_DELIMITED && t.is_packable() => {
+                    // Packed repeated values are accepted whether or not the field is declared
+                    // as packed
+                    let mut packed = Input::new(input.length_delimited()?);
+                    while !packed.is_empty() {
+                        values[idx].push(self.decode_scalar(
+                            t,
+                            wire_type(t),
+                            &mut packed,
+                            depth,
+                        )?);
+                    }
+                }
+                t => {
+                    values[idx].push(self.decode_scalar(t, actual_wire_type, &mut input, depth)?)
+                }
+            }
+        }
+
+        let mut cells = Vec::new();
+        for ((field, mut field_values), field_entries) in
+            fields.iter().zip(values.drain(..)).zip(entries.drain(..))
+        {
+            cells.push(match (&field.field_type, field.repeated) {
+                (FieldType::Map(key_type, value_type), _) => {
+                    let dict = Dict::new(self.value_type(key_type)?, self.value_type(value_type)?);
+                    for (k, v) in field_entries {
+                        dict.insert(k, v)?;
+                    }
+                    Value::Dict(dict)
+                }
+                (FieldType::Message(name), true) => {
+                    let columns = self.columns(name)?;
+                    Value::Table(Table::new(
+                        columns.clone(),
+                        field_values
+                            .drain(..)
+                            .map(|v| match v {
+                                Value::Struct(s) => s.to_row(),
+                                _ => Row::new(vec![]),
+                            })
+                            .collect(),
+                    ))
+                }
+                (t, true) => Value::List(List::new(self.value_type(t)?, field_values)),
+                (t, false) => match field_values.pop() {
+                    Some(value) => value,
+                    None if field.presence => Value::Empty(),
+                    None => self.default(t)?,
+                },
+            });
+        }
+        Ok(Row::new(cells))
+    }
+
+    fn encode_scalar(
+        &self,
+        field_type: &FieldType,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> CrushResult<()> {
+        match field_type {
+            FieldType::Double => out.extend_from_slice(&float(value)?.to_bits().to_le_bytes()),
+            FieldType::Float => {
+                out.extend_from_slice(&(float(value)? as f32).to_bits().to_le_bytes())
+            }
+            FieldType::Int32 => wire::write_varint(out, integer::<i32>(value)? as i64 as u64),
+            FieldType::Int64 => wire::write_varint(out, integer::<i64>(value)? as u64),
+            FieldType::Uint32 => wire::write_varint(out, integer::<u32>(value)? as u64),
+            FieldType::Uint64 => wire::write_varint(out, integer::<u64>(value)?),
+            FieldType::Sint32 => {
+                wire::write_varint(out, wire::zigzag_encode(integer::<i32>(value)? as i64))
+            }
+            FieldType::Sint64 => {
+                wire::write_varint(out, wire::zigzag_encode(integer::<i64>(value)?))
+            }
+            FieldType::Fixed32 => out.extend_from_slice(&integer::<u32>(value)?.to_le_bytes()),
+            FieldType::Fixed64 => out.extend_from_slice(&integer::<u64>(value)?.to_le_bytes()),
+            FieldType::Sfixed32 => out.extend_from_slice(&integer::<i32>(value)?.to_le_bytes()),
+            FieldType::Sfixed64 => out.extend_from_slice(&integer::<i64>(value)?.to_le_bytes()),
+            FieldType::Bool => match value {
+                Value::Bool(b) => wire::write_varint(out, *b as u64),
+                v => return data_error(format!("Expected a bool, got a {}", v.value_type())),
+            },
+            FieldType::Enum(name) => {
+                let number = match value {
+                    Value::String(s) => match self.schema.enumeration(name)?.number(s) {
+                        Some(number) => number,
+                        None => match s.parse::<i64>() {
+                            Ok(number) => number,
+                            Err(_) => {
+                                return data_error(format!("Unknown value {} for enum {}", s, name))
+                            }
+                        },
+                    },
+                    v => integer::<i32>(v)? as i64,
+                };
+                wire::write_varint(out, number as u64);
+            }
+            FieldType::String => match value {
+                Value::String(s) => wire::write_length_delimited(out, s.as_bytes()),
+                v => wire::write_length_delimited(out, v.to_string().as_bytes()),
+            },
+            FieldType::Bytes => match value {
+                Value::Binary(b) => wire::write_length_delimited(out, b),
+                Value::String(s) => wire::write_length_delimited(out, s.as_bytes()),
+                v => return data_error(format!("Expected binary data, got a {}", v.value_type())),
+            },
+            FieldType::Message(name) => match value {
+                Value::Struct(s) => {
+                    let mut message = Vec::new();
+                    self.encode(name, s, &mut message)?;
+                    wire::write_length_delimited(out, &message);
+                }
+                v => return data_error(format!("Expected a struct, got a {}", v.value_type())),
+            },
+            FieldType::Map(_, _) | FieldType::Named(_) => {
+                return data_error(format!("Unexpected field type {:?}", field_type))
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a struct as a message. Members that are missing or empty are not written.
+    fn encode(&self, message: &str, value: &Struct, out: &mut Vec<u8>) -> CrushResult<()> {
+        for field in &self.schema.message(message)?.fields {
+            let value = match value.get(&field.name) {
+                None | Some(Value::Empty()) => continue,
+                Some(value) => value.materialize(),
+            };
+            match (&field.field_type, field.repeated) {
+                (FieldType::Map(key_type, value_type), _) => {
+                    let elements = match value {
+                        Value::Dict(d) => d.elements(),
+                        v => {
+                            return data_error(format!("Expected a dict, got a {}", v.value_type()))
+                        }
+                    };
+                    for (k, v) in elements {
+                        let mut entry = Vec::new();
+                        wire::write_key(&mut entry, 1, wire_type(key_type));
+                        self.encode_scalar(key_type, &k, &mut entry)?;
+                        wire::write_key(&mut entry, 2, wire_type(value_type));
+                        self.encode_scalar(value_type, &v, &mut entry)?;
+                        wire::write_key(out, field.number, wire::LENGTH_DELIMITED);
+                        wire::write_length_delimited(out, &entry);
+                    }
+                }
+                (t, true) => {
+                    let elements = match value {
+                        Value::List(l) => l.dump(),
+                        Value::Table(table) => table
+                            .rows()
+                            .iter()
+                            .map(|r| Value::Struct(r.clone().into_struct(table.types())))
+                            .collect(),
+                        v => {
+                            return data_error(format!("Expected a list, got a {}", v.value_type()))
+                        }
+                    };
+                    if field.packed {
+                        let mut packed = Vec::new();
+                        for element in &elements {
+                            self.encode_scalar(t, element, &mut packed)?;
+                        }
+                        wire::write_key(out, field.number, wire::LENGTH_DELIMITED);
+                        wire::write_length_delimited(out, &packed);
+                    } else {
+                        for element in &elements {
+                            wire::write_key(out, field.number, wire_type(t));
+                            self.encode_scalar(t, element, out)?;
+                        }
+                    }
+                }
+                (t, false) => {
+                    wire::write_key(out, field.number, wire_type(t));
+                    self.encode_scalar(t, &value, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[signature(
+from,
+can_block = true,
+output = Unknown,
+short = "Parse protocol buffer messages using a .proto schema",
+example = "pbuf:from ./order.bin schema=./shop.proto message=\"shop.Order\"",
+long = "The schema is parsed at runtime, together with the files it imports. Messages are
+    returned as structs, repeated fields as lists, repeated message fields as tables and map
+    fields as dicts. Enum values are returned as the name of the value, bytes fields as binary
+    data. Missing fields get their default value, except for fields that track whether they
+    are set, like message fields, fields in a oneof and proto2 or proto3 optional fields, which
+    are empty.
+
+    If delimited is set, the input is a stream of messages that are each preceded by their
+    length as a varint, and the output is a table stream with one row per message.")]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[description("the .proto file describing the message.")]
+    schema: Files,
+    #[description("the full name of the message type, including its package.")]
+    message: String,
+    #[default(false)]
+    #[description("read a stream of length delimited messages.")]
+    delimited: bool,
+}
+
+fn from(context: CommandContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let schema = Schema::load(&cfg.schema.into_file()?)?;
+    let codec = Codec::new(&schema);
+    let columns = codec.columns(&cfg.message)?;
+    let mut data = Vec::new();
+    to_crush_error(cfg.files.reader(context.input)?.read_to_end(&mut data))?;
+    if cfg.delimited {
+        let output = context.output.initialize(columns)?;
+        let mut input = Input::new(&data);
+        while !input.is_empty() {
+            output.send(codec.decode(&cfg.message, input.length_delimited()?)?)?;
+        }
+        Ok(())
+    } else {
+        context.output.send(Value::Struct(
+            codec.decode(&cfg.message, &data)?.into_struct(&columns),
+        ))
+    }
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize to protocol buffer format using a .proto schema",
+example = "pbuf:from ./order.bin schema=./shop.proto message=\"shop.Order\" | pbuf:to ./copy.bin schema=./shop.proto message=\"shop.Order\"",
+long = "The input is a struct with the fields of the message as members. Members that are
+    missing or empty are not written. Enum values can be given by name or by number.
+
+    If delimited is set, the input must be a table or a table stream, and every row is written
+    as a message preceded by its length as a varint.")]
+struct To {
+    #[unnamed()]
+    #[description("destination. If unspecified, will write to io as a binary_stream.")]
+    file: Files,
+    #[description("the .proto file describing the message.")]
+    schema: Files,
+    #[description("the full name of the message type, including its package.")]
+    message: String,
+    #[default(false)]
+    #[description("write every row of the input as a length delimited message.")]
+    delimited: bool,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let schema = Schema::load(&cfg.schema.into_file()?)?;
+    let codec = Codec::new(&schema);
+    let value = context.input.recv()?;
+    let mut data = Vec::new();
+    if cfg.delimited {
+        let mut input = match value.stream() {
+            Some(input) => input,
+            None => return argument_error("Expected a table or a table stream"),
+        };
+        let types = input.types().to_vec();
+        while let Ok(row) = input.read() {
+            let mut message = Vec::new();
+            codec.encode(&cfg.message, &row.into_struct(&types), &mut message)?;
+            wire::write_length_delimited(&mut data, &message);
+        }
+    } else {
+        match value {
+            Value::Struct(s) => codec.encode(&cfg.message, &s, &mut data)?,
+            v => return argument_error(format!("Expected a struct, got a {}", v.value_type())),
+        }
+    }
+    let mut writer = cfg.file.writer(context.output)?;
+    to_crush_error(writer.write_all(&data))?;
+    writer.finish()
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "pbuf",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        syntax = "proto3";
+        package test;
+        message Item {
+            enum Kind { BOOK = 0; MUSIC = 1; }
+            string name = 1;
+            Kind kind = 2;
+            repeated sint32 ratings = 3;
+        }
+        message Order {
+            int64 id = 1;
+            repeated Item items = 2;
+            map<string, double> prices = 3;
+            Item gift = 4;
+            bytes token = 5;
+        }
+    "#;
+
+    fn schema() -> Schema {
+        Schema::from_source(SCHEMA).unwrap()
+    }
+
+    #[test]
+    fn decode_known_bytes() {
+        let schema = schema();
+        let codec = Codec::new(&schema);
+        // name="a", kind=MUSIC, ratings=[-1, 2] (packed)
+        let data = [0x0a, 0x01, b'a', 0x10, 0x01, 0x1a, 0x02, 0x01, 0x04];
+        let row = codec.decode("test.Item", &data).unwrap();
+        let s = row.into_struct(&codec.columns("test.Item").unwrap());
+        assert!(s.get("name") == Some(Value::string("a")));
+        assert!(s.get("kind") == Some(Value::string("MUSIC")));
+        match s.get("ratings") {
+            Some(Value::List(l)) => {
+                assert!(l.dump() == vec![Value::Integer(-1), Value::Integer(2)])
+            }
+            _ => panic!("Expected a list of ratings"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let schema = schema();
+        let codec = Codec::new(&schema);
+        let item = |name: &str| {
+            Struct::new(
+                vec![
+                    ("name".to_string(), Value::string(name)),
+                    ("kind".to_string(), Value::string("MUSIC")),
+                ],
+                None,
+            )
+        };
+        let prices = Dict::new(ValueType::String, ValueType::Float);
+        prices
+            .insert(Value::string("a"), Value::Float(1.5))
+            .unwrap();
+        let order = Struct::new(
+            vec![
+                ("id".to_string(), Value::Integer(-7)),
+                (
+                    "items".to_string(),
+                    Value::List(List::new(
+                        ValueType::Struct,
+                        vec![Value::Struct(item("a")), Value::Struct(item("b"))],
+                    )),
+                ),
+                ("prices".to_string(), Value::Dict(prices)),
+                ("token".to_string(), Value::Binary(vec![1, 2, 3])),
+            ],
+            None,
+        );
+        let mut data = Vec::new();
+        codec.encode("test.Order", &order, &mut data).unwrap();
+        let decoded = codec
+            .decode("test.Order", &data)
+            .unwrap()
+            .into_struct(&codec.columns("test.Order").unwrap());
+        assert!(decoded.get("id") == Some(Value::Integer(-7)));
+        assert!(matches!(decoded.get("gift"), Some(Value::Empty())));
+        assert!(decoded.get("token") == Some(Value::Binary(vec![1, 2, 3])));
+        match decoded.get("items") {
+            Some(Value::Table(t)) => {
+                assert_eq!(t.rows().len(), 2);
+                assert!(t.rows()[1].cells()[0] == Value::string("b"));
+            }
+            _ => panic!("Expected a table of items"),
+        }
+        match decoded.get("prices") {
+            Some(Value::Dict(d)) => assert!(d.get(&Value::string("a")) == Some(Value::Float(1.5))),
+            _ => panic!("Expected a dict of prices"),
+        }
+    }
+}
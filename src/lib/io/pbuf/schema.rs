@@ -0,0 +1,625 @@
+use crate::lang::errors::{argument_error, data_error, error, to_crush_error, CrushResult};
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    /// The full name of an enum.
+    Enum(String),
+    /// The full name of a message.
+    Message(String),
+    Map(Box<FieldType>, Box<FieldType>),
+    /// A reference to a message or enum that has not been resolved yet.
+    Named(String),
+}
+
+impl FieldType {
+    fn scalar(name: &str) -> Option<FieldType> {
+        Some(match name {
+            "double" => FieldType::Double,
+            "float" => FieldType::Float,
+            "int32" => FieldType::Int32,
+            "int64" => FieldType::Int64,
+            "uint32" => FieldType::Uint32,
+            "uint64" => FieldType::Uint64,
+            "sint32" => FieldType::Sint32,
+            "sint64" => FieldType::Sint64,
+            "fixed32" => FieldType::Fixed32,
+            "fixed64" => FieldType::Fixed64,
+            "sfixed32" => FieldType::Sfixed32,
+            "sfixed64" => FieldType::Sfixed64,
+            "bool" => FieldType::Bool,
+            "string" => FieldType::String,
+            "bytes" => FieldType::Bytes,
+            _ => return None,
+        })
+    }
+
+    /// Scalar numeric types can use the packed encoding when repeated.
+    pub fn is_packable(&self) -> bool {
+        !matches!(
+            self,
+            FieldType::String
+                | FieldType::Bytes
+                | FieldType::Message(_)
+                | FieldType::Map(_, _)
+                | FieldType::Named(_)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub number: u64,
+    pub field_type: FieldType,
+    pub repeated: bool,
+    /// Whether the field tracks if it has been set, as opposed to having a default value.
+    pub presence: bool,
+    pub packed: bool,
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+pub struct Enum {
+    pub values: Vec<(String, i64)>,
+}
+
+impl Enum {
+    pub fn name(&self, number: i64) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(_, n)| *n == number)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn number(&self, name: &str) -> Option<i64> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, n)| *n)
+    }
+}
+
+/// The messages and enums of a set of .proto files, indexed by their full names, without a
+/// leading dot.
+#[derive(Debug, Default)]
+pub struct Schema {
+    messages: HashMap<String, Message>,
+    enums: HashMap<String, Enum>,
+}
+
+impl Schema {
+    /// Parse a .proto file and the files it imports. Imports are looked up relative to the
+    /// directory of the importing file, and imports that can't be found are ignored, unless
+    /// a type from them is used.
+    pub fn load(file: &Path) -> CrushResult<Schema> {
+        let mut schema = Schema::default();
+        let mut loaded = Vec::new();
+        schema.load_file(file, true, &mut loaded)?;
+        schema.resolve()?;
+        Ok(schema)
+    }
+
+    /// Parse the source of a single .proto file that imports nothing.
+    #[cfg(test)]
+    pub fn from_source(text: &str) -> CrushResult<Schema> {
+        let mut schema = Schema::default();
+        schema.parse(text)?;
+        schema.resolve()?;
+        Ok(schema)
+    }
+
+    fn load_file(
+        &mut self,
+        file: &Path,
+        required: bool,
+        loaded: &mut Vec<PathBuf>,
+    ) -> CrushResult<()> {
+        if loaded.iter().any(|f| f == file) {
+            return Ok(());
+        }
+        loaded.push(file.to_path_buf());
+        let text = match read_to_string(file) {
+            Ok(text) => text,
+            Err(_) if !required => return Ok(()),
+            Err(e) => return to_crush_error(Err(e)),
+        };
+        let imports = match self.parse(&text) {
+            Ok(imports) => imports,
+            Err(e) => return data_error(format!("{}: {}", file.to_string_lossy(), e.message())),
+        };
+        let dir = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        for import in imports {
+            self.load_file(&dir.join(import), false, loaded)?;
+        }
+        Ok(())
+    }
+
+    /// Parse the source of a .proto file, returning the files that it imports.
+    fn parse(&mut self, text: &str) -> CrushResult<Vec<String>> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            last_packed: None,
+        };
+        let mut imports = Vec::new();
+        let mut package = String::new();
+        let mut proto3 = false;
+        while let Some(token) = parser.next() {
+            match token.as_str() {
+                "syntax" => {
+                    parser.expect("=")?;
+                    proto3 = parser.string()? == "proto3";
+                    parser.expect(";")?;
+                }
+                "package" => {
+                    package = parser.identifier()?;
+                    parser.expect(";")?;
+                }
+                "import" => {
+                    if matches!(parser.peek(), Some("public") | Some("weak")) {
+                        parser.next();
+                    }
+                    imports.push(parser.string()?);
+                    parser.expect(";")?;
+                }
+                "option" => parser.skip_statement()?,
+                "message" => self.parse_message(&mut parser, &package, proto3)?,
+                "enum" => self.parse_enum(&mut parser, &package)?,
+                "service" | "extend" => {
+                    parser.identifier()?;
+                    parser.skip_block()?;
+                }
+                ";" => {}
+                t => return data_error(format!("Unexpected token {}", t)),
+            }
+        }
+        Ok(imports)
+    }
+
+    fn parse_message(&mut self, parser: &mut Parser, scope: &str, proto3: bool) -> CrushResult<()> {
+        let name = qualify(scope, &parser.identifier()?);
+        parser.expect("{")?;
+        let mut fields = Vec::new();
+        self.parse_message_body(parser, &name, proto3, None, &mut fields)?;
+        self.messages.insert(name, Message { fields });
+        Ok(())
+    }
+
+    fn parse_message_body(
+        &mut self,
+        parser: &mut Parser,
+        name: &str,
+        proto3: bool,
+        oneof: Option<&str>,
+        fields: &mut Vec<Field>,
+    ) -> CrushResult<()> {
+        loop {
+            let token = match parser.next() {
+                Some(token) => token,
+                None => return data_error("Unexpected end of file"),
+            };
+            match token.as_str() {
+                "}" => return Ok(()),
+                ";" => {}
+                "message" => self.parse_message(parser, name, proto3)?,
+                "enum" => self.parse_enum(parser, name)?,
+                "option" | "reserved" | "extensions" => parser.skip_statement()?,
+                "extend" => {
+                    parser.identifier()?;
+                    parser.skip_block()?;
+                }
+                "oneof" => {
+                    let oneof_name = parser.identifier()?;
+                    parser.expect("{")?;
+                    self.parse_message_body(parser, name, proto3, Some(&oneof_name), fields)?;
+                }
+                "map" => {
+                    parser.expect("<")?;
+                    let key = parser.identifier()?;
+                    parser.expect(",")?;
+                    let value = parser.identifier()?;
+                    parser.expect(">")?;
+                    let key_type = match FieldType::scalar(&key) {
+                        Some(t) => t,
+                        None => return data_error(format!("Invalid map key type {}", key)),
+                    };
+                    let value_type = FieldType::scalar(&value).unwrap_or(FieldType::Named(value));
+                    let mut field = parser
+                        .field_rest(FieldType::Map(Box::from(key_type), Box::from(value_type)))?;
+                    field.repeated = true;
+                    field.packed = false;
+                    fields.push(field);
+                }
+                label => {
+                    let (label, type_name) = match label {
+                        "repeated" | "optional" | "required" => {
+                            (Some(label.to_string()), parser.identifier()?)
+                        }
+                        _ => (None, token.clone()),
+                    };
+                    let field_type =
+                        FieldType::scalar(&type_name).unwrap_or(FieldType::Named(type_name));
+                    let mut field = parser.field_rest(field_type)?;
+                    field.repeated = label.as_deref() == Some("repeated");
+                    field.presence = oneof.is_some()
+                        || (proto3 && label.as_deref() == Some("optional"))
+                        || (!proto3 && !field.repeated);
+                    // Repeated scalars are packed by default in proto3. Whether the type is
+                    // a scalar is only known once named types are resolved.
+                    field.packed = field.repeated && parser.last_packed.unwrap_or(proto3);
+                    fields.push(field);
+                }
+            }
+        }
+    }
+
+    fn parse_enum(&mut self, parser: &mut Parser, scope: &str) -> CrushResult<()> {
+        let name = qualify(scope, &parser.identifier()?);
+        parser.expect("{")?;
+        let mut values = Vec::new();
+        loop {
+            let token = match parser.next() {
+                Some(token) => token,
+                None => return data_error("Unexpected end of file"),
+            };
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" | "reserved" => parser.skip_statement()?,
+                value_name => {
+                    parser.expect("=")?;
+                    let number = parser.integer()?;
+                    parser.skip_options()?;
+                    parser.expect(";")?;
+                    values.push((value_name.to_string(), number));
+                }
+            }
+        }
+        self.enums.insert(name, Enum { values });
+        Ok(())
+    }
+
+    /// Replace all named types with references to messages and enums.
+    fn resolve(&mut self) -> CrushResult<()> {
+        let names = self.messages.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            let mut fields = self.messages[&name].fields.clone();
+            for field in &mut fields {
+                field.field_type = self.resolve_type(&field.field_type, &name)?;
+                field.packed = field.packed && field.field_type.is_packable();
+                if let FieldType::Message(_) = field.field_type {
+                    field.presence = !field.repeated;
+                }
+            }
+            self.messages.get_mut(&name).unwrap().fields = fields;
+        }
+        Ok(())
+    }
+
+    fn resolve_type(&self, field_type: &FieldType, scope: &str) -> CrushResult<FieldType> {
+        match field_type {
+            FieldType::Named(name) => {
+                if let Some(full) = name.strip_prefix('.') {
+                    return self.named(full, name);
+                }
+                // Search the scope of the message and all enclosing scopes
+                let mut scope = scope.to_string();
+                loop {
+                    let candidate = qualify(&scope, name);
+                    if self.messages.contains_key(&candidate) || self.enums.contains_key(&candidate)
+                    {
+                        return self.named(&candidate, name);
+                    }
+                    if scope.is_empty() {
+                        return data_error(format!("Unknown type {}", name));
+                    }
+                    scope = match scope.rfind('.') {
+                        Some(idx) => scope[..idx].to_string(),
+                        None => String::new(),
+                    };
+                }
+            }
+            FieldType::Map(key, value) => Ok(FieldType::Map(
+                key.clone(),
+                Box::from(self.resolve_type(value, scope)?),
+            )),
+            t => Ok(t.clone()),
+        }
+    }
+
+    fn named(&self, full: &str, name: &str) -> CrushResult<FieldType> {
+        if self.messages.contains_key(full) {
+            Ok(FieldType::Message(full.to_string()))
+        } else if self.enums.contains_key(full) {
+            Ok(FieldType::Enum(full.to_string()))
+        } else {
+            data_error(format!("Unknown type {}", name))
+        }
+    }
+
+    pub fn message(&self, name: &str) -> CrushResult<&Message> {
+        match self.messages.get(name.trim_start_matches('.')) {
+            Some(message) => Ok(message),
+            None => argument_error(format!("Unknown message {}", name)),
+        }
+    }
+
+    pub fn enumeration(&self, name: &str) -> CrushResult<&Enum> {
+        match self.enums.get(name) {
+            Some(e) => Ok(e),
+            None => error(format!("Unknown enum {}", name)),
+        }
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn tokenize(text: &str) -> CrushResult<Vec<String>> {
+    let mut tokens = Vec::new();
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '/' && chars.get(pos + 1) == Some(&'/') {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+        } else if c == '/' && chars.get(pos + 1) == Some(&'*') {
+            pos += 2;
+            while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/')) {
+                pos += 1;
+            }
+            pos += 2;
+        } else if c == '"' || c == '\'' {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && chars[pos] != c {
+                if chars[pos] == '\\' {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            if pos >= chars.len() {
+                return data_error("Unterminated string");
+            }
+            pos += 1;
+            tokens.push(chars[start..pos].iter().collect());
+        } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '+' {
+            let start = pos;
+            while pos < chars.len()
+                && (chars[pos].is_alphanumeric() || chars[pos] == '_' || chars[pos] == '.')
+                || (pos == start && (c == '-' || c == '+'))
+            {
+                pos += 1;
+            }
+            tokens.push(chars[start..pos].iter().collect());
+        } else {
+            tokens.push(c.to_string());
+            pos += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    /// The value of the packed option of the last field parsed, if it was given.
+    last_packed: Option<bool>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<String> {
+        let res = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        res
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> CrushResult<()> {
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            Some(t) => data_error(format!("Expected {}, found {}", expected, t)),
+            None => data_error(format!("Expected {}, found end of file", expected)),
+        }
+    }
+
+    fn identifier(&mut self) -> CrushResult<String> {
+        match self.next() {
+            Some(t) if t.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '.') => Ok(t),
+            Some(t) => data_error(format!("Expected an identifier, found {}", t)),
+            None => data_error("Expected an identifier, found end of file"),
+        }
+    }
+
+    fn string(&mut self) -> CrushResult<String> {
+        match self.next() {
+            Some(t) if t.starts_with('"') || t.starts_with('\'') => {
+                Ok(t[1..t.len() - 1].to_string())
+            }
+            Some(t) => data_error(format!("Expected a string, found {}", t)),
+            None => data_error("Expected a string, found end of file"),
+        }
+    }
+
+    fn integer(&mut self) -> CrushResult<i64> {
+        let token = match self.next() {
+            Some(t) => t,
+            None => return data_error("Expected a number, found end of file"),
+        };
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token.trim_start_matches('+')),
+        };
+        let value = if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            i64::from_str_radix(hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            i64::from_str_radix(&digits[1..], 8)
+        } else {
+            digits.parse::<i64>()
+        };
+        match value {
+            Ok(v) if negative => Ok(-v),
+            Ok(v) => Ok(v),
+            Err(_) => data_error(format!("Expected a number, found {}", token)),
+        }
+    }
+
+    /// Parse the rest of a field, after its type: `name = number [options];`
+    fn field_rest(&mut self, field_type: FieldType) -> CrushResult<Field> {
+        let name = self.identifier()?;
+        self.expect("=")?;
+        let number = self.integer()?;
+        if number <= 0 {
+            return data_error(format!("Invalid field number {}", number));
+        }
+        self.last_packed = None;
+        self.skip_options()?;
+        self.expect(";")?;
+        Ok(Field {
+            name,
+            number: number as u64,
+            field_type,
+            repeated: false,
+            presence: false,
+            packed: self.last_packed.unwrap_or(false),
+        })
+    }
+
+    /// Skip options in brackets, remembering the value of the packed option.
+    fn skip_options(&mut self) -> CrushResult<()> {
+        if self.peek() != Some("[") {
+            return Ok(());
+        }
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "]" => return Ok(()),
+                "packed" => {
+                    self.expect("=")?;
+                    self.last_packed = Some(self.next().as_deref() == Some("true"));
+                }
+                _ => {}
+            }
+        }
+        data_error("Unterminated options")
+    }
+
+    fn skip_statement(&mut self) -> CrushResult<()> {
+        let mut depth = 0;
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                ";" if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+        data_error("Unexpected end of file")
+    }
+
+    fn skip_block(&mut self) -> CrushResult<()> {
+        self.expect("{")?;
+        let mut depth = 1;
+        while let Some(token) = self.next() {
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+        data_error("Unexpected end of file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Schema {
+        Schema::from_source(text).unwrap()
+    }
+
+    #[test]
+    fn messages_and_enums() {
+        let schema = parse(
+            r#"
+            syntax = "proto3";
+            package shop;
+            // A customer order
+            message Order {
+                enum Status { OPEN = 0; SHIPPED = 1 [deprecated = true]; }
+                message Line { string sku = 1; uint32 count = 2; }
+                int64 id = 1;
+                Status status = 2;
+                repeated Line lines = 3;
+                map<string, int32> tags = 4;
+                oneof payment { string card = 5; bool invoice = 6; }
+                repeated int32 codes = 7 [packed = false];
+            }
+            "#,
+        );
+        let order = schema.message("shop.Order").unwrap();
+        let types = order
+            .fields
+            .iter()
+            .map(|f| f.field_type.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(types[1], FieldType::Enum("shop.Order.Status".to_string()));
+        assert_eq!(types[2], FieldType::Message("shop.Order.Line".to_string()));
+        assert_eq!(
+            types[3],
+            FieldType::Map(Box::from(FieldType::String), Box::from(FieldType::Int32))
+        );
+        assert!(order.fields[4].presence);
+        assert!(!order.fields[6].packed);
+        assert_eq!(
+            schema.enumeration("shop.Order.Status").unwrap().name(1),
+            Some("SHIPPED")
+        );
+    }
+
+    #[test]
+    fn unknown_types_are_errors() {
+        let mut schema = Schema::default();
+        schema.parse("message A { B b = 1; }").unwrap();
+        assert!(schema.resolve().is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::lang::errors::{data_error, CrushResult};
+
+pub const VARINT: u64 = 0;
+pub const FIXED64: u64 = 1;
+pub const LENGTH_DELIMITED: u64 = 2;
+pub const START_GROUP: u64 = 3;
+pub const END_GROUP: u64 = 4;
+pub const FIXED32: u64 = 5;
+
+/// How deeply groups and messages may be nested, so that malicious input can't overflow the
+/// stack.
+pub const MAX_DEPTH: usize = 100;
+
+/// A cursor over an encoded message.
+pub struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    pub fn new(data: &'a [u8]) -> Input<'a> {
+        Input { data, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn varint(&mut self) -> CrushResult<u64> {
+        let mut res = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = match self.data.get(self.pos) {
+                Some(b) => *b,
+                None => return data_error("Truncated varint"),
+            };
+            self.pos += 1;
+            res |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(res);
+            }
+        }
+        data_error("Invalid varint")
+    }
+
+    pub fn bytes(&mut self, len: usize) -> CrushResult<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return data_error("Truncated message");
+        }
+        let res = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(res)
+    }
+
+    pub fn fixed32(&mut self) -> CrushResult<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub fn fixed64(&mut self) -> CrushResult<u64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn length_delimited(&mut self) -> CrushResult<&'a [u8]> {
+        let len = self.varint()? as usize;
+        self.bytes(len)
+    }
+
+    /// Read a field key, returning the field number and the wire type.
+    pub fn key(&mut self) -> CrushResult<(u64, u64)> {
+        let key = self.varint()?;
+        Ok((key >> 3, key & 7))
+    }
+
+    /// Skip the value of a field of an unknown field number.
+    pub fn skip(&mut self, field_number: u64, wire_type: u64) -> CrushResult<()> {
+        self.skip_nested(field_number, wire_type, 0)
+    }
+
+    fn skip_nested(&mut self, field_number: u64, wire_type: u64, depth: usize) -> CrushResult<()> {
+        match wire_type {
+            VARINT => {
+                self.varint()?;
+            }
+            FIXED64 => {
+                self.bytes(8)?;
+            }
+            LENGTH_DELIMITED => {
+                self.length_delimited()?;
+            }
+            FIXED32 => {
+                self.bytes(4)?;
+            }
+            START_GROUP if depth >= MAX_DEPTH => return data_error("Groups nested too deeply"),
+            START_GROUP => loop {
+                let (number, wire_type) = self.key()?;
+                if wire_type == END_GROUP {
+                    if number != field_number {
+                        return data_error("Mismatched end of group");
+                    }
+                    break;
+                }
+                self.skip_nested(number, wire_type, depth + 1)?;
+            },
+            t => return data_error(format!("Invalid wire type {}", t)),
+        }
+        Ok(())
+    }
+}
+
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn write_key(out: &mut Vec<u8>, field_number: u64, wire_type: u64) {
+    write_varint(out, field_number << 3 | wire_type);
+}
+
+pub fn write_length_delimited(out: &mut Vec<u8>, data: &[u8]) {
+    write_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints() {
+        for value in &[0u64, 1, 127, 128, 300, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, *value);
+            assert_eq!(Input::new(&out).varint().unwrap(), *value);
+        }
+        let mut out = Vec::new();
+        write_varint(&mut out, 300);
+        assert_eq!(out, vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn zigzag() {
+        for value in &[0i64, -1, 1, -64, i64::MIN, i64::MAX] {
+            assert_eq!(zigzag_decode(zigzag_encode(*value)), *value);
+        }
+        assert_eq!(zigzag_encode(-1), 1);
+        assert_eq!(zigzag_encode(1), 2);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(Input::new(&[0x80]).varint().is_err());
+        assert!(Input::new(&[0x05, 0x01]).length_delimited().is_err());
+    }
+
+    #[test]
+    fn nested_groups() {
+        // Start groups of field number 1, each closed by a matching end group
+        let mut data = Vec::new();
+        for _ in 0..10 {
+            write_key(&mut data, 1, START_GROUP);
+        }
+        for _ in 0..10 {
+            write_key(&mut data, 1, END_GROUP);
+        }
+        let mut input = Input::new(&data[1..]);
+        assert!(input.skip(1, START_GROUP).is_ok());
+        assert!(input.is_empty());
+
+        let data = vec![0x0b; 100_000];
+        assert!(Input::new(&data[1..]).skip(1, START_GROUP).is_err());
+    }
+}
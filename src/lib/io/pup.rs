@@ -25,7 +25,8 @@ fn to(context: CommandContext) -> CrushResult<()> {
     let cfg: To = To::parse(context.arguments, &context.printer)?;
     let mut writer = cfg.file.writer(context.output)?;
     let value = context.input.recv()?;
-    serialize_writer(&value, &mut writer)
+    serialize_writer(&value, &mut writer)?;
+    writer.finish()
 }
 
 #[signature(
@@ -173,7 +173,7 @@ fn to(context: CommandContext) -> CrushResult<()> {
     let serde_value = context.input.recv()?;
     let toml_value = to_toml(serde_value)?;
     to_crush_error(writer.write(toml_value.to_string().as_bytes()))?;
-    Ok(())
+    writer.finish()
 }
 
 pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
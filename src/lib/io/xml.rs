@@ -0,0 +1,406 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, data_error, mandate, to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use signature::signature;
+use std::io::{BufReader, Read, Write};
+
+lazy_static! {
+    static ref ELEMENT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("tag", ValueType::String),
+        ColumnType::new(
+            "attributes",
+            ValueType::Dict(Box::from(ValueType::String), Box::from(ValueType::String))
+        ),
+        ColumnType::new("children", ValueType::List(Box::from(ValueType::Struct))),
+        ColumnType::new("text", ValueType::String),
+    ];
+}
+
+/// An XML element. Only the direct text content of an element is kept, in the order it
+/// appears but without its position relative to child elements. Tag and attribute names are
+/// kept as written, including their namespace prefix, and namespace declarations are kept as
+/// xmlns attributes, so that the element can be written back unchanged.
+#[derive(Debug, PartialEq)]
+struct Element {
+    tag: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn parse(xml: &str) -> CrushResult<Element> {
+        let doc = to_crush_error(roxmltree::Document::parse(xml))?;
+        Ok(Element::from_node(doc.root_element()))
+    }
+
+    fn from_node(node: roxmltree::Node) -> Element {
+        let input = node.document().input_text();
+        let inherited = node
+            .parent_element()
+            .map(|parent| parent.namespaces())
+            .unwrap_or(&[]);
+        let declarations = node
+            .namespaces()
+            .iter()
+            .filter(|ns| {
+                !inherited
+                    .iter()
+                    .any(|i| i.name() == ns.name() && i.uri() == ns.uri())
+            })
+            .map(|ns| match ns.name() {
+                Some(prefix) => (format!("xmlns:{}", prefix), ns.uri().to_string()),
+                None => ("xmlns".to_string(), ns.uri().to_string()),
+            });
+        Element {
+            tag: qualified_name(&input[node.range().start + 1..]).to_string(),
+            attributes: declarations
+                .chain(node.attributes().iter().map(|a| {
+                    (
+                        qualified_name(&input[a.range().start..]).to_string(),
+                        a.value().to_string(),
+                    )
+                }))
+                .collect(),
+            children: node
+                .children()
+                .filter(|n| n.is_element())
+                .map(Element::from_node)
+                .collect(),
+            text: node
+                .children()
+                .filter(|n| n.is_text())
+                .filter_map(|n| n.text())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        }
+    }
+
+    fn from_value(value: Value) -> CrushResult<Element> {
+        match value {
+            Value::Struct(s) => {
+                let tag = match s.get("tag") {
+                    Some(Value::String(tag)) => tag,
+                    _ => return data_error("Expected an element with a tag"),
+                };
+                let attributes = match s.get("attributes") {
+                    Some(Value::Dict(d)) => d
+                        .elements()
+                        .drain(..)
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                    _ => vec![],
+                };
+                let children = match s.get("children") {
+                    Some(Value::List(l)) => l
+                        .dump()
+                        .drain(..)
+                        .map(Element::from_value)
+                        .collect::<CrushResult<Vec<_>>>()?,
+                    _ => vec![],
+                };
+                let text = match s.get("text") {
+                    Some(Value::Empty()) | None => String::new(),
+                    Some(v) => v.to_string(),
+                };
+                Ok(Element {
+                    tag,
+                    attributes,
+                    children,
+                    text,
+                })
+            }
+            _ => data_error("Expected an element"),
+        }
+    }
+
+    fn to_row(&self) -> CrushResult<Row> {
+        let attributes = Dict::new(ValueType::String, ValueType::String);
+        for (k, v) in &self.attributes {
+            attributes.insert(Value::string(k), Value::string(v))?;
+        }
+        Ok(Row::new(vec![
+            Value::string(&self.tag),
+            Value::Dict(attributes),
+            Value::List(List::new(
+                ValueType::Struct,
+                self.children
+                    .iter()
+                    .map(|c| c.to_value())
+                    .collect::<CrushResult<Vec<_>>>()?,
+            )),
+            Value::string(&self.text),
+        ]))
+    }
+
+    fn to_value(&self) -> CrushResult<Value> {
+        Ok(Value::Struct(self.to_row()?.into_struct(&ELEMENT_TYPE)))
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.tag);
+        for (k, v) in &self.attributes {
+            out.push_str(&format!(" {}=\"{}\"", k, escape(v)));
+        }
+        if self.children.is_empty() && self.text.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        out.push_str(&escape(&self.text));
+        for child in &self.children {
+            child.write(out);
+        }
+        out.push_str(&format!("</{}>", self.tag));
+    }
+}
+
+/// The name at the start of the specified text, including its namespace prefix, if any.
+fn qualified_name(text: &str) -> &str {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '=' || c == '/' || c == '>')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Axis {
+    Child,
+    Descendant,
+}
+
+/// A parsed path like `/rss/channel/item` or `//testcase`.
+struct Path {
+    absolute: bool,
+    steps: Vec<(Axis, String)>,
+}
+
+impl Path {
+    fn parse(path: &str) -> CrushResult<Path> {
+        let absolute = path.starts_with('/');
+        let rest = if absolute { &path[1..] } else { path };
+        let mut steps = Vec::new();
+        let mut axis = Axis::Child;
+        for segment in rest.split('/') {
+            if segment.is_empty() {
+                axis = Axis::Descendant;
+            } else {
+                steps.push((axis, segment.to_string()));
+                axis = Axis::Child;
+            }
+        }
+        if steps.is_empty() {
+            return argument_error("Empty path");
+        }
+        Ok(Path { absolute, steps })
+    }
+
+    /// Find all elements matching the path. Relative paths are relative to the specified
+    /// element, absolute paths start above it, so that their first step matches it.
+    fn select<'a>(&self, element: &'a Element) -> Vec<&'a Element> {
+        let mut context: Vec<&Element> = vec![element];
+        for (idx, (axis, name)) in self.steps.iter().enumerate() {
+            let mut next: Vec<&Element> = Vec::new();
+            for e in &context {
+                let candidates = match (idx == 0 && self.absolute, axis) {
+                    (true, Axis::Child) => vec![*e],
+                    (true, Axis::Descendant) => descendants_or_self(e),
+                    (false, Axis::Child) => e.children.iter().collect(),
+                    (false, Axis::Descendant) => e
+                        .children
+                        .iter()
+                        .flat_map(|c| descendants_or_self(c))
+                        .collect(),
+                };
+                for c in candidates {
+                    if (name == "*" || c.tag == *name) && !next.iter().any(|n| std::ptr::eq(*n, c)) {
+                        next.push(c);
+                    }
+                }
+            }
+            context = next;
+        }
+        context
+    }
+}
+
+fn descendants_or_self(element: &Element) -> Vec<&Element> {
+    let mut res = vec![element];
+    for child in &element.children {
+        res.append(&mut descendants_or_self(child));
+    }
+    res
+}
+
+#[signature(
+from,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Parse xml format",
+example = "(xml:from ./pom.xml):children",
+long = "The root element of the document is returned as a struct with the members tag,
+    attributes (a dict), children (a list of elements) and text. Only the direct text content of
+    an element is kept, with leading and trailing whitespace removed.
+
+    Tags and attribute names include their namespace prefix, if any, and namespace
+    declarations are attributes named xmlns or xmlns:prefix of the element they are declared
+    on.")]
+struct From {
+    #[unnamed()]
+    files: Files,
+}
+
+fn from(context: CommandContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut text = String::new();
+    to_crush_error(reader.read_to_string(&mut text))?;
+    context.output.send(Element::parse(&text)?.to_value()?)
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize to xml format",
+example = "xml:from ./pom.xml | xml:to ./copy.xml",
+long = "The input must be an element as returned by xml:from.")]
+struct To {
+    #[unnamed()]
+    file: Files,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let element = Element::from_value(context.input.recv()?)?;
+    let mut text = String::new();
+    element.write(&mut text);
+    text.push('\n');
+    let mut writer = cfg.file.writer(context.output)?;
+    to_crush_error(writer.write_all(text.as_bytes()))?;
+    writer.finish()
+}
+
+#[signature(
+select,
+can_block = true,
+output = Known(ValueType::TableStream(ELEMENT_TYPE.clone())),
+short = "Return all elements matching a path",
+example = "xml:from ./report.xml | xml:select \"//testcase\" | where {attributes[\"name\"] =~ re\"io.*\"}",
+long = "Paths consist of tag names separated by /, and are relative to the input element.
+    A path starting with / starts above the input element, so its first tag name must match
+    the input element. // matches any number of levels, and * matches any tag name.
+
+    The input is an element, or a table or table stream of elements, like the output of this
+    command.")]
+struct Select {
+    #[description("the path of the elements to return.")]
+    path: String,
+}
+
+fn select(context: CommandContext) -> CrushResult<()> {
+    let cfg: Select = Select::parse(context.arguments, &context.printer)?;
+    let path = Path::parse(&cfg.path)?;
+    let elements = match context.input.recv()? {
+        Value::Struct(s) => vec![Element::from_value(Value::Struct(s))?],
+        value => {
+            let mut input = mandate(value.stream(), "Expected an element or a table of elements")?;
+            let types = input.types().to_vec();
+            let mut elements = Vec::new();
+            while let Ok(row) = input.read() {
+                elements.push(Element::from_value(Value::Struct(row.into_struct(&types)))?);
+            }
+            elements
+        }
+    };
+    let output = context.output.initialize(ELEMENT_TYPE.clone())?;
+    for element in &elements {
+        for selected in path.select(element) {
+            output.send(selected.to_row()?)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "xml",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Select::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(path: &str, element: &Element) -> Vec<String> {
+        Path::parse(path)
+            .unwrap()
+            .select(element)
+            .iter()
+            .map(|e| format!("{}:{}", e.tag, e.text))
+            .collect()
+    }
+
+    #[test]
+    fn select_paths() {
+        let doc = Element::parse(
+            "<rss><channel><item><title>a</title></item><item><title>b</title></item></channel></rss>",
+        )
+        .unwrap();
+        assert_eq!(tags("channel/item/title", &doc), vec!["title:a", "title:b"]);
+        assert_eq!(tags("/rss/channel/*/title", &doc), vec!["title:a", "title:b"]);
+        assert_eq!(tags("//title", &doc), vec!["title:a", "title:b"]);
+        assert_eq!(tags("//rss", &doc), vec!["rss:"]);
+        assert_eq!(tags("//channel//title", &doc), vec!["title:a", "title:b"]);
+        assert!(tags("/channel", &doc).is_empty());
+    }
+
+    #[test]
+    fn namespaces() {
+        let xml = "<project xmlns=\"urn:a\" xmlns:b=\"urn:b\"><b:name b:lang=\"en\">x</b:name><c xmlns=\"urn:c\"/></project>";
+        let element = Element::parse(xml).unwrap();
+        assert_eq!(element.tag, "project");
+        assert_eq!(element.children[0].tag, "b:name");
+        assert_eq!(
+            element.children[1].attributes,
+            vec![("xmlns".to_string(), "urn:c".to_string())]
+        );
+        let mut out = String::new();
+        element.write(&mut out);
+        assert_eq!(out, xml);
+    }
+
+    #[test]
+    fn serialize() {
+        let xml = "<a x=\"1 &amp; 2\"><b>text &lt;3</b><c/></a>";
+        let element = Element::parse(xml).unwrap();
+        let mut out = String::new();
+        element.write(&mut out);
+        assert_eq!(out, xml);
+    }
+}
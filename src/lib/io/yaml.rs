@@ -0,0 +1,239 @@
+use crate::lang::execution_context::CommandContext;
+use crate::{
+    lang::errors::{CrushError, CrushErrorType},
+    lang::{table::Row, value::Value, value::ValueType},
+};
+use std::io::{BufReader, Read, Write};
+
+use super::json::list_or_table;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, error, mandate, to_crush_error, CrushResult};
+use crate::lang::files::Files;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::ColumnType;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use signature::signature;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+fn from_yaml(yaml_value: &serde_yaml::Value) -> CrushResult<Value> {
+    match yaml_value {
+        serde_yaml::Value::Null => Ok(Value::Empty()),
+        serde_yaml::Value::Bool(b) => Ok(Value::Bool(*b)),
+        serde_yaml::Value::Number(f) => {
+            if f.is_u64() {
+                Ok(Value::Integer(f.as_u64().expect("") as i128))
+            } else if f.is_i64() {
+                Ok(Value::Integer(f.as_i64().expect("") as i128))
+            } else {
+                Ok(Value::Float(f.as_f64().ok_or(CrushError::new(
+                    CrushErrorType::InvalidData("Not a valid number".to_string()),
+                ))?))
+            }
+        }
+        serde_yaml::Value::String(s) => match DateTime::parse_from_rfc3339(s) {
+            Ok(t) => Ok(Value::Time(t.with_timezone(&Local))),
+            Err(_) => Ok(Value::string(s.as_str())),
+        },
+        serde_yaml::Value::Sequence(seq) => list_or_table(
+            seq.iter()
+                .map(from_yaml)
+                .collect::<CrushResult<Vec<Value>>>()?,
+        ),
+        serde_yaml::Value::Mapping(m) => {
+            if m.iter().all(|(k, _)| k.is_string()) {
+                Ok(Value::Struct(Struct::new(
+                    m.iter()
+                        .map(|(k, v)| Ok((k.as_str().unwrap_or("").to_string(), from_yaml(v)?)))
+                        .collect::<CrushResult<Vec<(String, Value)>>>()?,
+                    None,
+                )))
+            } else {
+                let elements = m
+                    .iter()
+                    .map(|(k, v)| Ok((from_yaml(k)?, from_yaml(v)?)))
+                    .collect::<CrushResult<Vec<(Value, Value)>>>()?;
+                let key_types: HashSet<ValueType> =
+                    elements.iter().map(|(k, _)| k.value_type()).collect();
+                let value_types: HashSet<ValueType> =
+                    elements.iter().map(|(_, v)| v.value_type()).collect();
+                let single = |types: HashSet<ValueType>| match types.len() {
+                    1 => types.into_iter().next().unwrap(),
+                    _ => ValueType::Any,
+                };
+                let dict = Dict::new(single(key_types), single(value_types));
+                for (k, v) in elements {
+                    dict.insert(k, v)?;
+                }
+                Ok(Value::Dict(dict))
+            }
+        }
+    }
+}
+
+fn to_yaml(value: Value) -> CrushResult<serde_yaml::Value> {
+    match value.materialize() {
+        Value::Empty() => Ok(serde_yaml::Value::Null),
+
+        Value::File(s) => Ok(serde_yaml::Value::from(mandate(
+            s.to_str(),
+            "Invalid filename",
+        )?)),
+
+        Value::String(s) => Ok(serde_yaml::Value::from(s)),
+
+        Value::Integer(i) => Ok(serde_yaml::Value::from(to_crush_error(i64::try_from(i))?)),
+
+        Value::List(l) => Ok(serde_yaml::Value::Sequence(
+            l.dump()
+                .drain(..)
+                .map(to_yaml)
+                .collect::<CrushResult<Vec<_>>>()?,
+        )),
+
+        Value::Table(t) => {
+            let types = t.types().to_vec();
+            let structs = t
+                .rows()
+                .iter()
+                .map(|r| r.clone().into_struct(&types))
+                .map(|s| to_yaml(Value::Struct(s)))
+                .collect::<CrushResult<Vec<_>>>()?;
+            Ok(serde_yaml::Value::Sequence(structs))
+        }
+
+        Value::Bool(b) => Ok(serde_yaml::Value::from(b)),
+
+        Value::Float(f) => Ok(serde_yaml::Value::from(f)),
+
+        Value::Struct(s) => {
+            let mut map = serde_yaml::Mapping::new();
+            for (k, v) in s.local_elements() {
+                map.insert(serde_yaml::Value::from(k), to_yaml(v)?);
+            }
+            Ok(serde_yaml::Value::Mapping(map))
+        }
+
+        Value::Dict(d) => {
+            let mut map = serde_yaml::Mapping::new();
+            for (k, v) in d.elements() {
+                map.insert(to_yaml(k)?, to_yaml(v)?);
+            }
+            Ok(serde_yaml::Value::Mapping(map))
+        }
+
+        Value::Duration(d) => Ok(match d.num_nanoseconds() {
+            // Durations with a fraction of a second are written as float seconds
+            Some(nanos) if nanos % 1_000_000_000 != 0 => {
+                serde_yaml::Value::from(nanos as f64 / 1_000_000_000.0)
+            }
+            _ => serde_yaml::Value::from(d.num_seconds()),
+        }),
+
+        Value::Time(t) => Ok(serde_yaml::Value::from(t.to_rfc3339())),
+
+        v => error(format!("Unsupported data type {}", v.value_type())),
+    }
+}
+
+#[signature(
+from,
+can_block = true,
+output = Unknown,
+short = "Parse yaml format",
+example = "yaml:from ./deployment.yaml",
+long = "If the input contains multiple documents, the output is a table stream with one row per
+    document, in a column named document.")]
+struct From {
+    #[unnamed()]
+    files: Files,
+}
+
+pub fn from(context: CommandContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut text = String::new();
+    to_crush_error(reader.read_to_string(&mut text))?;
+
+    let mut documents = serde_yaml::Deserializer::from_str(&text)
+        .map(|document| from_yaml(&to_crush_error(serde_yaml::Value::deserialize(document))?))
+        .collect::<CrushResult<Vec<Value>>>()?;
+
+    match documents.len() {
+        0 => context.output.send(Value::Empty()),
+        1 => context.output.send(documents.remove(0)),
+        _ => {
+            let output = context
+                .output
+                .initialize(vec![ColumnType::new("document", ValueType::Any)])?;
+            for document in documents {
+                output.send(Row::new(vec![document]))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize to yaml format",
+example = "ls | yaml:to",
+long = "If documents is set, the input must be a table or a table stream, and every row is
+    written as a separate document. Rows with a single column are written as the value of that
+    column.")]
+struct To {
+    #[unnamed()]
+    file: Files,
+    #[default(false)]
+    #[description("write every row of the input as a separate document.")]
+    documents: bool,
+}
+
+fn to(context: CommandContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let value = context.input.recv()?;
+    let documents = if cfg.documents {
+        let mut input = match value.stream() {
+            Some(input) => input,
+            None => return argument_error("Expected a table or a table stream"),
+        };
+        let types = input.types().to_vec();
+        let mut documents = Vec::new();
+        while let Ok(row) = input.read() {
+            documents.push(if types.len() == 1 {
+                row.into_vec().remove(0)
+            } else {
+                Value::Struct(row.into_struct(&types))
+            });
+        }
+        documents
+    } else {
+        vec![value]
+    };
+
+    let mut writer = cfg.file.writer(context.output)?;
+    for document in documents {
+        let text = to_crush_error(serde_yaml::to_string(&to_yaml(document)?))?;
+        to_crush_error(writer.write_all(text.as_bytes()))?;
+        to_crush_error(writer.write_all(b"\n"))?;
+    }
+    writer.finish()
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_namespace(
+        "yaml",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
@@ -2,9 +2,10 @@ use crate::lang::errors::to_crush_error;
 use crate::lang::execute;
 use crate::lang::printer::Printer;
 use crate::lang::stream::ValueSender;
+use crate::util::file::config_dir;
 use crate::{lang::errors::CrushResult, lang::scope::Scope};
 use std::fs::read_dir;
-use std::path::Path;
+use std::path::PathBuf;
 
 #[macro_use]
 pub mod binary_op;
@@ -15,6 +16,10 @@ mod constants;
 mod control;
 #[cfg(target_os = "linux")]
 mod dbus;
+mod encoding;
+mod env;
+mod hash;
+mod history;
 mod host;
 mod io;
 mod math;
@@ -30,53 +35,101 @@ pub mod types;
 mod user;
 mod var;
 
+pub use control::{last_status, status_count};
+
+/// Libraries written in Crush that are a part of Crush itself.
+const BUILTIN_LIBRARIES: &[(&str, &str)] = &[("lls", include_str!("../crushlib/lls.crush"))];
+
+enum Library {
+    Builtin(&'static str),
+    File(PathBuf),
+}
+
+/**
+    The directories to load libraries written in Crush from. This is the colon separated list
+    of directories in the CRUSH_PATH environment variable, or ~/.config/crush/lib if it
+    is not set.
+*/
+fn library_path() -> Vec<PathBuf> {
+    match std::env::var("CRUSH_PATH") {
+        Ok(path) => path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        Err(_) => config_dir()
+            .map(|dir| vec![dir.join("lib")])
+            .unwrap_or_default(),
+    }
+}
+
 fn declare_external(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushResult<()> {
-    match read_dir("src/crushlib/") {
-        Err(_) => Ok(()),
-        Ok(dirs) => {
-            for lib in dirs {
-                match lib {
-                    Ok(entry) => match entry.file_name().to_str() {
-                        None => {
-                            printer.error("Invalid filename encountered during library loading");
-                        }
-                        Some(name_with_extension) => {
-                            let name = name_with_extension.trim_end_matches(".crush");
-                            let s = load_external_namespace(
-                                name,
-                                &entry.path(),
-                                root,
-                                printer,
-                                output,
-                            )?;
-                            if name == "lls" {
-                                root.r#use(&s);
+    for (name, code) in BUILTIN_LIBRARIES {
+        let s = load_external_namespace(name, Library::Builtin(code), root, printer, output)?;
+        if *name == "lls" {
+            root.r#use(&s);
+        }
+    }
+
+    for dir in library_path() {
+        let entries = match read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for lib in entries {
+            match lib {
+                Ok(entry) => match entry.file_name().to_str() {
+                    None => {
+                        printer.error("Invalid filename encountered during library loading");
+                    }
+                    Some(name_with_extension) => {
+                        if let Some(name) = name_with_extension.strip_suffix(".crush") {
+                            // Libraries in earlier directories of the path take precedence
+                            if root.get(name)?.is_none() {
+                                printer.handle_error(load_external_namespace(
+                                    name,
+                                    Library::File(entry.path()),
+                                    root,
+                                    printer,
+                                    output,
+                                ));
                             }
                         }
-                    },
-                    err => printer.handle_error(to_crush_error(err)),
-                }
+                    }
+                },
+                err => printer.handle_error(to_crush_error(err)),
             }
-            Ok(())
         }
     }
+    Ok(())
 }
 
 fn load_external_namespace(
     name: &str,
-    file: &Path,
+    library: Library,
     root: &Scope,
     printer: &Printer,
     output: &ValueSender,
 ) -> CrushResult<Scope> {
     let local_printer = printer.clone();
     let local_output = output.clone();
-    let local_file = file.to_path_buf();
+    let local_name = name.to_string();
     root.create_namespace(
         name,
         Box::new(move |env| {
             let tmp_env: Scope = env.create_temporary_namespace();
-            execute::file(tmp_env.clone(), &local_file, &local_printer, &local_output)?;
+            match library {
+                Library::Builtin(code) => execute::code(
+                    tmp_env.clone(),
+                    code,
+                    &format!("<{}>", local_name),
+                    &local_printer,
+                    &local_output,
+                ),
+                Library::File(file) => {
+                    execute::file(tmp_env.clone(), &file, &local_printer, &local_output)?
+                }
+            }
             let data = tmp_env.export()?;
             for (k, v) in data.mapping {
                 env.declare(&k, v)?;
@@ -96,6 +149,9 @@ pub fn declare(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushRe
     #[cfg(target_os = "linux")]
     proc::declare(root)?;
     io::declare(root)?;
+    encoding::declare(root)?;
+    env::declare(root)?;
+    hash::declare(root)?;
     control::declare(root)?;
     constants::declare(root)?;
     math::declare(root)?;
@@ -103,6 +159,7 @@ pub fn declare(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushRe
     remote::declare(root)?;
     random::declare(root)?;
     host::declare(root)?;
+    history::declare(root)?;
     #[cfg(target_os = "linux")]
     dbus::declare(root)?;
     #[cfg(target_os = "linux")]
@@ -1,9 +1,14 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::Command;
 use crate::lang::command::OutputType::Known;
-use crate::lang::errors::{error, to_crush_error, CrushResult};
+use crate::lang::errors::{error, mandate, to_crush_error, CrushResult};
 use crate::lang::execution_context::{ArgumentVector, CommandContext};
+use crate::lang::jobs;
+use crate::lang::pretty_printer::create_pretty_printer;
 use crate::lang::scope::Scope;
+use crate::lang::stream::empty_channel;
 use crate::lang::table::ColumnType;
+use crate::util::thread::{build, handle};
 use crate::util::user_map::{create_user_map, UserMap};
 use crate::{lang::table::Row, lang::value::Value, lang::value::ValueType};
 use chrono::Duration;
@@ -28,6 +33,12 @@ lazy_static! {
         ColumnType::new("vms", ValueType::Integer),
         ColumnType::new("name", ValueType::String),
     ];
+    static ref JOBS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("id", ValueType::Integer),
+        ColumnType::new("state", ValueType::String),
+        ColumnType::new("start", ValueType::Time),
+        ColumnType::new("command", ValueType::String),
+    ];
 }
 
 #[signature(
@@ -123,12 +134,117 @@ fn kill(context: CommandContext) -> CrushResult<()> {
     context.output.send(Value::Empty())
 }
 
+#[signature(
+jobs,
+can_block = false,
+short = "Return a table stream containing information on all background jobs",
+output = Known(ValueType::TableStream(JOBS_OUTPUT_TYPE.clone())),
+long = "Jobs that have finished are listed until they are waited for using fg or wait.")]
+struct Jobs {
+}
+
+fn jobs(context: CommandContext) -> CrushResult<()> {
+    context.arguments.check_len(0)?;
+    let output = context.output.initialize(JOBS_OUTPUT_TYPE.clone())?;
+    for job in jobs::list() {
+        output.send(Row::new(vec![
+            Value::Integer(job.id as i128),
+            Value::string(&job.state.to_string()),
+            Value::Time(job.start_time),
+            Value::string(&job.invocation),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+fg,
+can_block = true,
+short = "Wait for a background job to finish",
+output = Known(ValueType::Empty),
+long = "If no job id is given, the most recently started job is used.")]
+struct Fg {
+    #[description("the id of the job to wait for.")]
+    id: Option<usize>,
+}
+
+fn fg(context: CommandContext) -> CrushResult<()> {
+    let cfg: Fg = Fg::parse(context.arguments, &context.printer)?;
+    let id = match cfg.id {
+        Some(id) => id,
+        None => mandate(jobs::last(), "No current job")?,
+    };
+    jobs::wait(id)?;
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+wait,
+can_block = true,
+short = "Wait for a set of background jobs to finish",
+output = Known(ValueType::Empty),
+long = "If no job ids are given, wait for all background jobs to finish.")]
+struct Wait {
+    #[unnamed("the ids of the jobs to wait for.")]
+    id: Vec<usize>,
+}
+
+fn wait(context: CommandContext) -> CrushResult<()> {
+    let cfg: Wait = Wait::parse(context.arguments, &context.printer)?;
+    if cfg.id.is_empty() {
+        jobs::wait_all()?;
+    } else {
+        for id in cfg.id {
+            jobs::wait(id)?;
+        }
+    }
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+bg,
+can_block = false,
+short = "Run a command as a background job",
+output = Known(ValueType::Integer),
+example = "bg {find . | group ^user}",
+long = "The output of the command is printed to the screen once it is available. Returns the id
+    of the new job.")]
+struct Bg {
+    #[description("the command to run in the background.")]
+    command: Command,
+}
+
+fn bg(context: CommandContext) -> CrushResult<()> {
+    let cfg: Bg = Bg::parse(context.arguments, &context.printer)?;
+    let invocation = cfg.command.to_string();
+    let scope = context.scope.clone();
+    let printer = context.printer.background();
+    let job_printer = printer.clone();
+    let job = handle(build("bg").spawn(move || {
+        job_printer.handle_error(cfg.command.invoke(CommandContext {
+            input: empty_channel(),
+            output: create_pretty_printer(job_printer.clone()),
+            arguments: Vec::new(),
+            scope,
+            this: None,
+            printer: job_printer.clone(),
+            background: true,
+        }))
+    }));
+    let id = jobs::spawn(invocation, job, &printer)?;
+    context.output.send(Value::Integer(id as i128))
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_namespace(
         "proc",
         Box::new(move |env| {
             Ps::declare(env)?;
             Kill::declare(env)?;
+            Jobs::declare(env)?;
+            Fg::declare(env)?;
+            Bg::declare(env)?;
+            Wait::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
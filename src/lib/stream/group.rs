@@ -39,6 +39,7 @@ fn aggregate(
     commands: Vec<Command>,
     printer: Printer,
     scope: Scope,
+    background: bool,
     destination: OutputStream,
     task_input: Receiver<(Vec<Value>, InputStream)>,
 ) -> CrushResult<()> {
@@ -59,6 +60,7 @@ fn aggregate(
                     scope: scope.clone(),
                     this: None,
                     printer: printer.clone(),
+                    background,
                 })?;
                 let mut result = key;
                 result.push(output_receiver.recv()?);
@@ -78,6 +80,7 @@ fn aggregate(
                         scope: scope.clone(),
                         this: None,
                         printer: printer.clone(),
+                        background,
                     }));
                     receivers.push(output_receiver);
                 }
@@ -104,6 +107,7 @@ fn create_worker_thread(
     cfg: &Group,
     printer: &Printer,
     scope: &Scope,
+    background: bool,
     destination: &OutputStream,
     task_input: &Receiver<(Vec<Value>, InputStream)>,
 ) -> JobJoinHandle {
@@ -122,6 +126,7 @@ fn create_worker_thread(
             my_commands,
             my_printer,
             my_scope,
+            background,
             my_destination,
             my_input,
         ));
@@ -160,7 +165,14 @@ pub fn group(context: CommandContext) -> CrushResult<()> {
     let (task_output, task_input) = unbounded::<(Vec<Value>, InputStream)>();
 
     for _ in 0..16 {
-        create_worker_thread(&cfg, &context.printer, &context.scope, &output, &task_input);
+        create_worker_thread(
+            &cfg,
+            &context.printer,
+            &context.scope,
+            context.background,
+            &output,
+            &task_input,
+        );
     }
 
     drop(task_input);
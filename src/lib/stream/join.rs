@@ -1,213 +1,454 @@
-use crate::lang::argument::Argument;
+use crate::lang::argument::{Argument, ArgumentHandler};
 use crate::lang::errors::argument_error;
 use crate::lang::errors::CrushError;
 use crate::lang::errors::CrushResult;
 use crate::lang::execution_context::{ArgumentVector, CommandContext};
-use crate::lang::printer::Printer;
 use crate::lang::r#struct::Struct;
-use crate::lang::stream::CrushStream;
-use crate::lang::stream::OutputStream;
+use crate::lang::stream::{OutputStream, Stream};
 use crate::lang::table::ColumnType;
 use crate::lang::table::ColumnVec;
 use crate::lang::table::Row;
-use crate::lang::value::Value;
-use crate::lang::value::ValueType;
+use crate::lang::value::{Field, Value, ValueType};
 use crate::util::replace::Replace;
+use signature::signature;
 use std::collections::HashMap;
 
-pub struct Config {
-    left_table_idx: usize,
-    right_table_idx: usize,
-    left_column_idx: usize,
-    right_column_idx: usize,
+#[signature(
+    join,
+    can_block = true,
+    short = "Join two tables together on the specified columns",
+    long = "The output contains all columns of the left table, followed by the columns of the
+    right table that are not join columns. Columns of the right table with the same name as
+    another column are renamed by prefixing them with right_. For rows that only exist in one of
+    the tables, the columns of the other table are empty, except for the join columns.
+
+    Both inputs are read at the same time. The input that ends first is kept in memory, and the
+    other one is streamed, so the order of the output follows the order of the larger input.
+
+    The kind of join is one of:
+
+    * inner, only rows that exist in both tables,
+    * left, all rows of the left table,
+    * right, all rows of the right table,
+    * full, all rows of both tables,
+    * semi, the rows of the left table that exist in the right table,
+    * anti, the rows of the left table that do not exist in the right table.
+
+    Rows with empty join columns never match any other row.
+
+    For backwards compatibility, join also accepts a struct with two table columns as input,
+    and the columns to join on as ^table:column, e.g.
+    `data l=home r=age | join ^l:name ^r:name`",
+    example = "join (csv:from ./home.csv) (csv:from ./age.csv) ^name kind=left"
+)]
+pub struct Join {
+    #[description("the left table.")]
+    left: Stream,
+    #[description("the right table.")]
+    right: Stream,
+    #[unnamed()]
+    #[description("the columns to join on, which must exist in both tables.")]
+    on: Vec<Field>,
+    #[description("additional columns of the left table to join on.")]
+    left_on: Vec<Field>,
+    #[description("additional columns of the right table to join on, one for every column in left_on.")]
+    right_on: Vec<Field>,
+    #[values("inner", "left", "right", "full", "semi", "anti")]
+    #[default("inner")]
+    #[description("the kind of join.")]
+    kind: String,
 }
 
-pub fn get_sub_type(cell_type: &ValueType) -> Result<&[ColumnType], CrushError> {
-    match cell_type {
-        ValueType::TableStream(sub_types) | ValueType::Table(sub_types) => Ok(sub_types),
-        _ => argument_error("Expected a table column"),
-    }
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Semi,
+    Anti,
 }
 
-pub fn guess_tables(
-    input_type: &[ColumnType],
-) -> Result<(usize, usize, &[ColumnType], &[ColumnType]), CrushError> {
-    let tables: Vec<(usize, &Vec<ColumnType>)> = input_type
-        .iter()
-        .enumerate()
-        .flat_map(|(idx, t)| match &t.cell_type {
-            ValueType::TableStream(sub_types) | ValueType::Table(sub_types) => {
-                Some((idx, sub_types))
-            }
-            _ => None,
-        })
-        .collect();
-    if tables.len() == 2 {
-        Ok((tables[0].0, tables[1].0, tables[0].1, tables[1].1))
-    } else {
-        argument_error(
-            format!(
-                "Could not guess tables to join, expected two tables, found {}",
-                tables.len()
-            )
-            .as_str(),
-        )
+impl Kind {
+    fn parse(kind: &str) -> CrushResult<Kind> {
+        match kind {
+            "inner" => Ok(Kind::Inner),
+            "left" => Ok(Kind::Left),
+            "right" => Ok(Kind::Right),
+            "full" => Ok(Kind::Full),
+            "semi" => Ok(Kind::Semi),
+            "anti" => Ok(Kind::Anti),
+            _ => argument_error(format!("Unknown join kind {}", kind).as_str()),
+        }
+    }
+
+    /// True if rows of the left table without a match are part of the output.
+    fn keeps_left(self) -> bool {
+        matches!(self, Kind::Left | Kind::Full)
+    }
+
+    /// True if rows of the right table without a match are part of the output.
+    fn keeps_right(self) -> bool {
+        matches!(self, Kind::Right | Kind::Full)
+    }
+
+    /// True if the output only contains the columns of the left table.
+    fn is_filter(self) -> bool {
+        matches!(self, Kind::Semi | Kind::Anti)
     }
 }
 
-fn scan_table(
-    table: &str,
-    column: &str,
-    input_type: &[ColumnType],
-) -> Result<(usize, usize), CrushError> {
-    let table_idx = input_type.find_str(&table.to_string())?;
-    let column_idx =
-        get_sub_type(&input_type[table_idx].cell_type)?.find_str(&column.to_string())?;
-    Ok((table_idx, column_idx))
+struct Config {
+    kind: Kind,
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    /// The number of columns in each table.
+    left_width: usize,
+    right_width: usize,
 }
 
-fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> Result<Config, CrushError> {
-    arguments.check_len(2)?;
+impl Config {
+    fn new(
+        kind: Kind,
+        left_types: &[ColumnType],
+        right_types: &[ColumnType],
+        left_fields: Vec<Field>,
+        right_fields: Vec<Field>,
+    ) -> CrushResult<Config> {
+        if left_fields.is_empty() {
+            return argument_error("No columns to join on");
+        }
+        if left_fields.len() != right_fields.len() {
+            return argument_error("Expected the same number of join columns in both tables");
+        }
+        let left_keys = left_fields
+            .iter()
+            .map(|f| left_types.find(f))
+            .collect::<CrushResult<Vec<_>>>()?;
+        let right_keys = right_fields
+            .iter()
+            .map(|f| right_types.find(f))
+            .collect::<CrushResult<Vec<_>>>()?;
+        for (l, r) in left_keys.iter().zip(right_keys.iter()) {
+            if !left_types[*l].cell_type.is_hashable() || !right_types[*r].cell_type.is_hashable()
+            {
+                return argument_error(
+                    "Cannot join on this column type. (It is either mutable or not comparable)",
+                );
+            }
+        }
+        Ok(Config {
+            kind,
+            left_keys,
+            right_keys,
+            left_width: left_types.len(),
+            right_width: right_types.len(),
+        })
+    }
 
-    match (&arguments[0].value, &arguments[1].value) {
-        (Value::Field(l), Value::Field(r)) => {
-            let config = match (l.len(), r.len()) {
-                (1, 1) => {
-                    let (left_table_idx, right_table_idx, left_types, right_types) =
-                        guess_tables(&input_type)?;
-
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx: left_types.find(&l)?,
-                        right_column_idx: right_types.find(&r)?,
-                    }
+    fn output_type(&self, left: &[ColumnType], right: &[ColumnType]) -> Vec<ColumnType> {
+        let mut res = Vec::new();
+        for (idx, column) in left.iter().enumerate() {
+            let cell_type = match self.left_keys.iter().position(|k| *k == idx) {
+                Some(key) if column.cell_type == right[self.right_keys[key]].cell_type => {
+                    column.cell_type.clone()
                 }
-                (2, 2) => {
-                    let (left_table_idx, left_column_idx) =
-                        scan_table(l[0].as_ref(), l[1].as_ref(), &input_type)?;
-
-                    let (right_table_idx, right_column_idx) =
-                        scan_table(r[0].as_ref(), r[1].as_ref(), &input_type)?;
+                Some(_) => ValueType::Any,
+                None if self.kind.keeps_right() => ValueType::Any,
+                None => column.cell_type.clone(),
+            };
+            res.push(ColumnType::new(&column.name, cell_type));
+        }
+        if !self.kind.is_filter() {
+            for (idx, column) in right.iter().enumerate() {
+                if !self.right_keys.contains(&idx) {
+                    res.push(ColumnType::new(
+                        &unique_name(&res, &column.name),
+                        if self.kind.keeps_left() {
+                            ValueType::Any
+                        } else {
+                            column.cell_type.clone()
+                        },
+                    ));
+                }
+            }
+        }
+        res
+    }
 
-                    if left_table_idx == right_table_idx {
-                        return argument_error("Left and right table can't be the same");
-                    }
+    /// The values of the join columns of a row, or None if any of them is empty, since such
+    /// rows never match any other row.
+    fn key(&self, row: &Row, left: bool) -> Option<Vec<Value>> {
+        let keys = if left {
+            &self.left_keys
+        } else {
+            &self.right_keys
+        };
+        keys.iter()
+            .map(|idx| match &row.cells()[*idx] {
+                Value::Empty() => None,
+                value => Some(value.clone()),
+            })
+            .collect()
+    }
 
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx,
-                        right_column_idx,
+    /// Combine a row of each table into an output row. A missing left row is replaced with the
+    /// join columns of the right row, and a missing right row with empty values.
+    fn combine(&self, left: Option<&Row>, right: Option<&Row>) -> Row {
+        let mut cells = match left {
+            Some(row) => row.cells().clone(),
+            None => {
+                let mut cells = vec![Value::Empty(); self.left_width];
+                if let Some(right) = right {
+                    for (l, r) in self.left_keys.iter().zip(self.right_keys.iter()) {
+                        cells[*l] = right.cells()[*r].clone();
                     }
                 }
-                _ => {
-                    return argument_error(
-                        "Expected both fields on the form %table.column or %column",
-                    )
-                }
-            };
-
-            let r_type = &get_sub_type(&input_type[config.right_table_idx].cell_type)?
-                [config.right_column_idx]
-                .cell_type;
-            let l_type = &get_sub_type(&input_type[config.left_table_idx].cell_type)?
-                [config.left_column_idx]
-                .cell_type;
-            if r_type != l_type {
-                return argument_error("Cannot join two columns of different types");
+                cells
             }
-            if !r_type.is_hashable() {
-                argument_error(
-                    "Cannot join on this column type. (It is either mutable or not comparable)",
-                )
-            } else {
-                Ok(config)
+        };
+        if !self.kind.is_filter() {
+            match right {
+                Some(row) => {
+                    for (idx, cell) in row.cells().iter().enumerate() {
+                        if !self.right_keys.contains(&idx) {
+                            cells.push(cell.clone());
+                        }
+                    }
+                }
+                None => cells.resize(
+                    cells.len() + self.right_width - self.right_keys.len(),
+                    Value::Empty(),
+                ),
             }
         }
-        _ => argument_error("Expected arguments like %table1.col == %table2.col"),
+        Row::new(cells)
     }
 }
 
-fn combine(mut l: Row, r: Row, cfg: &Config) -> Row {
-    for (idx, c) in r.into_vec().drain(..).enumerate() {
-        if idx != cfg.right_column_idx {
-            l.push(c);
+/// A name for a column of the right table that doesn't clash with any of the columns before it.
+fn unique_name(columns: &[ColumnType], name: &str) -> String {
+    let taken = |name: &str| columns.iter().any(|c| c.name == name);
+    if !taken(name) {
+        return name.to_string();
+    }
+    let mut res = format!("right_{}", name);
+    let mut count = 1;
+    while taken(&res) {
+        count += 1;
+        res = format!("right_{}_{}", name, count);
+    }
+    res
+}
+
+/// Read from both streams in turn until one of them ends. Returns the rows of the smaller
+/// stream, the rows read so far from the larger stream, and whether the left stream was the
+/// smaller one.
+fn read_smaller(left: &mut Stream, right: &mut Stream) -> (Vec<Row>, Vec<Row>, bool) {
+    let mut left_rows = Vec::new();
+    let mut right_rows = Vec::new();
+    loop {
+        match left.read() {
+            Ok(row) => left_rows.push(row),
+            Err(_) => return (left_rows, right_rows, true),
+        }
+        match right.read() {
+            Ok(row) => right_rows.push(row),
+            Err(_) => return (right_rows, left_rows, false),
         }
     }
-    l
 }
 
 fn do_join(
     cfg: &Config,
-    l: &mut dyn CrushStream,
-    r: &mut dyn CrushStream,
+    mut left: Stream,
+    mut right: Stream,
     output: &OutputStream,
-    printer: &Printer,
 ) -> CrushResult<()> {
-    let mut l_data: HashMap<Value, Row> = HashMap::new();
-    while let Ok(row) = l.read() {
-        l_data.insert(row.cells()[cfg.left_column_idx].clone(), row);
+    let (hashed, buffered, hashed_is_left) = read_smaller(&mut left, &mut right);
+    let mut streamed = if hashed_is_left { right } else { left };
+
+    let mut index: HashMap<Vec<Value>, Vec<usize>> = HashMap::new();
+    for (idx, row) in hashed.iter().enumerate() {
+        if let Some(key) = cfg.key(row, hashed_is_left) {
+            index.entry(key).or_default().push(idx);
+        }
     }
+    let mut matched = vec![false; hashed.len()];
 
-    while let Ok(r_row) = r.read() {
-        l_data
-            .remove(&r_row.cells()[cfg.right_column_idx])
-            .map(|l_row| {
-                printer.handle_error(output.send(combine(l_row, r_row, cfg)));
-            });
+    let mut process = |row: Row| -> CrushResult<()> {
+        let matches = cfg
+            .key(&row, !hashed_is_left)
+            .and_then(|key| index.get(&key))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[]);
+        for idx in matches {
+            matched[*idx] = true;
+        }
+        match (cfg.kind, hashed_is_left) {
+            (Kind::Semi, false) if !matches.is_empty() => output.send(row),
+            (Kind::Anti, false) if matches.is_empty() => output.send(row),
+            (Kind::Semi, _) | (Kind::Anti, _) => Ok(()),
+            _ if matches.is_empty() => {
+                if hashed_is_left && cfg.kind.keeps_right() {
+                    output.send(cfg.combine(None, Some(&row)))
+                } else if !hashed_is_left && cfg.kind.keeps_left() {
+                    output.send(cfg.combine(Some(&row), None))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => {
+                for idx in matches {
+                    let other = &hashed[*idx];
+                    output.send(if hashed_is_left {
+                        cfg.combine(Some(other), Some(&row))
+                    } else {
+                        cfg.combine(Some(&row), Some(other))
+                    })?;
+                }
+                Ok(())
+            }
+        }
+    };
+
+    for row in buffered {
+        process(row)?;
+    }
+    while let Ok(row) = streamed.read() {
+        process(row)?;
+    }
+
+    for (row, matched) in hashed.iter().zip(matched) {
+        match (cfg.kind, hashed_is_left, matched) {
+            (Kind::Semi, true, true) | (Kind::Anti, true, false) => output.send(row.clone())?,
+            (kind, true, false) if kind.keeps_left() => {
+                output.send(cfg.combine(Some(row), None))?
+            }
+            (kind, false, false) if kind.keeps_right() => {
+                output.send(cfg.combine(None, Some(row)))?
+            }
+            _ => {}
+        }
     }
     Ok(())
 }
 
-pub fn run(
-    config: Config,
-    row: Struct,
-    output: OutputStream,
-    printer: &Printer,
+fn run(
+    kind: Kind,
+    left: Stream,
+    right: Stream,
+    left_fields: Vec<Field>,
+    right_fields: Vec<Field>,
+    context: CommandContext,
 ) -> CrushResult<()> {
-    let mut v = row.to_vec();
-    match (
-        v.replace(config.left_table_idx, Value::Integer(0)).stream(),
-        v.replace(config.right_table_idx, Value::Integer(0))
-            .stream(),
-    ) {
-        (Some(mut l), Some(mut r)) => do_join(&config, l.as_mut(), r.as_mut(), &output, printer),
-        _ => panic!("Wrong row format"),
+    let cfg = Config::new(
+        kind,
+        left.types(),
+        right.types(),
+        left_fields,
+        right_fields,
+    )?;
+    let output = context
+        .output
+        .initialize(cfg.output_type(left.types(), right.types()))?;
+    do_join(&cfg, left, right, &output)
+}
+
+fn find_table(input_type: &[ColumnType], name: &str) -> Result<usize, CrushError> {
+    let idx = input_type.find_str(name)?;
+    match input_type[idx].cell_type {
+        ValueType::TableStream(_) | ValueType::Table(_) => Ok(idx),
+        _ => argument_error("Expected a table column"),
     }
 }
 
-fn get_output_type(input_type: &[ColumnType], cfg: &Config) -> Result<Vec<ColumnType>, CrushError> {
-    let tables: Vec<Option<&Vec<ColumnType>>> = input_type
+fn guess_tables(input_type: &[ColumnType]) -> Result<(usize, usize), CrushError> {
+    let tables: Vec<usize> = input_type
         .iter()
-        .map(|t| match &t.cell_type {
-            ValueType::TableStream(sub_types) | ValueType::Table(sub_types) => Some(sub_types),
-            _ => None,
+        .enumerate()
+        .filter(|(_, t)| {
+            matches!(
+                t.cell_type,
+                ValueType::TableStream(_) | ValueType::Table(_)
+            )
         })
+        .map(|(idx, _)| idx)
         .collect();
+    if tables.len() == 2 {
+        Ok((tables[0], tables[1]))
+    } else {
+        argument_error(
+            format!(
+                "Could not guess tables to join, expected two tables, found {}",
+                tables.len()
+            )
+            .as_str(),
+        )
+    }
+}
 
-    match (tables[cfg.left_table_idx], tables[cfg.right_table_idx]) {
-        (Some(v1), Some(v2)) => {
-            let mut res = v1.clone();
-            for (idx, c) in v2.iter().enumerate() {
-                if idx != cfg.right_column_idx {
-                    res.push(c.clone());
-                }
-            }
-            Ok(res)
+/// The original syntax, where the input is a struct with two tables and the arguments are the
+/// columns to join on, optionally prefixed with the name of their table.
+fn join_struct(s: Struct, arguments: Vec<Argument>, context: CommandContext) -> CrushResult<()> {
+    arguments.check_len(2)?;
+    let input_type = s.local_signature();
+    let (l, r) = match (&arguments[0].value, &arguments[1].value) {
+        (Value::Field(l), Value::Field(r)) => (l.clone(), r.clone()),
+        _ => return argument_error("Expected arguments like ^table1:col ^table2:col"),
+    };
+    let (left_idx, right_idx, left_field, right_field) = match (l.len(), r.len()) {
+        (1, 1) => {
+            let (left_idx, right_idx) = guess_tables(&input_type)?;
+            (left_idx, right_idx, l, r)
         }
-        _ => argument_error("Impossible error?"),
+        (2, 2) => (
+            find_table(&input_type, &l[0])?,
+            find_table(&input_type, &r[0])?,
+            l[1..].to_vec(),
+            r[1..].to_vec(),
+        ),
+        _ => {
+            return argument_error("Expected both fields on the form ^table:column or ^column")
+        }
+    };
+    if left_idx == right_idx {
+        return argument_error("Left and right table can't be the same");
+    }
+
+    let mut v = s.to_vec();
+    match (
+        v.replace(left_idx, Value::Integer(0)).stream(),
+        v.replace(right_idx, Value::Integer(0)).stream(),
+    ) {
+        (Some(left), Some(right)) => run(
+            Kind::Inner,
+            left,
+            right,
+            vec![left_field],
+            vec![right_field],
+            context,
+        ),
+        _ => argument_error("Expected two tables"),
     }
 }
 
-pub fn perform(context: CommandContext) -> CrushResult<()> {
-    match context.input.recv()? {
-        Value::Struct(s) => {
-            let cfg = parse(&s.local_signature(), context.arguments)?;
-            let output_type = get_output_type(&s.local_signature(), &cfg)?;
-            let output = context.output.initialize(output_type)?;
-            run(cfg, s, output, &context.printer)
-        }
-        _ => argument_error("Expected a struct"),
+pub fn join(mut context: CommandContext) -> CrushResult<()> {
+    if let Value::Struct(s) = context.input.recv()? {
+        let arguments = std::mem::take(&mut context.arguments);
+        return join_struct(s, arguments, context);
     }
+    let cfg: Join = Join::parse(std::mem::take(&mut context.arguments), &context.printer)?;
+    let mut left_fields = cfg.on.clone();
+    left_fields.extend(cfg.left_on);
+    let mut right_fields = cfg.on;
+    right_fields.extend(cfg.right_on);
+    run(
+        Kind::parse(&cfg.kind)?,
+        cfg.left,
+        cfg.right,
+        left_fields,
+        right_fields,
+        context,
+    )
 }
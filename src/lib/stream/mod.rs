@@ -17,10 +17,12 @@ mod select;
 mod group;
 mod join;
 mod uniq;
+mod window;
 mod zip;
 
 mod count;
 mod seq;
+mod stats;
 mod sum_avg;
 
 pub fn declare(root: &Scope) -> CrushResult<()> {
@@ -41,10 +43,7 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "reverse", "Reverses the order of the rows in the io", None,
                 Passthrough)?;
             group::Group::declare(env)?;
-            env.declare_command(
-                "join", join::perform, true,
-                "join left:field right:field", "Join two streams together on the specified keys", None,
-                Unknown)?;
+            join::Join::declare(env)?;
             env.declare_command(
                 "uniq", uniq::uniq, true,
                 "uniq column:field",
@@ -75,6 +74,15 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "avg column:field",
                 "Calculate the average of the specific column across all rows",
                 example!("ps | avg ^cpu"), Unknown)?;
+            stats::Median::declare(env)?;
+            stats::Percentile::declare(env)?;
+            stats::Variance::declare(env)?;
+            stats::Stddev::declare(env)?;
+            stats::CountDistinct::declare(env)?;
+            stats::Mode::declare(env)?;
+            stats::First::declare(env)?;
+            stats::Last::declare(env)?;
+            stats::Histogram::declare(env)?;
             env.declare_command(
                 "select", select::select, true,
                 "select copy_fields:field... [%] new_field=definition:command",
@@ -85,6 +93,7 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "enumerate", "Prepend a column containing the row number to each row of the io", None, Unknown)?;
             zip::Zip::declare(env)?;
             seq::Seq::declare(env)?;
+            window::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
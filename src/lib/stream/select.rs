@@ -60,21 +60,28 @@ pub fn run(config: Config, mut input: Stream, context: CommandContext) -> CrushR
                             scope: context.scope.clone(),
                             this: None,
                             printer: context.printer.clone(),
+                            background: context.background,
                         })?;
                         receiver.recv()?
                     }
                     Source::Argument(idx) => row.cells()[*idx].clone(),
                 };
+                // Copied columns keep their type, computed columns get the type of their
+                // first value
+                let value_type = match source {
+                    Source::Argument(idx) => input_type[*idx].cell_type.clone(),
+                    Source::Closure(_) => value.value_type(),
+                };
 
                 match location {
                     Location::Append(name) => {
-                        output_type.push(ColumnType::new(name.as_ref(), value.value_type()));
+                        output_type.push(ColumnType::new(name.as_ref(), value_type));
                         first_result.push(value);
                     }
                     Location::Replace(idx) => {
                         output_type.replace(
                             *idx,
-                            ColumnType::new(output_type[*idx].name.as_ref(), value.value_type()),
+                            ColumnType::new(output_type[*idx].name.as_ref(), value_type),
                         );
                         first_result[*idx] = value;
                     }
@@ -110,6 +117,7 @@ pub fn run(config: Config, mut input: Stream, context: CommandContext) -> CrushR
                         scope: context.scope.clone(),
                         this: None,
                         printer: context.printer.clone(),
+                        background: context.background,
                     })?;
                     receiver.recv()?
                 }
@@ -1,32 +1,183 @@
-use crate::lang::argument::ArgumentHandler;
+use crate::lang::argument::{Argument, ArgumentHandler};
 use crate::lang::command::OutputType::Passthrough;
-use crate::lang::errors::{error, CrushResult};
+use crate::lang::errors::{error, mandate, CrushResult};
 use crate::lang::execution_context::CommandContext;
 use crate::lang::stream::CrushStream;
 use crate::lang::table::ColumnVec;
 use crate::lang::table::Row;
-use crate::lang::value::Field;
+use crate::lang::value::{Field, Value};
 use crate::{lang::errors::argument_error, lang::stream::OutputStream};
 use signature::signature;
+use std::cmp::Ordering;
 
 #[signature(
     sort,
     can_block=true,
-    short="Sort io based on column",
-    long="ps | sort ^cpu",
+    short="Sort io based on one or more columns",
+    long="Rows are compared on the first key, then on the second key if the first keys are
+    equal, and so on. Keys are used in the order they are given, whether they are ascending or
+    descending. The sort is stable, so rows with equal keys keep their order.
+
+    Any two values can be compared. Integers and floats are compared by their numerical value,
+    NaN and empty values sort after all other values, and values of other different types are
+    ordered by the name of their type, with integers and floats sorting as \"number\".",
+    example="ps | sort descending=^cpu ^name",
     output=Passthrough)]
 pub struct Sort {
-    #[description("the column to sort on. Not required if there is only one column.")]
-    field: Option<Field>,
+    #[unnamed()]
+    #[description("the columns to sort on in ascending order. Not required if there is only one column.")]
+    field: Vec<Field>,
+    #[description("the columns to sort on in descending order.")]
+    descending: Vec<Field>,
+    #[default(false)]
+    #[description("reverse the order of all keys.")]
+    reverse: bool,
+    #[default(false)]
+    #[description("compare sequences of digits in strings by their numerical value, so that \"file2\" comes before \"file10\".")]
+    natural: bool,
+    #[default(false)]
+    #[description("compare strings without regard to case.")]
+    ignore_case: bool,
+}
+
+/// How to compare two strings.
+#[derive(Clone, Copy, Default)]
+pub struct Collation {
+    pub natural: bool,
+    pub ignore_case: bool,
+}
+
+impl Collation {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        match (self.natural, self.ignore_case) {
+            (false, false) => a.cmp(b),
+            (false, true) => a.to_lowercase().cmp(&b.to_lowercase()),
+            (true, false) => natural_compare(a, b),
+            (true, true) => natural_compare(&a.to_lowercase(), &b.to_lowercase()),
+        }
+    }
+}
+
+/// Split a string into alternating runs of digits and non-digits.
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let len = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(len);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+/// Compare two strings, treating each run of digits as a number.
+fn natural_compare(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+    loop {
+        let res = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                if x.starts_with(|c: char| c.is_ascii_digit())
+                    && y.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    let x = x.trim_start_matches('0');
+                    let y = y.trim_start_matches('0');
+                    x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                } else {
+                    x.cmp(y)
+                }
+            }
+        };
+        if res != Ordering::Equal {
+            return res;
+        }
+    }
+}
+
+/// Compare two floats, with NaN after all other numbers.
+fn float_compare(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// A total order of all values, used for sorting.
+pub fn compare(a: &Value, b: &Value, collation: Collation) -> Ordering {
+    match (a, b) {
+        (Value::Empty(), Value::Empty()) => Ordering::Equal,
+        (Value::Empty(), _) => Ordering::Greater,
+        (_, Value::Empty()) => Ordering::Less,
+        (Value::String(x), Value::String(y)) => collation.compare(x, y),
+        (Value::Float(x), Value::Float(y)) => float_compare(*x, *y),
+        (Value::Integer(x), Value::Float(y)) => float_compare(*x as f64, *y),
+        (Value::Float(x), Value::Integer(y)) => float_compare(*x, *y as f64),
+        _ => match a.partial_cmp(b) {
+            Some(ordering) => ordering,
+            None => type_rank(a).cmp(&type_rank(b)),
+        },
+    }
+}
+
+/// The order of values of different types. Integers and floats share a rank, since they are
+/// compared by their numerical value.
+fn type_rank(value: &Value) -> String {
+    match value {
+        Value::Integer(_) | Value::Float(_) => "number".to_string(),
+        value => value.value_type().to_string(),
+    }
+}
+
+/// Whether each sort key is descending, in the order the keys were given. Ascending and
+/// descending keys are parsed into separate lists, so this is needed to interleave them.
+fn directions(arguments: &[Argument]) -> Vec<bool> {
+    let mut res = Vec::new();
+    for argument in arguments {
+        let descending = match argument.argument_type.as_deref() {
+            None => false,
+            Some("descending") => true,
+            Some(_) => continue,
+        };
+        let count = match &argument.value {
+            Value::List(list) => list.len(),
+            _ => 1,
+        };
+        res.resize(res.len() + count, descending);
+    }
+    res
 }
 
-pub fn run(idx: usize, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+pub fn run(
+    keys: &[(usize, bool)],
+    collation: Collation,
+    input: &mut dyn CrushStream,
+    output: OutputStream,
+) -> CrushResult<()> {
     let mut res: Vec<Row> = Vec::new();
     while let Ok(row) = input.read() {
         res.push(row);
     }
 
-    res.sort_by(|a, b| a.cells()[idx].partial_cmp(&b.cells()[idx]).expect("OH NO!"));
+    res.sort_by(|a, b| {
+        keys.iter()
+            .map(|(idx, descending)| {
+                let ordering = compare(&a.cells()[*idx], &b.cells()[*idx], collation);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
 
     for row in res {
         output.send(row)?;
@@ -39,24 +190,82 @@ pub fn sort(context: CommandContext) -> CrushResult<()> {
     match context.input.recv()?.stream() {
         Some(mut input) => {
             let output = context.output.initialize(input.types().to_vec())?;
+            let directions = directions(&context.arguments);
             let cfg: Sort = Sort::parse(context.arguments, &context.printer)?;
-            let idx = match cfg.field {
-                None => {
-                    if input.types().len() == 1 {
-                        0
-                    } else {
-                        return argument_error("Missing comparison key");
-                    }
+            let mut ascending = cfg.field.into_iter();
+            let mut descending = cfg.descending.into_iter();
+            let mut keys = Vec::with_capacity(directions.len());
+            if directions.is_empty() {
+                if input.types().len() == 1 {
+                    keys.push((0, cfg.reverse));
+                } else {
+                    return argument_error("Missing comparison key");
                 }
-                Some(field) => input.types().find(&field)?,
-            };
-
-            if input.types()[idx].cell_type.is_comparable() {
-                run(idx, input.as_mut(), output)
-            } else {
-                argument_error("Bad comparison key")
             }
+            for is_descending in directions {
+                let field = if is_descending {
+                    descending.next()
+                } else {
+                    ascending.next()
+                };
+                let idx = input.types().find(&mandate(field, "Invalid sort key")?)?;
+                if !input.types()[idx].cell_type.is_comparable() {
+                    return argument_error("Bad comparison key");
+                }
+                keys.push((idx, is_descending != cfg.reverse));
+            }
+            let collation = Collation {
+                natural: cfg.natural,
+                ignore_case: cfg.ignore_case,
+            };
+            run(&keys, collation, input.as_mut(), output)
         }
         None => error("Expected a stream"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural_compare("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_compare("1.10.0", "1.9.3"), Ordering::Greater);
+        assert_eq!(natural_compare("a", "a1"), Ordering::Less);
+        assert_eq!(natural_compare("x007", "x7"), Ordering::Less);
+        assert_eq!(natural_compare("x7", "x7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn total_order() {
+        let collation = Collation::default();
+        assert_eq!(
+            compare(&Value::Float(f64::NAN), &Value::Float(1.0), collation),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&Value::Integer(2), &Value::Float(1.5), collation),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&Value::Empty(), &Value::string("a"), collation),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&Value::Bool(true), &Value::string("a"), collation),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare(
+                &Value::string("B"),
+                &Value::string("a"),
+                Collation {
+                    natural: false,
+                    ignore_case: true
+                }
+            ),
+            Ordering::Greater
+        );
+    }
+}
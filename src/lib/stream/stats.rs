@@ -0,0 +1,442 @@
+use super::sort::{compare, Collation};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{argument_error, error, mandate, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::stream::Stream;
+use crate::lang::stream::ValueReceiver;
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueKey, ValueType};
+use chrono::{DateTime, Duration, Local};
+use signature::signature;
+use std::collections::{HashMap, HashSet};
+
+/// Read the input stream of a command and find the column to operate on, which may be omitted if
+/// there is only one column.
+fn input(input: &ValueReceiver, column: Option<Field>) -> CrushResult<(Stream, usize)> {
+    let input = mandate(input.recv()?.stream(), "Expected a stream")?;
+    let idx = match column {
+        Some(field) => input.types().find(&field)?,
+        None if input.types().len() == 1 => 0,
+        None => return argument_error("Specify which column to operate on"),
+    };
+    Ok((input, idx))
+}
+
+/// Check that the column contains values that can be placed on a number line.
+fn check_numeric(input: &Stream, idx: usize, operation: &str) -> CrushResult<ValueType> {
+    match &input.types()[idx].cell_type {
+        ValueType::Integer | ValueType::Float | ValueType::Duration | ValueType::Time => {
+            Ok(input.types()[idx].cell_type.clone())
+        }
+        t => argument_error(
+            format!("Can't calculate {} of elements of type {}", operation, t).as_str(),
+        ),
+    }
+}
+
+fn check_hashable(input: &Stream, idx: usize, operation: &str) -> CrushResult<()> {
+    let t = &input.types()[idx].cell_type;
+    if t.is_hashable() {
+        Ok(())
+    } else {
+        argument_error(format!("Can't calculate {} of elements of type {}", operation, t).as_str())
+    }
+}
+
+/// All non-empty values of a column.
+fn read_values(mut input: Stream, idx: usize) -> Vec<Value> {
+    let mut res = Vec::new();
+    while let Ok(row) = input.read() {
+        match &row.cells()[idx] {
+            Value::Empty() => {}
+            value => res.push(value.clone()),
+        }
+    }
+    res
+}
+
+fn nanoseconds(d: Duration) -> f64 {
+    match d.num_nanoseconds() {
+        Some(n) => n as f64,
+        None => d.num_milliseconds() as f64 * 1_000_000.0,
+    }
+}
+
+fn from_nanoseconds(n: f64) -> Duration {
+    if n.abs() < i64::MAX as f64 {
+        Duration::nanoseconds(n as i64)
+    } else {
+        Duration::milliseconds((n / 1_000_000.0) as i64)
+    }
+}
+
+/// The position of a value on a number line. Durations are measured in nanoseconds, and times
+/// in nanoseconds since the origin, which is set to the first time seen.
+fn offset(value: &Value, origin: &mut Option<DateTime<Local>>) -> CrushResult<f64> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Duration(d) => Ok(nanoseconds(*d)),
+        Value::Time(t) => Ok(nanoseconds(*t - *origin.get_or_insert(*t))),
+        _ => error("Invalid cell value"),
+    }
+}
+
+/// The value at the specified fraction of the distance between two values.
+fn interpolate(a: &Value, b: &Value, fraction: f64) -> CrushResult<Value> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => {
+            Ok(Value::Float(*a as f64 + (*b - *a) as f64 * fraction))
+        }
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + (b - a) * fraction)),
+        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(
+            *a + from_nanoseconds(nanoseconds(*b - *a) * fraction),
+        )),
+        (Value::Time(a), Value::Time(b)) => Ok(Value::Time(
+            *a + from_nanoseconds(nanoseconds(*b - *a) * fraction),
+        )),
+        _ => error("Invalid cell value"),
+    }
+}
+
+/// The p-quantile of the values, interpolating linearly between the two closest values.
+fn quantile(mut values: Vec<Value>, p: f64) -> CrushResult<Value> {
+    if !(0.0..=1.0).contains(&p) {
+        return argument_error("The percentile must be between 0 and 1");
+    }
+    if values.is_empty() {
+        return Ok(Value::Empty());
+    }
+    values.sort_by(|a, b| compare(a, b, Collation::default()));
+    let rank = p * (values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = std::cmp::min(lower + 1, values.len() - 1);
+    interpolate(&values[lower], &values[upper], rank - lower as f64)
+}
+
+#[signature(
+    median,
+    can_block = true,
+    short = "Calculate the median of the specified column across all rows",
+    long = "The median of an even number of values is the average of the two middle values, so the median of integers is a float.",
+    example = "ps | median ^cpu"
+)]
+pub struct Median {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+}
+
+fn median(context: CommandContext) -> CrushResult<()> {
+    let cfg: Median = Median::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    check_numeric(&input, idx, "median")?;
+    context.output.send(quantile(read_values(input, idx), 0.5)?)
+}
+
+#[signature(
+    percentile,
+    can_block = true,
+    short = "Calculate a percentile of the specified column across all rows",
+    long = "Values between two rows are interpolated linearly, so the percentile of integers is a float.",
+    example = "ps | percentile ^cpu p=0.99"
+)]
+pub struct Percentile {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+    #[description("the percentile, between 0 and 1.")]
+    p: f64,
+}
+
+fn percentile(context: CommandContext) -> CrushResult<()> {
+    let cfg: Percentile = Percentile::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    check_numeric(&input, idx, "percentile")?;
+    context
+        .output
+        .send(quantile(read_values(input, idx), cfg.p)?)
+}
+
+/// The number of non-empty values of a column and the sum of their squared distance from the
+/// mean, calculated in a single pass using Welford's algorithm.
+fn spread(mut input: Stream, idx: usize) -> CrushResult<(usize, f64)> {
+    let mut origin = None;
+    let mut count = 0;
+    let mut mean = 0.0;
+    let mut sum_of_squares = 0.0;
+    while let Ok(row) = input.read() {
+        if let Value::Empty() = row.cells()[idx] {
+            continue;
+        }
+        let x = offset(&row.cells()[idx], &mut origin)?;
+        count += 1;
+        let delta = x - mean;
+        mean += delta / count as f64;
+        sum_of_squares += delta * (x - mean);
+    }
+    Ok((count, sum_of_squares))
+}
+
+/// The variance of the values, or None if there are too few values to calculate it.
+fn variance_of(input: Stream, idx: usize, population: bool) -> CrushResult<Option<f64>> {
+    let (count, sum_of_squares) = spread(input, idx)?;
+    let degrees_of_freedom = if population {
+        count
+    } else {
+        count.saturating_sub(1)
+    };
+    if degrees_of_freedom == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(sum_of_squares / degrees_of_freedom as f64))
+    }
+}
+
+#[signature(
+    variance,
+    can_block = true,
+    short = "Calculate the variance of the specified column across all rows",
+    long = "This is the sample variance, unless --population is specified. The variance of fewer than two values is empty.",
+    example = "ps | variance ^cpu"
+)]
+pub struct Variance {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+    #[default(false)]
+    #[description("calculate the population variance instead of the sample variance.")]
+    population: bool,
+}
+
+fn variance(context: CommandContext) -> CrushResult<()> {
+    let cfg: Variance = Variance::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    match check_numeric(&input, idx, "variance")? {
+        ValueType::Integer | ValueType::Float => context.output.send(
+            variance_of(input, idx, cfg.population)?
+                .map(Value::Float)
+                .unwrap_or(Value::Empty()),
+        ),
+        t => argument_error(
+            format!(
+                "Can't calculate variance of elements of type {}, use stddev instead",
+                t
+            )
+            .as_str(),
+        ),
+    }
+}
+
+#[signature(
+    stddev,
+    can_block = true,
+    short = "Calculate the standard deviation of the specified column across all rows",
+    long = "This is the sample standard deviation, unless --population is specified. The standard deviation of durations and times is a duration. The standard deviation of fewer than two values is empty.",
+    example = "ps | stddev ^cpu"
+)]
+pub struct Stddev {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+    #[default(false)]
+    #[description(
+        "calculate the population standard deviation instead of the sample standard deviation."
+    )]
+    population: bool,
+}
+
+fn stddev(context: CommandContext) -> CrushResult<()> {
+    let cfg: Stddev = Stddev::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    let value_type = check_numeric(&input, idx, "standard deviation")?;
+    let res = variance_of(input, idx, cfg.population)?.map(f64::sqrt);
+    context.output.send(match (res, value_type) {
+        (None, _) => Value::Empty(),
+        (Some(res), ValueType::Integer) | (Some(res), ValueType::Float) => Value::Float(res),
+        (Some(res), _) => Value::Duration(from_nanoseconds(res)),
+    })
+}
+
+#[signature(
+    count_distinct,
+    can_block = true,
+    short = "Count the number of distinct values in the specified column",
+    long = "Empty values are not counted.",
+    example = "ps | count_distinct ^user",
+    output = Known(ValueType::Integer)
+)]
+pub struct CountDistinct {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+}
+
+fn count_distinct(context: CommandContext) -> CrushResult<()> {
+    let cfg: CountDistinct = CountDistinct::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    check_hashable(&input, idx, "count_distinct")?;
+    let distinct: HashSet<Value> = read_values(input, idx).into_iter().collect();
+    context.output.send(Value::Integer(distinct.len() as i128))
+}
+
+#[signature(
+    mode,
+    can_block = true,
+    short = "Find the most common value in the specified column",
+    long = "If several values are equally common, the one that appears first is picked. Empty values are ignored.",
+    example = "ps | mode ^user"
+)]
+pub struct Mode {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+}
+
+fn mode(context: CommandContext) -> CrushResult<()> {
+    let cfg: Mode = Mode::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    check_hashable(&input, idx, "mode")?;
+    let values = read_values(input, idx);
+    // The index of the first occurrence and the number of occurrences of every value
+    let mut counts: HashMap<ValueKey, (usize, usize)> = HashMap::new();
+    for (idx, value) in values.iter().enumerate() {
+        counts.entry(ValueKey::new(value)).or_insert((idx, 0)).1 += 1;
+    }
+    let res = counts
+        .values()
+        .max_by(|(idx1, count1), (idx2, count2)| count1.cmp(count2).then(idx2.cmp(idx1)));
+    context.output.send(
+        res.map(|(idx, _)| values[*idx].clone())
+            .unwrap_or(Value::Empty()),
+    )
+}
+
+#[signature(
+    first,
+    can_block = true,
+    short = "The value of the specified column in the first row",
+    example = "ps | first ^pid"
+)]
+pub struct First {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+}
+
+fn first(context: CommandContext) -> CrushResult<()> {
+    let cfg: First = First::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, cfg.column)?;
+    context.output.send(match input.read() {
+        Ok(row) => row.into_vec().swap_remove(idx),
+        Err(_) => Value::Empty(),
+    })
+}
+
+#[signature(
+    last,
+    can_block = true,
+    short = "The value of the specified column in the last row",
+    example = "ps | last ^pid"
+)]
+pub struct Last {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+}
+
+fn last(context: CommandContext) -> CrushResult<()> {
+    let cfg: Last = Last::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, cfg.column)?;
+    let mut res = Value::Empty();
+    while let Ok(row) = input.read() {
+        res = row.into_vec().swap_remove(idx);
+    }
+    context.output.send(res)
+}
+
+#[signature(
+    histogram,
+    can_block = true,
+    short = "Count the number of values of the specified column in equally wide buckets",
+    long = "The buckets span from the smallest to the largest value. Each bucket includes its lower bound, and the last bucket also includes the largest value. The bounds of buckets of integers are floats.",
+    example = "ps | histogram ^cpu buckets=5"
+)]
+pub struct Histogram {
+    #[description("the column to operate on. Not required if there is only one column.")]
+    column: Option<Field>,
+    #[default(10usize)]
+    #[description("the number of buckets.")]
+    buckets: usize,
+}
+
+fn histogram(context: CommandContext) -> CrushResult<()> {
+    let cfg: Histogram = Histogram::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, cfg.column)?;
+    let value_type = check_numeric(&input, idx, "histogram")?;
+    if cfg.buckets == 0 {
+        return argument_error("Expected at least one bucket");
+    }
+    let bound_type = match value_type {
+        ValueType::Integer => ValueType::Float,
+        t => t,
+    };
+    let output = context.output.initialize(vec![
+        ColumnType::new("from", bound_type.clone()),
+        ColumnType::new("to", bound_type),
+        ColumnType::new("count", ValueType::Integer),
+    ])?;
+
+    let mut values = read_values(input, idx);
+    values.retain(|v| !matches!(v, Value::Float(f) if f.is_nan()));
+    values.sort_by(|a, b| compare(a, b, Collation::default()));
+    let (min, max) = match (values.first(), values.last()) {
+        (Some(min), Some(max)) => (min.clone(), max.clone()),
+        _ => return Ok(()),
+    };
+
+    let mut origin = None;
+    let start = offset(&min, &mut origin)?;
+    let width = (offset(&max, &mut origin)? - start) / cfg.buckets as f64;
+    let mut counts = vec![0i128; cfg.buckets];
+    for value in &values {
+        let bucket = if width > 0.0 {
+            ((offset(value, &mut origin)? - start) / width) as usize
+        } else {
+            0
+        };
+        counts[std::cmp::min(bucket, cfg.buckets - 1)] += 1;
+    }
+
+    for (bucket, count) in counts.into_iter().enumerate() {
+        output.send(Row::new(vec![
+            interpolate(&min, &max, bucket as f64 / cfg.buckets as f64)?,
+            interpolate(&min, &max, (bucket + 1) as f64 / cfg.buckets as f64)?,
+            Value::Integer(count),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_interpolates() {
+        let values = vec![
+            Value::Integer(4),
+            Value::Integer(1),
+            Value::Integer(2),
+            Value::Integer(3),
+        ];
+        assert!(quantile(values.clone(), 0.5).unwrap() == Value::Float(2.5));
+        assert!(quantile(values.clone(), 0.0).unwrap() == Value::Float(1.0));
+        assert!(quantile(values, 1.0).unwrap() == Value::Float(4.0));
+        assert!(quantile(vec![Value::Integer(1)], 1.5).is_err());
+    }
+
+    #[test]
+    fn durations_interpolate() {
+        let res = interpolate(
+            &Value::Duration(Duration::seconds(1)),
+            &Value::Duration(Duration::seconds(3)),
+            0.25,
+        )
+        .unwrap();
+        assert!(res == Value::Duration(Duration::milliseconds(1500)));
+    }
+}
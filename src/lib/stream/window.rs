@@ -0,0 +1,569 @@
+use super::sort::{compare, Collation};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::errors::{argument_error, error, mandate, CrushResult};
+use crate::lang::execution_context::CommandContext;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{Stream, ValueReceiver, ValueSender};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueKey, ValueType};
+use chrono::{DateTime, Duration, Local};
+use signature::signature;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+
+fn input(input: &ValueReceiver) -> CrushResult<Stream> {
+    mandate(input.recv()?.stream(), "Expected a stream")
+}
+
+/// The indices of the columns that rows are partitioned by.
+fn partition_columns(types: &[ColumnType], partition: &[Field]) -> CrushResult<Vec<usize>> {
+    let res = partition
+        .iter()
+        .map(|f| types.find(f))
+        .collect::<CrushResult<Vec<_>>>()?;
+    if res.iter().all(|idx| types[*idx].cell_type.is_hashable()) {
+        Ok(res)
+    } else {
+        argument_error(
+            "Can't partition on this column type. (It is either mutable or not comparable)",
+        )
+    }
+}
+
+fn partition_key(columns: &[usize], row: &Row) -> Vec<ValueKey> {
+    columns
+        .iter()
+        .map(|idx| ValueKey::new(&row.cells()[*idx]))
+        .collect()
+}
+
+/// Stream all rows of the input to the output, with one additional column. The value of the
+/// column is computed from the row and the state of the partition the row belongs to.
+fn append<S: Default>(
+    mut input: Stream,
+    output: ValueSender,
+    partition: &[Field],
+    column: ColumnType,
+    mut compute: impl FnMut(&mut S, &Row) -> CrushResult<Value>,
+) -> CrushResult<()> {
+    let partition = partition_columns(input.types(), partition)?;
+    let mut output_type = input.types().to_vec();
+    output_type.push(column);
+    let output = output.initialize(output_type)?;
+    let mut states: HashMap<Vec<ValueKey>, S> = HashMap::new();
+    while let Ok(mut row) = input.read() {
+        let state = states.entry(partition_key(&partition, &row)).or_default();
+        let value = compute(state, &row)?;
+        row.push(value);
+        output.send(row)?;
+    }
+    Ok(())
+}
+
+fn add(a: &Value, b: &Value) -> CrushResult<Value> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(*a + *b)),
+        _ => error("Invalid cell value"),
+    }
+}
+
+fn check_summable(types: &[ColumnType], idx: usize, operation: &str) -> CrushResult<()> {
+    match &types[idx].cell_type {
+        ValueType::Integer | ValueType::Float | ValueType::Duration => Ok(()),
+        t => argument_error(
+            format!("Can't calculate {} of elements of type {}", operation, t).as_str(),
+        ),
+    }
+}
+
+#[signature(
+    lag,
+    can_block = true,
+    short = "Append a column with the value of the specified column in a previous row",
+    long = "The new column has the type of the copied column, and is empty for the first rows of each partition.",
+    example = "journal | window:lag ^time | select % delta={time - lag}"
+)]
+pub struct Lag {
+    #[description("the column to copy.")]
+    column: Field,
+    #[default(1usize)]
+    #[description("the number of rows to look back.")]
+    offset: usize,
+    #[description("only look at previous rows with the same values in these columns.")]
+    partition: Vec<Field>,
+    #[default("lag")]
+    #[description("the name of the new column.")]
+    name: String,
+}
+
+fn lag(context: CommandContext) -> CrushResult<()> {
+    let cfg: Lag = Lag::parse(context.arguments, &context.printer)?;
+    let input = input(&context.input)?;
+    let idx = input.types().find(&cfg.column)?;
+    if cfg.offset == 0 {
+        return argument_error("The offset must be at least 1");
+    }
+    let column = ColumnType::new(&cfg.name, input.types()[idx].cell_type.clone());
+    append(
+        input,
+        context.output,
+        &cfg.partition,
+        column,
+        |previous: &mut VecDeque<Value>, row| {
+            let res = if previous.len() == cfg.offset {
+                previous.pop_front().unwrap_or(Value::Empty())
+            } else {
+                Value::Empty()
+            };
+            previous.push_back(row.cells()[idx].clone());
+            Ok(res)
+        },
+    )
+}
+
+#[signature(
+    lead,
+    can_block = true,
+    short = "Append a column with the value of the specified column in a later row",
+    long = "The new column has the type of the copied column, and is empty for the last rows of each partition. Rows are held back until the later row has been read, but are otherwise streamed in their original order.",
+    long = "Since the end of a partition is only known at the end of the input, a row whose partition has no more rows holds back all later rows until the input ends, and they are all buffered in memory.",
+    example = "journal | window:lead ^time"
+)]
+pub struct Lead {
+    #[description("the column to copy.")]
+    column: Field,
+    #[default(1usize)]
+    #[description("the number of rows to look ahead.")]
+    offset: usize,
+    #[description("only look at later rows with the same values in these columns.")]
+    partition: Vec<Field>,
+    #[default("lead")]
+    #[description("the name of the new column.")]
+    name: String,
+}
+
+fn lead(context: CommandContext) -> CrushResult<()> {
+    let cfg: Lead = Lead::parse(context.arguments, &context.printer)?;
+    let mut input = input(&context.input)?;
+    let idx = input.types().find(&cfg.column)?;
+    if cfg.offset == 0 {
+        return argument_error("The offset must be at least 1");
+    }
+    let partition = partition_columns(input.types(), &cfg.partition)?;
+    let mut output_type = input.types().to_vec();
+    output_type.push(ColumnType::new(
+        &cfg.name,
+        input.types()[idx].cell_type.clone(),
+    ));
+    let output = context.output.initialize(output_type)?;
+
+    // Rows that have not been sent yet, in input order, with their lead value once it is known.
+    // The first row in the queue has the number sent.
+    let mut pending: VecDeque<(Row, Option<Value>)> = VecDeque::new();
+    let mut sent = 0;
+    // The numbers of the rows of each partition that are still waiting for their lead value.
+    let mut waiting: HashMap<Vec<ValueKey>, VecDeque<usize>> = HashMap::new();
+
+    while let Ok(row) = input.read() {
+        let number = sent + pending.len();
+        let queue = waiting.entry(partition_key(&partition, &row)).or_default();
+        queue.push_back(number);
+        if queue.len() > cfg.offset {
+            if let Some(earlier) = queue.pop_front() {
+                pending[earlier - sent].1 = Some(row.cells()[idx].clone());
+            }
+        }
+        pending.push_back((row, None));
+        while let Some((_, Some(_))) = pending.front() {
+            if let Some((mut row, Some(value))) = pending.pop_front() {
+                row.push(value);
+                output.send(row)?;
+                sent += 1;
+            }
+        }
+    }
+
+    for (mut row, value) in pending {
+        row.push(value.unwrap_or(Value::Empty()));
+        output.send(row)?;
+    }
+    Ok(())
+}
+
+#[signature(
+    cumsum,
+    can_block = true,
+    short = "Append a column with the running total of the specified column",
+    example = "du | window:cumsum ^size"
+)]
+pub struct Cumsum {
+    #[description("the column to sum.")]
+    column: Field,
+    #[description("keep a separate total for each combination of values in these columns.")]
+    partition: Vec<Field>,
+    #[default("cumsum")]
+    #[description("the name of the new column.")]
+    name: String,
+}
+
+fn cumsum(context: CommandContext) -> CrushResult<()> {
+    let cfg: Cumsum = Cumsum::parse(context.arguments, &context.printer)?;
+    let input = input(&context.input)?;
+    let idx = input.types().find(&cfg.column)?;
+    check_summable(input.types(), idx, "sum")?;
+    let column = ColumnType::new(&cfg.name, input.types()[idx].cell_type.clone());
+    append(
+        input,
+        context.output,
+        &cfg.partition,
+        column,
+        |total: &mut Option<Value>, row| {
+            let res = match (total.take(), &row.cells()[idx]) {
+                (total, Value::Empty()) => total,
+                (None, value) => Some(value.clone()),
+                (Some(total), value) => Some(add(&total, value)?),
+            };
+            *total = res.clone();
+            Ok(res.unwrap_or(Value::Empty()))
+        },
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Aggregation {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregation::Sum => "sum",
+            Aggregation::Avg => "average",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+        }
+    }
+
+    fn output_type(self, input_type: &ValueType) -> CrushResult<ValueType> {
+        match (self, input_type) {
+            (Aggregation::Avg, ValueType::Integer) => Ok(ValueType::Float),
+            (Aggregation::Sum, t) | (Aggregation::Avg, t) => match t {
+                ValueType::Integer | ValueType::Float | ValueType::Duration => Ok(t.clone()),
+                t => argument_error(
+                    format!("Can't calculate {} of elements of type {}", self.name(), t).as_str(),
+                ),
+            },
+            (_, t) if t.is_comparable() => Ok(t.clone()),
+            (_, t) => argument_error(
+                format!("Can't calculate {} of elements of type {}", self.name(), t).as_str(),
+            ),
+        }
+    }
+
+    /// Aggregate all non-empty values. The aggregate of no values is empty.
+    fn aggregate<'a>(self, values: impl Iterator<Item = &'a Value>) -> CrushResult<Value> {
+        let mut values = values.filter(|v| !matches!(v, Value::Empty()));
+        let mut res = match values.next() {
+            Some(value) => value.clone(),
+            None => return Ok(Value::Empty()),
+        };
+        let mut count = 1;
+        for value in values {
+            count += 1;
+            res = match self {
+                Aggregation::Sum | Aggregation::Avg => add(&res, value)?,
+                Aggregation::Min => pick(res, value, Ordering::Less),
+                Aggregation::Max => pick(res, value, Ordering::Greater),
+            };
+        }
+        match (self, res) {
+            (Aggregation::Avg, Value::Integer(sum)) => Ok(Value::Float(sum as f64 / count as f64)),
+            (Aggregation::Avg, Value::Float(sum)) => Ok(Value::Float(sum / count as f64)),
+            (Aggregation::Avg, Value::Duration(sum)) => Ok(Value::Duration(sum / count)),
+            (_, res) => Ok(res),
+        }
+    }
+}
+
+/// Replace the current value with the new one if it compares in the specified way.
+fn pick(current: Value, new: &Value, ordering: Ordering) -> Value {
+    if compare(new, &current, Collation::default()) == ordering {
+        new.clone()
+    } else {
+        current
+    }
+}
+
+/// The values that are part of a rolling window, with the time of each row if the window is
+/// limited by a duration.
+#[derive(Default)]
+struct Window {
+    values: VecDeque<(Option<DateTime<Local>>, Value)>,
+}
+
+/// The arguments shared by all rolling aggregations.
+struct Rolling {
+    column: Field,
+    rows: Option<usize>,
+    duration: Option<Duration>,
+    time: Option<Field>,
+    partition: Vec<Field>,
+    name: String,
+}
+
+/// Declare the signature and command function of a rolling aggregation. Any additional long
+/// descriptions are appended to the description of the window shared by all of them.
+macro_rules! rolling_aggregation {
+    (
+        $signature:ident,
+        $name:ident,
+        $aggregation:expr,
+        $short:tt,
+        $example:tt,
+        $default_name:tt
+        $(, $long:tt)*
+    ) => {
+        #[signature(
+            $name,
+            can_block = true,
+            short = $short,
+            long = "The window ends at the current row, and is either the specified number of rows, or all rows less than the specified duration older than the current row. The rows must be sorted by the time column for the latter.",
+            $(long = $long,)*
+            example = $example
+        )]
+        pub struct $signature {
+            #[description("the column to aggregate.")]
+            column: Field,
+            #[description("the number of rows in the window.")]
+            rows: Option<usize>,
+            #[description("the length of time covered by the window.")]
+            duration: Option<Duration>,
+            #[description("the column with the time of each row, used together with duration.")]
+            time: Option<Field>,
+            #[description("use a separate window for each combination of values in these columns.")]
+            partition: Vec<Field>,
+            #[default($default_name)]
+            #[description("the name of the new column.")]
+            name: String,
+        }
+
+        fn $name(context: CommandContext) -> CrushResult<()> {
+            let cfg: $signature = $signature::parse(context.arguments, &context.printer)?;
+            rolling(
+                &context.input,
+                context.output,
+                $aggregation,
+                Rolling {
+                    column: cfg.column,
+                    rows: cfg.rows,
+                    duration: cfg.duration,
+                    time: cfg.time,
+                    partition: cfg.partition,
+                    name: cfg.name,
+                },
+            )
+        }
+    };
+}
+
+fn rolling(
+    input: &ValueReceiver,
+    output: ValueSender,
+    aggregation: Aggregation,
+    cfg: Rolling,
+) -> CrushResult<()> {
+    let input = self::input(input)?;
+    let idx = input.types().find(&cfg.column)?;
+    let rows = cfg.rows;
+    let duration = cfg.duration;
+    let time_idx = match (duration, cfg.time) {
+        (None, None) => None,
+        (Some(_), Some(time)) => {
+            let time_idx = input.types().find(&time)?;
+            if input.types()[time_idx].cell_type != ValueType::Time {
+                return argument_error("Expected the time column to be of type time");
+            }
+            Some(time_idx)
+        }
+        (Some(_), None) => return argument_error("A duration requires a time column"),
+        (None, Some(_)) => return argument_error("A time column requires a duration"),
+    };
+    if rows.is_none() && duration.is_none() {
+        return argument_error("Specify the size of the window using rows or duration");
+    }
+    if rows == Some(0) {
+        return argument_error("The window must be at least one row");
+    }
+    let column = ColumnType::new(
+        &cfg.name,
+        aggregation.output_type(&input.types()[idx].cell_type)?,
+    );
+    append(
+        input,
+        output,
+        &cfg.partition,
+        column,
+        |window: &mut Window, row| {
+            let now = match time_idx.map(|time_idx| &row.cells()[time_idx]) {
+                Some(Value::Time(t)) => Some(*t),
+                Some(_) => return error("Invalid cell value, expected a time"),
+                None => None,
+            };
+            window.values.push_back((now, row.cells()[idx].clone()));
+            if let Some(rows) = rows {
+                while window.values.len() > rows {
+                    window.values.pop_front();
+                }
+            }
+            if let (Some(duration), Some(now)) = (duration, now) {
+                while let Some((Some(t), _)) = window.values.front() {
+                    if *t > now - duration {
+                        break;
+                    }
+                    window.values.pop_front();
+                }
+            }
+            aggregation.aggregate(window.values.iter().map(|(_, value)| value))
+        },
+    )
+}
+
+rolling_aggregation!(
+    Sum,
+    sum,
+    Aggregation::Sum,
+    "Append a column with the sum of the specified column over a rolling window",
+    "journal | window:sum ^size duration=(duration:new minutes=5) time=^time",
+    "sum"
+);
+
+rolling_aggregation!(
+    Avg,
+    avg,
+    Aggregation::Avg,
+    "Append a column with the average of the specified column over a rolling window",
+    "ps | window:avg ^cpu rows=3",
+    "avg",
+    "The average of integers is a float."
+);
+
+rolling_aggregation!(
+    Min,
+    min,
+    Aggregation::Min,
+    "Append a column with the minimum of the specified column over a rolling window",
+    "ps | window:min ^cpu rows=3",
+    "min"
+);
+
+rolling_aggregation!(
+    Max,
+    max,
+    Aggregation::Max,
+    "Append a column with the maximum of the specified column over a rolling window",
+    "ps | window:max ^cpu rows=3",
+    "max"
+);
+
+#[signature(
+    rank,
+    can_block = true,
+    short = "Append a column with the rank of each row within its partition",
+    long = "The rows must already be sorted on the specified column. Rows with equal values share the same rank, and the next rank skips ahead by the number of rows that share it, so the ranks of the values 5, 7, 7 and 9 are 1, 2, 2 and 4.",
+    example = "csv:from scores.csv | sort descending=^score | window:rank ^score partition=^team"
+)]
+pub struct Rank {
+    #[description("the column that rows are sorted on.")]
+    column: Field,
+    #[description("rank rows separately for each combination of values in these columns.")]
+    partition: Vec<Field>,
+    #[default("rank")]
+    #[description("the name of the new column.")]
+    name: String,
+}
+
+#[derive(Default)]
+struct RankState {
+    rows: i128,
+    rank: i128,
+    previous: Option<Value>,
+}
+
+fn rank(context: CommandContext) -> CrushResult<()> {
+    let cfg: Rank = Rank::parse(context.arguments, &context.printer)?;
+    let input = input(&context.input)?;
+    let idx = input.types().find(&cfg.column)?;
+    append(
+        input,
+        context.output,
+        &cfg.partition,
+        ColumnType::new(&cfg.name, ValueType::Integer),
+        |state: &mut RankState, row| {
+            let value = &row.cells()[idx];
+            state.rows += 1;
+            if state
+                .previous
+                .as_ref()
+                .map(|p| compare(p, value, Collation::default()))
+                != Some(Ordering::Equal)
+            {
+                state.rank = state.rows;
+                state.previous = Some(value.clone());
+            }
+            Ok(Value::Integer(state.rank))
+        },
+    )
+}
+
+#[signature(
+    row_number,
+    can_block = true,
+    short = "Append a column with the number of each row within its partition, starting at 1",
+    example = "ps | window:row_number partition=^user"
+)]
+pub struct RowNumber {
+    #[description("number rows separately for each combination of values in these columns.")]
+    partition: Vec<Field>,
+    #[default("row_number")]
+    #[description("the name of the new column.")]
+    name: String,
+}
+
+fn row_number(context: CommandContext) -> CrushResult<()> {
+    let cfg: RowNumber = RowNumber::parse(context.arguments, &context.printer)?;
+    let input = input(&context.input)?;
+    append(
+        input,
+        context.output,
+        &cfg.partition,
+        ColumnType::new(&cfg.name, ValueType::Integer),
+        |rows: &mut i128, _| {
+            *rows += 1;
+            Ok(Value::Integer(*rows))
+        },
+    )
+}
+
+pub fn declare(stream: &mut ScopeLoader) -> CrushResult<()> {
+    stream.create_namespace(
+        "window",
+        Box::new(move |env| {
+            Lag::declare(env)?;
+            Lead::declare(env)?;
+            Cumsum::declare(env)?;
+            Sum::declare(env)?;
+            Avg::declare(env)?;
+            Min::declare(env)?;
+            Max::declare(env)?;
+            Rank::declare(env)?;
+            RowNumber::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use crate::lang::command::Command;
 use crate::lang::command::OutputType::Known;
-use crate::lang::errors::{mandate, CrushResult};
+use crate::lang::errors::{data_error, mandate, to_crush_error, CrushResult};
 use crate::lang::execution_context::{ArgumentVector, This};
 use crate::lang::value::ValueType;
 use crate::lang::{execution_context::CommandContext, value::Value};
@@ -8,6 +8,7 @@ use lazy_static::lazy_static;
 use ordered_map::OrderedMap;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
+use std::io::Read;
 
 lazy_static! {
     pub static ref METHODS: OrderedMap<String, Command> = {
@@ -15,10 +16,23 @@ lazy_static! {
         let path = vec!["global", "types", "binary"];
         Len::declare_method(&mut res, &path);
         GetItem::declare_method(&mut res, &path);
+        ToString::declare_method(&mut res, &path);
         res
     };
 }
 
+/// The bytes of this binary, reading binary streams to the end.
+fn data(this: Option<Value>) -> CrushResult<Vec<u8>> {
+    match this {
+        Some(Value::BinaryStream(mut stream)) => {
+            let mut res = Vec::new();
+            to_crush_error(stream.read_to_end(&mut res))?;
+            Ok(res)
+        }
+        this => this.binary(),
+    }
+}
+
 #[signature(
 len,
 can_block = false,
@@ -28,7 +42,7 @@ short = "The number of bytes in the binary.",
 struct Len {}
 
 fn len(context: CommandContext) -> CrushResult<()> {
-    let val = context.this.binary()?;
+    let val = data(context.this)?;
     context.output.send(Value::Integer(val.len() as i128))
 }
 
@@ -45,8 +59,28 @@ struct GetItem {
 
 fn __getitem__(context: CommandContext) -> CrushResult<()> {
     let cfg: GetItem = GetItem::parse(context.arguments, &context.printer)?;
-    let val = context.this.binary()?;
+    let val = data(context.this)?;
     context.output.send(Value::Integer(
         *mandate(val.get(cfg.index), "Index out of bounds")? as i128,
     ))
 }
+
+#[signature(
+to_string,
+can_block = true,
+output = Known(ValueType::String),
+short = "The string encoded by this binary as UTF-8.",
+long = "Use encoding:charset:decode to decode binaries using another character set.",
+example = "(bin:from Cargo.toml):to_string"
+)]
+struct ToString {}
+
+fn to_string(context: CommandContext) -> CrushResult<()> {
+    match String::from_utf8(data(context.this)?) {
+        Ok(s) => context.output.send(Value::String(s)),
+        Err(e) => data_error(format!(
+            "Invalid UTF-8 at byte {}",
+            e.utf8_error().valid_up_to()
+        )),
+    }
+}
@@ -165,6 +165,7 @@ lazy_static! {
                 Known(ValueType::Integer),
             );
             IsDigit::declare_method(&mut res, &path);
+            ToBinary::declare_method(&mut res, &path);
             res
         };
 }
@@ -321,3 +322,18 @@ fn is_digit(context: CommandContext) -> CrushResult<()> {
         s.chars().all(|ch| ch.is_digit(cfg.radix as u32)),
     ))
 }
+
+#[signature(
+    to_binary,
+    can_block = false,
+    output = Known(ValueType::Binary),
+    short = "The UTF-8 encoded bytes of this string",
+    long = "Use encoding:charset:encode to encode a string using another character set.",
+    example = "\"hello\":to_binary | encoding:base64:encode"
+)]
+struct ToBinary {}
+
+fn to_binary(context: CommandContext) -> CrushResult<()> {
+    let s = context.this.string()?;
+    context.output.send(Value::Binary(s.into_bytes()))
+}
@@ -7,23 +7,85 @@ mod util;
 
 use rustyline;
 
+use crate::lang::argument::Argument;
 use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::execution_context::CommandContext;
 use crate::lang::pretty_printer::create_pretty_printer;
 use crate::lang::printer::Printer;
 use crate::lang::scope::Scope;
-use crate::lang::stream::ValueSender;
-use crate::lang::{execute, printer};
-use crate::util::file::home;
+use crate::lang::stream::{channels, empty_channel, ValueSender};
+use crate::lang::value::Value;
+use crate::lang::history::HistoryEntry;
+use crate::lang::{editor, execute, history, jobs, parser, printer};
+use crate::util::file::{config_dir, cwd};
+use chrono::Local;
 use lib::declare;
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
 use std::io::Read;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 
-fn crush_history_file() -> PathBuf {
-    home()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join(".crush_history")
+const DEFAULT_PROMPT: &str = "crush# ";
+
+/**
+    Render the prompt by calling the closure in the prompt variable, if there is one. The
+    closure receives the current working directory, the current user, the exit status of the
+    last external command and the number of running background jobs as named arguments.
+*/
+fn prompt(global_env: &Scope, printer: &Printer) -> String {
+    let closure = match global_env.get("prompt") {
+        Ok(Some(Value::Command(closure))) => closure,
+        _ => return DEFAULT_PROMPT.to_string(),
+    };
+    let (sender, receiver) = channels();
+    let arguments = vec![
+        Argument::named(
+            "cwd",
+            Value::File(cwd().unwrap_or_else(|_| PathBuf::from("/"))),
+        ),
+        Argument::named(
+            "user",
+            Value::string(
+                &users::get_current_username()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+        ),
+        Argument::named("status", Value::Integer(lib::last_status() as i128)),
+        Argument::named("jobs", Value::Integer(jobs::running() as i128)),
+    ];
+    let result = closure.invoke(CommandContext {
+        input: empty_channel(),
+        output: sender,
+        arguments,
+        scope: global_env.clone(),
+        this: None,
+        printer: printer.clone(),
+        background: false,
+    });
+    if let Err(err) = result {
+        printer.crush_error(err);
+        return DEFAULT_PROMPT.to_string();
+    }
+    match receiver.recv() {
+        Ok(Value::Empty()) | Err(_) => DEFAULT_PROMPT.to_string(),
+        Ok(value) => value.to_string(),
+    }
+}
+
+/// Execute the user's init file, if there is one.
+fn run_init_file(global_env: &Scope, printer: &Printer, pretty_printer: &ValueSender) {
+    if let Ok(dir) = config_dir() {
+        let file = dir.join("init.crush");
+        if file.exists() {
+            printer.handle_error(execute::file(
+                global_env.clone(),
+                &file,
+                printer,
+                pretty_printer,
+            ));
+        }
+    }
 }
 
 fn run_interactive(
@@ -34,18 +96,56 @@ fn run_interactive(
     printer.line("Welcome to Crush");
     printer.line(r#"Type "help" for... help."#);
 
-    let mut rl = Editor::<()>::new();
-    let _ = rl.load_history(&crush_history_file());
+    let mut rl = editor::editor(&global_env);
+    printer.handle_error(history::migrate());
+    match history::load(printer) {
+        Ok(entries) => {
+            let skip = entries.len().saturating_sub(history::EDITOR_ENTRIES);
+            for entry in entries.into_iter().skip(skip) {
+                rl.add_history_entry(entry.command);
+            }
+        }
+        Err(err) => printer.crush_error(err),
+    }
+    // Input that has been entered but not yet executed, because it is incomplete
+    let mut input = String::new();
     loop {
-        let readline = rl.readline("crush# ");
+        let readline = if input.is_empty() {
+            rl.readline(&prompt(&global_env, printer))
+        } else {
+            rl.readline("     > ")
+        };
 
         match readline {
-            Ok(cmd) if cmd.is_empty() => {}
+            Ok(cmd) if cmd.is_empty() && input.is_empty() => {}
             Ok(cmd) => {
-                rl.add_history_entry(&cmd);
-                execute::string(global_env.clone(), &cmd, &printer, pretty_printer);
+                if !input.is_empty() {
+                    input.push('\n');
+                }
+                input.push_str(&cmd);
+                if parser::is_incomplete(&input) {
+                    continue;
+                }
+                rl.add_history_entry(&input);
+                let (counting_printer, error_count) = printer.count_errors();
+                let status_count = lib::status_count();
+                let start = Local::now();
+                let dir = cwd().unwrap_or_else(|_| PathBuf::from("/"));
+                execute::string(global_env.clone(), &input, &counting_printer, pretty_printer);
+                // Output of external commands on stderr isn't counted as an error, only the
+                // exit status of the last one that ran is
+                let status_ok = lib::status_count() == status_count || lib::last_status() == 0;
+                printer.handle_error(history::append(&HistoryEntry {
+                    command: input.clone(),
+                    start: Some(start),
+                    duration: Some(Local::now() - start),
+                    cwd: Some(dir),
+                    success: Some(status_ok && error_count.load(Ordering::SeqCst) == 0),
+                }));
+                input.clear();
             }
             Err(ReadlineError::Interrupted) => {
+                input.clear();
                 printer.line("^C");
             }
             Err(ReadlineError::Eof) => {
@@ -57,10 +157,6 @@ fn run_interactive(
                 break;
             }
         }
-
-        if let Err(err) = rl.save_history(&crush_history_file()) {
-            printer.line(&format!("Error: Failed to save history: {}", err))
-        }
     }
     Ok(())
 }
@@ -74,7 +170,11 @@ fn run() -> CrushResult<()> {
 
     let args = std::env::args().collect::<Vec<_>>();
     match &args[..] {
-        [_exe] => run_interactive(my_scope, &printer, &pretty_printer)?,
+        [_exe] => {
+            let pretty_printer = pretty_printer.terminal();
+            run_init_file(&my_scope, &printer, &pretty_printer);
+            run_interactive(my_scope, &printer, &pretty_printer)?
+        }
         [_exe, arg] => {
             if arg == "--pup" {
                 let mut buff = Vec::new();
@@ -8,3 +8,8 @@ pub fn cwd() -> CrushResult<PathBuf> {
 pub fn home() -> CrushResult<PathBuf> {
     dirs::home_dir().ok_or_else(|| "Could not find users home directory".into())
 }
+
+/// The directory containing the configuration of Crush, ~/.config/crush.
+pub fn config_dir() -> CrushResult<PathBuf> {
+    Ok(home()?.join(".config").join("crush"))
+}